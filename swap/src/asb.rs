@@ -1,6 +1,13 @@
+pub mod cold_storage;
 pub mod command;
 pub mod config;
+pub mod hooks;
+pub mod limits;
+pub mod onion;
+pub mod price_feed;
 mod rate;
+pub mod refill;
+pub mod reload;
 pub mod tracing;
 pub mod transport;
 
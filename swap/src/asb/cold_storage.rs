@@ -0,0 +1,365 @@
+//! Addresses of a cold-storage wallet the ASB sends its Bitcoin to.
+//!
+//! The cold-storage wallet is given as output descriptor or xpub, the ASB only
+//! ever sees its public keys. Every address is derived at the next unused
+//! derivation index which is tracked in the database, so no address is handed
+//! out twice even across restarts.
+
+use crate::bitcoin::wallet::EstimateFeeRate;
+use crate::bitcoin::{Address, Amount, PartiallySignedTransaction, Wallet};
+use crate::database::Database;
+use anyhow::{bail, Context, Result};
+use bdk::database::BatchDatabase;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::util::bip32::{ChildNumber, ExtendedPubKey};
+use bitcoin::Network;
+use miniscript::descriptor::DescriptorPublicKey;
+use miniscript::{Descriptor, DescriptorTrait, TranslatePk2};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the balance of the hot wallet is checked for sweeping.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// The length of the script of a P2WPKH output, the weights of the redeem and
+/// punish transactions are computed for.
+const P2WPKH_SCRIPT_LEN: usize = 22;
+
+#[derive(Clone)]
+pub struct ColdStorage {
+    descriptor: Descriptor<DescriptorPublicKey>,
+    network: Network,
+    /// The length of the script of every address of the descriptor.
+    script_len: usize,
+    db: Arc<Database>,
+}
+
+impl ColdStorage {
+    /// Parses the cold-storage wallet from an output descriptor or an xpub.
+    ///
+    /// An xpub is used for native segwit addresses on its external chain,
+    /// i.e. `wpkh(<xpub>/0/*)`.
+    pub fn new(descriptor: &str, network: Network, db: Arc<Database>) -> Result<Self> {
+        let descriptor = parse_descriptor(descriptor, network)?;
+        let script_len = derive_address(&descriptor, 0, network)?
+            .script_pubkey()
+            .len();
+
+        Ok(Self {
+            descriptor,
+            network,
+            script_len,
+            db,
+        })
+    }
+
+    /// Derives the address at the next unused derivation index.
+    pub async fn next_address(&self) -> Result<Address> {
+        let index = self
+            .db
+            .next_derivation_index(&self.descriptor.to_string())
+            .await?;
+
+        derive_address(&self.descriptor, index, self.network)
+    }
+
+    /// The weight of a transaction computed for a P2WPKH output, e.g.
+    /// [`TxRedeem::weight`](crate::bitcoin::TxRedeem::weight), when it pays to
+    /// an address of the cold storage instead.
+    pub fn weight(&self, p2wpkh_weight: usize) -> usize {
+        // Both scripts are shorter than 253 bytes, so their length prefixes
+        // take up one byte either way.
+        p2wpkh_weight - P2WPKH_SCRIPT_LEN * 4 + self.script_len * 4
+    }
+}
+
+/// Derives the address the Bitcoin of a swap is paid to, from the cold storage
+/// if there is one or from the wallet otherwise.
+pub async fn new_address<B, D, C>(
+    cold_storage: Option<&ColdStorage>,
+    bitcoin_wallet: &Wallet<B, D, C>,
+) -> Result<Address>
+where
+    C: EstimateFeeRate,
+    D: BatchDatabase,
+{
+    match cold_storage {
+        Some(cold_storage) => cold_storage.next_address().await,
+        None => bitcoin_wallet.new_address().await,
+    }
+}
+
+/// The weight of a transaction computed for a P2WPKH output when it pays to an
+/// address from [`new_address`].
+pub fn weight(cold_storage: Option<&ColdStorage>, p2wpkh_weight: usize) -> usize {
+    match cold_storage {
+        Some(cold_storage) => cold_storage.weight(p2wpkh_weight),
+        None => p2wpkh_weight,
+    }
+}
+
+/// Sweeps the hot wallet into the cold-storage wallet whenever its balance
+/// reaches the threshold.
+///
+/// Runs until the ASB is stopped, failed sweeps are retried at the next
+/// check.
+pub async fn sweep_above(
+    cold_storage: ColdStorage,
+    bitcoin_wallet: Arc<Wallet>,
+    threshold: Amount,
+) {
+    loop {
+        if let Err(error) = sweep_if_above(&cold_storage, &bitcoin_wallet, threshold).await {
+            tracing::warn!("Failed to sweep Bitcoin to cold storage: {:#}", error);
+        }
+
+        tokio::time::sleep(SWEEP_INTERVAL).await;
+    }
+}
+
+async fn sweep_if_above(
+    cold_storage: &ColdStorage,
+    bitcoin_wallet: &Wallet,
+    threshold: Amount,
+) -> Result<()> {
+    bitcoin_wallet.sync().await?;
+
+    let (address, amount, psbt) = match sweep(cold_storage, bitcoin_wallet, threshold).await? {
+        Some(sweep) => sweep,
+        None => return Ok(()),
+    };
+
+    let signed_tx = bitcoin_wallet.sign_and_finalize(psbt).await?;
+    bitcoin_wallet.broadcast(signed_tx, "sweep").await?;
+
+    tracing::info!(%amount, %address, "Swept Bitcoin to cold storage");
+
+    Ok(())
+}
+
+/// Builds the transaction sending the whole balance of the wallet to the cold
+/// storage, unless the balance is below the threshold.
+async fn sweep<B, D, C>(
+    cold_storage: &ColdStorage,
+    bitcoin_wallet: &Wallet<B, D, C>,
+    threshold: Amount,
+) -> Result<Option<(Address, Amount, PartiallySignedTransaction)>>
+where
+    C: EstimateFeeRate,
+    D: BatchDatabase,
+{
+    let balance = bitcoin_wallet.balance().await?;
+    if balance < threshold {
+        tracing::debug!(%balance, %threshold, "Bitcoin balance below sweep threshold");
+        return Ok(None);
+    }
+
+    let address = cold_storage.next_address().await?;
+    let amount = bitcoin_wallet
+        .max_giveable(address.script_pubkey().len())
+        .await?;
+
+    let psbt = bitcoin_wallet
+        .send_to_address(address.clone(), amount)
+        .await?;
+
+    Ok(Some((address, amount, psbt)))
+}
+
+fn parse_descriptor(descriptor: &str, network: Network) -> Result<Descriptor<DescriptorPublicKey>> {
+    let descriptor = match ExtendedPubKey::from_str(descriptor) {
+        Ok(xpub) => {
+            if is_mainnet(xpub.network) != is_mainnet(network) {
+                bail!(
+                    "The cold storage xpub is for {} but the ASB runs on {}",
+                    xpub.network,
+                    network
+                )
+            }
+
+            format!("wpkh({}/0/*)", xpub)
+        }
+        Err(_) => descriptor.to_string(),
+    };
+
+    let descriptor = Descriptor::<DescriptorPublicKey>::from_str(&descriptor)
+        .context("The cold storage is neither a valid xpub nor a valid output descriptor")?;
+
+    if !descriptor.is_deriveable() {
+        bail!("The cold storage descriptor has to contain a wildcard to derive fresh addresses")
+    }
+
+    Ok(descriptor)
+}
+
+fn derive_address(
+    descriptor: &Descriptor<DescriptorPublicKey>,
+    index: u32,
+    network: Network,
+) -> Result<Address> {
+    let secp = Secp256k1::verification_only();
+
+    let descriptor = descriptor
+        .derive(ChildNumber::from_normal_idx(index)?)
+        .translate_pk2(|key| key.derive_public_key(&secp))
+        .context("Failed to derive cold storage key, hardened derivation is not supported")?;
+
+    descriptor
+        .address(network)
+        .context("Failed to derive cold storage address")
+}
+
+/// Extended keys only distinguish mainnet from all test networks.
+fn is_mainnet(network: Network) -> bool {
+    network == Network::Bitcoin
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::{TxPunish, TxRedeem};
+
+    // The account-level xpub of the BIP84 test vectors.
+    const XPUB: &str = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+
+    #[test]
+    fn xpub_derives_native_segwit_addresses_of_its_external_chain() {
+        let descriptor = parse_descriptor(XPUB, Network::Bitcoin).unwrap();
+
+        assert_eq!(
+            derive_address(&descriptor, 0, Network::Bitcoin)
+                .unwrap()
+                .to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        assert_eq!(
+            derive_address(&descriptor, 1, Network::Bitcoin)
+                .unwrap()
+                .to_string(),
+            "bc1qnjg0jd8228aq7egyzacy8cys3knf9xvrerkf9g"
+        );
+    }
+
+    #[test]
+    fn rejects_xpub_of_other_network() {
+        assert!(parse_descriptor(XPUB, Network::Testnet).is_err());
+    }
+
+    #[test]
+    fn rejects_descriptor_without_wildcard() {
+        let descriptor = format!("wpkh({}/0/0)", XPUB);
+
+        assert!(parse_descriptor(&descriptor, Network::Bitcoin).is_err());
+    }
+
+    #[tokio::test]
+    async fn addresses_are_not_reused_across_restarts() {
+        let db_dir = tempfile::tempdir().unwrap();
+
+        let first = {
+            let db = Arc::new(Database::open(db_dir.path()).unwrap());
+            let cold_storage = ColdStorage::new(XPUB, Network::Bitcoin, db).unwrap();
+            cold_storage.next_address().await.unwrap()
+        };
+        let db = Arc::new(Database::open(db_dir.path()).unwrap());
+        let cold_storage = ColdStorage::new(XPUB, Network::Bitcoin, db).unwrap();
+        let second = cold_storage.next_address().await.unwrap();
+
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn weight_is_computed_for_the_outputs_of_the_descriptor() {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(Database::open(db_dir.path()).unwrap());
+        let weight = |descriptor: String| {
+            ColdStorage::new(&descriptor, Network::Bitcoin, db.clone())
+                .unwrap()
+                .weight(TxRedeem::weight())
+        };
+
+        assert_eq!(weight(XPUB.to_string()), TxRedeem::weight());
+        assert_eq!(weight(format!("wpkh({}/0/*)", XPUB)), TxRedeem::weight());
+        assert_eq!(
+            weight(format!("sh(wpkh({}/0/*))", XPUB)),
+            TxRedeem::weight() + 4
+        );
+        assert_eq!(
+            weight(format!("pkh({}/0/*)", XPUB)),
+            TxRedeem::weight() + 12
+        );
+        assert_eq!(
+            weight(format!("wsh(multi(1,{}/0/*,{}/1/*))", XPUB, XPUB)),
+            TxRedeem::weight() + 48
+        );
+    }
+
+    #[tokio::test]
+    async fn swaps_pay_to_cold_storage_if_there_is_one() {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(Database::open(db_dir.path()).unwrap());
+        let descriptor = format!("pkh({}/0/*)", XPUB);
+        let cold_storage = ColdStorage::new(&descriptor, Network::Regtest, db).unwrap();
+        let wallet = Wallet::new_funded_default_fees(50_000);
+
+        let address = new_address(Some(&cold_storage), &wallet).await.unwrap();
+        assert_eq!(
+            address,
+            derive_address(&cold_storage.descriptor, 0, Network::Regtest).unwrap()
+        );
+        assert_eq!(
+            weight(Some(&cold_storage), TxPunish::weight()),
+            TxPunish::weight() + 12
+        );
+
+        let address = new_address(None, &wallet).await.unwrap();
+        assert_eq!(address.network, Network::Regtest);
+        assert_eq!(weight(None, TxPunish::weight()), TxPunish::weight());
+        assert_eq!(
+            cold_storage.next_address().await.unwrap(),
+            derive_address(&cold_storage.descriptor, 1, Network::Regtest).unwrap(),
+            "paying to the wallet should not use up an address of the cold storage"
+        );
+    }
+
+    #[tokio::test]
+    async fn balance_below_threshold_is_not_swept() {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(Database::open(db_dir.path()).unwrap());
+        let descriptor = format!("wpkh({}/0/*)", XPUB);
+        let cold_storage = ColdStorage::new(&descriptor, Network::Regtest, db).unwrap();
+        let wallet = Wallet::new_funded_default_fees(50_000);
+
+        let sweep = sweep(&cold_storage, &wallet, Amount::from_sat(50_001))
+            .await
+            .unwrap();
+
+        assert!(sweep.is_none());
+    }
+
+    #[tokio::test]
+    async fn balance_at_threshold_is_swept() {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(Database::open(db_dir.path()).unwrap());
+        let descriptor = format!("wpkh({}/0/*)", XPUB);
+        let cold_storage = ColdStorage::new(&descriptor, Network::Regtest, db).unwrap();
+        let wallet = Wallet::new_funded_default_fees(50_000);
+
+        let (address, amount, psbt) = sweep(&cold_storage, &wallet, Amount::from_sat(50_000))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let outputs = psbt.global.unsigned_tx.output;
+        assert_eq!(outputs[0].script_pubkey, address.script_pubkey());
+        assert_eq!(outputs[0].value, amount.as_sat());
+        assert_eq!(
+            amount,
+            wallet
+                .max_giveable(address.script_pubkey().len())
+                .await
+                .unwrap()
+        );
+    }
+}
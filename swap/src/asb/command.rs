@@ -1,10 +1,9 @@
 use crate::asb::config::GetDefaults;
-use crate::bitcoin::Amount;
-use crate::env;
+use crate::bitcoin::{validate_address, Amount, CoinControl};
 use crate::env::GetConfig;
+use crate::{env, history};
 use anyhow::{bail, Result};
-use bitcoin::Address;
-use serde::Serialize;
+use bitcoin::{Address, OutPoint};
 use std::ffi::OsString;
 use std::path::PathBuf;
 use structopt::StructOpt;
@@ -31,23 +30,77 @@ where
             env_config: env_config(is_testnet),
             cmd: Command::Start { resume_only },
         },
-        RawCommand::History => Arguments {
+        RawCommand::History {
+            swap_id,
+            states,
+            json,
+            csv,
+        } => Arguments {
             testnet: is_testnet,
             json: is_json,
             config_path: config_path(config, is_testnet)?,
             env_config: env_config(is_testnet),
-            cmd: Command::History,
+            cmd: Command::History {
+                swap_id,
+                states,
+                format: history::Format::from_flags(json, csv),
+            },
         },
-        RawCommand::WithdrawBtc { amount, address } => Arguments {
+        RawCommand::WithdrawBtc {
+            amount,
+            address,
+            utxos,
+            exclude_utxos,
+        } => Arguments {
             testnet: is_testnet,
             json: is_json,
             config_path: config_path(config, is_testnet)?,
             env_config: env_config(is_testnet),
             cmd: Command::WithdrawBtc {
                 amount,
-                address: bitcoin_address(address, is_testnet)?,
+                address: validate_address(address, is_testnet)?,
+                coin_control: CoinControl::from_flags(utxos, exclude_utxos),
+            },
+        },
+        RawCommand::MigrateDatabase => Arguments {
+            testnet: is_testnet,
+            json: is_json,
+            config_path: config_path(config, is_testnet)?,
+            env_config: env_config(is_testnet),
+            cmd: Command::MigrateDatabase,
+        },
+        RawCommand::Export {
+            swap_id,
+            output,
+            encrypt,
+        } => Arguments {
+            testnet: is_testnet,
+            json: is_json,
+            config_path: config_path(config, is_testnet)?,
+            env_config: env_config(is_testnet),
+            cmd: Command::Export {
+                swap_id,
+                output,
+                encrypt,
             },
         },
+        RawCommand::Import { file } => Arguments {
+            testnet: is_testnet,
+            json: is_json,
+            config_path: config_path(config, is_testnet)?,
+            env_config: env_config(is_testnet),
+            cmd: Command::Import { file },
+        },
+        RawCommand::Seed {
+            restore_from_mnemonic,
+            cmd,
+        } => Arguments {
+            testnet: is_testnet,
+            json: is_json,
+            config_path: config_path(config, is_testnet)?,
+            env_config: env_config(is_testnet),
+            cmd: seed_command(restore_from_mnemonic, cmd)?,
+        },
         RawCommand::Balance => Arguments {
             testnet: is_testnet,
             json: is_json,
@@ -55,6 +108,13 @@ where
             env_config: env_config(is_testnet),
             cmd: Command::Balance,
         },
+        RawCommand::ListUtxos => Arguments {
+            testnet: is_testnet,
+            json: is_json,
+            config_path: config_path(config, is_testnet)?,
+            env_config: env_config(is_testnet),
+            cmd: Command::ListUtxos,
+        },
         RawCommand::ManualRecovery(ManualRecovery::Redeem {
             redeem_params: RecoverCommandParams { swap_id, force },
             do_not_await_finality,
@@ -108,23 +168,6 @@ where
     Ok(arguments)
 }
 
-fn bitcoin_address(address: Address, is_testnet: bool) -> Result<Address> {
-    let network = if is_testnet {
-        bitcoin::Network::Testnet
-    } else {
-        bitcoin::Network::Bitcoin
-    };
-
-    if address.network != network {
-        bail!(BitcoinAddressNetworkMismatch {
-            expected: network,
-            actual: address.network
-        });
-    }
-
-    Ok(address)
-}
-
 fn config_path(config: Option<PathBuf>, is_testnet: bool) -> Result<PathBuf> {
     let config_path = if let Some(config_path) = config {
         config_path
@@ -145,13 +188,19 @@ fn env_config(is_testnet: bool) -> env::Config {
     }
 }
 
-#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Serialize)]
-#[error("Invalid Bitcoin address provided, expected address on network {expected:?}  but address provided is on {actual:?}")]
-pub struct BitcoinAddressNetworkMismatch {
-    #[serde(with = "crate::bitcoin::network")]
-    expected: bitcoin::Network,
-    #[serde(with = "crate::bitcoin::network")]
-    actual: bitcoin::Network,
+fn seed_command(restore_from_mnemonic: bool, cmd: Option<SeedCommand>) -> Result<Command> {
+    let command = match (restore_from_mnemonic, cmd) {
+        (true, None) => Command::RestoreSeed,
+        (false, Some(SeedCommand::ShowMnemonic)) => Command::ShowMnemonic,
+        (false, Some(SeedCommand::Encrypt)) => Command::EncryptSeed,
+        (false, Some(SeedCommand::ChangePassphrase)) => Command::ChangeSeedPassphrase,
+        (true, Some(_)) => {
+            bail!("--restore-from-mnemonic cannot be combined with a seed sub-command")
+        }
+        (false, None) => bail!("Either pass --restore-from-mnemonic or a seed sub-command"),
+    };
+
+    Ok(command)
 }
 
 #[derive(Debug, PartialEq)]
@@ -168,12 +217,31 @@ pub enum Command {
     Start {
         resume_only: bool,
     },
-    History,
+    History {
+        swap_id: Option<Uuid>,
+        states: bool,
+        format: history::Format,
+    },
     WithdrawBtc {
         amount: Option<Amount>,
         address: Address,
+        coin_control: CoinControl,
     },
     Balance,
+    ListUtxos,
+    MigrateDatabase,
+    Export {
+        swap_id: Uuid,
+        output: PathBuf,
+        encrypt: bool,
+    },
+    Import {
+        file: PathBuf,
+    },
+    EncryptSeed,
+    ChangeSeedPassphrase,
+    ShowMnemonic,
+    RestoreSeed,
     Redeem {
         swap_id: Uuid,
         force: bool,
@@ -236,7 +304,27 @@ pub enum RawCommand {
         resume_only: bool,
     },
     #[structopt(about = "Prints swap-id and the state of each swap ever made.")]
-    History,
+    History {
+        #[structopt(help = "Only show the swap with this id.")]
+        swap_id: Option<Uuid>,
+        #[structopt(
+            long = "states",
+            help = "Show all states the swap went through and when it entered them.",
+            requires = "swap-id"
+        )]
+        states: bool,
+        #[structopt(
+            long = "json",
+            help = "Print the history as JSON including all recorded details of each swap.",
+            conflicts_with = "csv"
+        )]
+        json: bool,
+        #[structopt(
+            long = "csv",
+            help = "Print the history as CSV including all recorded details of each swap."
+        )]
+        csv: bool,
+    },
     #[structopt(about = "Allows withdrawing BTC from the internal Bitcoin wallet.")]
     WithdrawBtc {
         #[structopt(
@@ -246,15 +334,88 @@ pub enum RawCommand {
         amount: Option<Amount>,
         #[structopt(long = "address", help = "The address to receive the Bitcoin.")]
         address: Address,
+        #[structopt(
+            long = "utxo",
+            help = "Only spend this UTXO of the internal wallet, given as <txid>:<vout>. Can be given multiple times, list-utxos shows the UTXOs of the wallet.",
+            number_of_values = 1,
+            conflicts_with = "exclude-utxo"
+        )]
+        utxos: Vec<OutPoint>,
+        #[structopt(
+            long = "exclude-utxo",
+            help = "Never spend this UTXO of the internal wallet, given as <txid>:<vout>. Can be given multiple times.",
+            number_of_values = 1
+        )]
+        exclude_utxos: Vec<OutPoint>,
     },
     #[structopt(
         about = "Prints the Bitcoin and Monero balance. Requires the monero-wallet-rpc to be running."
     )]
     Balance,
+    #[structopt(
+        about = "Prints the UTXOs of the internal Bitcoin wallet and where they came from, e.g. the redeem of a swap."
+    )]
+    ListUtxos,
+    #[structopt(
+        about = "Copies the swaps of the sled database into a new SQLite database. Set `database = \"sqlite\"` in the data section of the config afterwards to use it."
+    )]
+    MigrateDatabase,
+    #[structopt(
+        about = "Writes a swap into a recovery file that can be imported on another machine."
+    )]
+    Export {
+        #[structopt(
+            long = "swap-id",
+            help = "The swap id can be retrieved using the history subcommand"
+        )]
+        swap_id: Uuid,
+        #[structopt(
+            long = "output",
+            help = "The file to write the swap to.",
+            parse(from_os_str)
+        )]
+        output: PathBuf,
+        #[structopt(
+            long = "encrypt",
+            help = "Encrypt the recovery file with a passphrase."
+        )]
+        encrypt: bool,
+    },
+    #[structopt(
+        about = "Imports a swap from a recovery file. Swaps that are not finished are resumed on the next start."
+    )]
+    Import {
+        #[structopt(help = "The recovery file written by export.", parse(from_os_str))]
+        file: PathBuf,
+    },
+    #[structopt(
+        about = "Contains sub-commands for managing the seed all keys of the ASB are derived from."
+    )]
+    Seed {
+        #[structopt(
+            long = "restore-from-mnemonic",
+            help = "Restores the seed file from the BIP39 mnemonic shown by show-mnemonic."
+        )]
+        restore_from_mnemonic: bool,
+        #[structopt(subcommand)]
+        cmd: Option<SeedCommand>,
+    },
     #[structopt(about = "Contains sub-commands for recovering a swap manually.")]
     ManualRecovery(ManualRecovery),
 }
 
+#[derive(structopt::StructOpt, Debug)]
+pub enum SeedCommand {
+    #[structopt(about = "Prints the seed as BIP39 mnemonic to back it up on paper.")]
+    ShowMnemonic,
+    #[structopt(
+        about = "Encrypts the seed file with a passphrase. The passphrase is read from the file configured as `seed_passphrase_file` in the data section of the config, the ASB_SEED_PASSPHRASE environment variable or asked for on start."
+    )]
+    Encrypt,
+    #[structopt(about = "Changes the passphrase of the encrypted seed file.")]
+    ChangePassphrase,
+}
+
 #[derive(structopt::StructOpt, Debug)]
 pub enum ManualRecovery {
     #[structopt(
@@ -320,6 +481,7 @@ pub struct RecoverCommandParams {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bitcoin::BitcoinAddressNetworkMismatch;
     use std::str::FromStr;
 
     const BINARY_NAME: &str = "asb";
@@ -349,11 +511,64 @@ mod tests {
             json: false,
             config_path: default_mainnet_conf_path.clone(),
             env_config: mainnet_env_config,
-            cmd: Command::History,
+            cmd: Command::History {
+                swap_id: None,
+                states: false,
+                format: history::Format::Table,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+
+        let raw_ars = vec![BINARY_NAME, "history", "--json"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            config_path: default_mainnet_conf_path.clone(),
+            env_config: mainnet_env_config,
+            cmd: Command::History {
+                swap_id: None,
+                states: false,
+                format: history::Format::Json,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+
+        let raw_ars = vec![BINARY_NAME, "history", "--csv"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            config_path: default_mainnet_conf_path.clone(),
+            env_config: mainnet_env_config,
+            cmd: Command::History {
+                swap_id: None,
+                states: false,
+                format: history::Format::Csv,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+
+        let raw_ars = vec![BINARY_NAME, "history", SWAP_ID, "--states"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            config_path: default_mainnet_conf_path.clone(),
+            env_config: mainnet_env_config,
+            cmd: Command::History {
+                swap_id: Some(Uuid::parse_str(SWAP_ID).unwrap()),
+                states: true,
+                format: history::Format::Table,
+            },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
 
+        let raw_ars = vec![BINARY_NAME, "history", "--states"];
+        let result = parse_args(raw_ars);
+        assert!(result.is_err());
+
         let raw_ars = vec![BINARY_NAME, "balance"];
         let expected_args = Arguments {
             testnet: false,
@@ -365,11 +580,128 @@ mod tests {
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
 
+        let raw_ars = vec![BINARY_NAME, "migrate-database"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            config_path: default_mainnet_conf_path.clone(),
+            env_config: mainnet_env_config,
+            cmd: Command::MigrateDatabase,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "export",
+            "--swap-id",
+            SWAP_ID,
+            "--output",
+            "swap.pem",
+        ];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            config_path: default_mainnet_conf_path.clone(),
+            env_config: mainnet_env_config,
+            cmd: Command::Export {
+                swap_id: Uuid::parse_str(SWAP_ID).unwrap(),
+                output: PathBuf::from("swap.pem"),
+                encrypt: false,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+
+        let raw_ars = vec![BINARY_NAME, "seed", "encrypt"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            config_path: default_mainnet_conf_path.clone(),
+            env_config: mainnet_env_config,
+            cmd: Command::EncryptSeed,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+
+        let raw_ars = vec![BINARY_NAME, "seed", "show-mnemonic"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            config_path: default_mainnet_conf_path.clone(),
+            env_config: mainnet_env_config,
+            cmd: Command::ShowMnemonic,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+
+        let raw_ars = vec![BINARY_NAME, "seed", "--restore-from-mnemonic"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            config_path: default_mainnet_conf_path.clone(),
+            env_config: mainnet_env_config,
+            cmd: Command::RestoreSeed,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+
+        let raw_ars = vec![BINARY_NAME, "seed"];
+        let result = parse_args(raw_ars);
+        assert!(result.is_err());
+
+        let raw_ars = vec![BINARY_NAME, "seed", "change-passphrase"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            config_path: default_mainnet_conf_path.clone(),
+            env_config: mainnet_env_config,
+            cmd: Command::ChangeSeedPassphrase,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+
+        let raw_ars = vec![BINARY_NAME, "import", "swap.pem"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            config_path: default_mainnet_conf_path.clone(),
+            env_config: mainnet_env_config,
+            cmd: Command::Import {
+                file: PathBuf::from("swap.pem"),
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "withdraw-btc",
+            "--address",
+            BITCOIN_MAINNET_ADDRESS,
+        ];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            config_path: default_mainnet_conf_path.clone(),
+            env_config: mainnet_env_config,
+            cmd: Command::WithdrawBtc {
+                amount: None,
+                address: Address::from_str(BITCOIN_MAINNET_ADDRESS).unwrap(),
+                coin_control: CoinControl::Any,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+
+        let utxo = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b:0";
         let raw_ars = vec![
             BINARY_NAME,
             "withdraw-btc",
             "--address",
             BITCOIN_MAINNET_ADDRESS,
+            "--exclude-utxo",
+            utxo,
         ];
         let expected_args = Arguments {
             testnet: false,
@@ -379,11 +711,23 @@ mod tests {
             cmd: Command::WithdrawBtc {
                 amount: None,
                 address: Address::from_str(BITCOIN_MAINNET_ADDRESS).unwrap(),
+                coin_control: CoinControl::Exclude(vec![OutPoint::from_str(utxo).unwrap()]),
             },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
 
+        let raw_ars = vec![BINARY_NAME, "list-utxos"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            config_path: default_mainnet_conf_path.clone(),
+            env_config: mainnet_env_config,
+            cmd: Command::ListUtxos,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+
         let raw_ars = vec![
             BINARY_NAME,
             "manual-recovery",
@@ -486,7 +830,11 @@ mod tests {
             json: false,
             config_path: default_testnet_conf_path.clone(),
             env_config: testnet_env_config,
-            cmd: Command::History,
+            cmd: Command::History {
+                swap_id: None,
+                states: false,
+                format: history::Format::Table,
+            },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
@@ -517,6 +865,7 @@ mod tests {
             cmd: Command::WithdrawBtc {
                 amount: None,
                 address: Address::from_str(BITCOIN_TESTNET_ADDRESS).unwrap(),
+                coin_control: CoinControl::Any,
             },
         };
         let args = parse_args(raw_ars).unwrap();
@@ -619,8 +968,8 @@ mod tests {
 
     #[test]
     fn given_bitcoin_address_network_mismatch_then_error() {
-        let error =
-            bitcoin_address(Address::from_str(BITCOIN_MAINNET_ADDRESS).unwrap(), true).unwrap_err();
+        let error = validate_address(Address::from_str(BITCOIN_MAINNET_ADDRESS).unwrap(), true)
+            .unwrap_err();
 
         assert_eq!(
             error
@@ -632,7 +981,7 @@ mod tests {
             }
         );
 
-        let error = bitcoin_address(Address::from_str(BITCOIN_TESTNET_ADDRESS).unwrap(), false)
+        let error = validate_address(Address::from_str(BITCOIN_TESTNET_ADDRESS).unwrap(), false)
             .unwrap_err();
 
         assert_eq!(
@@ -1,5 +1,10 @@
+use crate::asb::hooks;
+use crate::asb::limits::Limits;
+use crate::database;
+use crate::encryption::Passphrase;
 use crate::env::{Mainnet, Testnet};
 use crate::fs::{ensure_directory_exists, system_config_dir, system_data_dir};
+use crate::network::spot_price::TimelockRanges;
 use crate::tor::{DEFAULT_CONTROL_PORT, DEFAULT_SOCKS5_PORT};
 use anyhow::{bail, Context, Result};
 use config::ConfigError;
@@ -81,9 +86,14 @@ fn default_asb_data_dir() -> Result<PathBuf> {
         .context("Could not generate default config file path")
 }
 
+/// The environment variable the passphrase of an encrypted seed is read from.
+pub const SEED_PASSPHRASE_ENV: &str = "ASB_SEED_PASSPHRASE";
+
 const DEFAULT_MIN_BUY_AMOUNT: f64 = 0.002f64;
 const DEFAULT_MAX_BUY_AMOUNT: f64 = 0.02f64;
 const DEFAULT_SPREAD: f64 = 0.02f64;
+const DEFAULT_PRICE_FEED_REFRESH_INTERVAL_SECS: u64 = 10;
+const DEFAULT_PRICE_FEED_MAX_AGE_SECS: u64 = 120;
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
 pub struct Config {
@@ -93,6 +103,8 @@ pub struct Config {
     pub monero: Monero,
     pub tor: TorConf,
     pub maker: Maker,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<Hook>,
 }
 
 impl Config {
@@ -112,6 +124,25 @@ impl Config {
 #[serde(deny_unknown_fields)]
 pub struct Data {
     pub dir: PathBuf,
+    #[serde(default)]
+    pub database: database::Backend,
+    /// The file the passphrase of an encrypted seed is read from, takes
+    /// precedence over the `ASB_SEED_PASSPHRASE` environment variable.
+    #[serde(default)]
+    pub seed_passphrase_file: Option<PathBuf>,
+}
+
+impl Data {
+    /// Where the passphrase of an encrypted seed is read from.
+    pub fn seed_passphrase(&self) -> Passphrase {
+        match &self.seed_passphrase_file {
+            Some(path) => Passphrase::File(path.clone()),
+            None if std::env::var_os(SEED_PASSPHRASE_ENV).is_some() => {
+                Passphrase::Env(SEED_PASSPHRASE_ENV.to_string())
+            }
+            None => Passphrase::Prompt,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -128,6 +159,22 @@ pub struct Bitcoin {
     pub finality_confirmations: Option<u32>,
     #[serde(with = "crate::bitcoin::network")]
     pub network: bitcoin::Network,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cold_storage: Option<ColdStorage>,
+}
+
+/// A wallet the ASB sends the Bitcoin of its swaps to.
+///
+/// Without a sweep threshold the redeem and punish transactions pay to the
+/// cold storage directly, otherwise they pay to the internal wallet which is
+/// swept into the cold storage once its balance reaches the threshold.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColdStorage {
+    /// An output descriptor with a wildcard or an xpub.
+    pub descriptor: String,
+    #[serde(default, with = "::bitcoin::util::amount::serde::as_btc::opt")]
+    pub sweep_threshold_btc: Option<bitcoin::Amount>,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -137,6 +184,30 @@ pub struct Monero {
     pub finality_confirmations: Option<u64>,
     #[serde(with = "crate::monero::network")]
     pub network: monero::Network,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refill: Option<Refill>,
+}
+
+/// Alerts when the Monero balance drops below the low watermark, optionally
+/// refilling it from a treasury wallet.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Refill {
+    #[serde(with = "crate::monero::monero_amount_as_xmr")]
+    pub low_watermark_xmr: crate::monero::Amount,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub treasury: Option<Treasury>,
+}
+
+/// A wallet in a second monero-wallet-rpc the Monero wallet is refilled from.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Treasury {
+    pub wallet_rpc_url: Url,
+    pub wallet_name: String,
+    /// How much is transferred from the treasury per refill.
+    #[serde(with = "crate::monero::monero_amount_as_xmr")]
+    pub refill_xmr: crate::monero::Amount,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -155,6 +226,59 @@ pub struct Maker {
     pub max_buy_btc: bitcoin::Amount,
     pub ask_spread: Decimal,
     pub price_ticker_ws_url: Url,
+    /// Only resume unfinished swaps, equivalent to `start --resume-only`.
+    #[serde(default)]
+    pub resume_only: bool,
+    #[serde(default)]
+    pub limits: Limits,
+    /// The timelocks accepted in spot price requests, only the defaults of
+    /// the network if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timelocks: Option<TimelockRanges>,
+    /// Hand out binding quotes that are honoured for this many seconds,
+    /// quotes are only indications if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_validity_secs: Option<u64>,
+    /// Read the ask price from a local price feed instead of the price
+    /// ticker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_feed: Option<PriceFeed>,
+}
+
+/// A local price feed, the ask price is read from either the file, the Unix
+/// socket or the output of the command.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PriceFeed {
+    pub file: Option<PathBuf>,
+    pub socket: Option<PathBuf>,
+    pub command: Option<Vec<String>>,
+    #[serde(default = "default_price_feed_refresh_interval_secs")]
+    pub refresh_interval_secs: u64,
+    /// How old the price may get before no more quotes are given, measured
+    /// from its `timestamp`, the modification time of the file or the time it
+    /// was read.
+    #[serde(default = "default_price_feed_max_age_secs")]
+    pub max_age_secs: u64,
+}
+
+fn default_price_feed_refresh_interval_secs() -> u64 {
+    DEFAULT_PRICE_FEED_REFRESH_INTERVAL_SECS
+}
+
+fn default_price_feed_max_age_secs() -> u64 {
+    DEFAULT_PRICE_FEED_MAX_AGE_SECS
+}
+
+/// A hook that is triggered by the given events of a swap.
+///
+/// The payload is either POSTed to the url or passed to the command on stdin.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hook {
+    pub events: Vec<hooks::Event>,
+    pub url: Option<Url>,
+    pub command: Option<Vec<String>>,
 }
 
 impl Default for TorConf {
@@ -288,7 +412,11 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
     println!();
 
     Ok(Config {
-        data: Data { dir: data_dir },
+        data: Data {
+            dir: data_dir,
+            database: database::Backend::default(),
+            seed_passphrase_file: None,
+        },
         network: Network {
             listen: listen_addresses,
         },
@@ -297,11 +425,13 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
             target_block,
             finality_confirmations: None,
             network: bitcoin_network,
+            cold_storage: None,
         },
         monero: Monero {
             wallet_rpc_url: monero_wallet_rpc_url,
             finality_confirmations: None,
             network: monero_network,
+            refill: None,
         },
         tor: TorConf {
             control_port: tor_control_port,
@@ -312,7 +442,13 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
             max_buy_btc: max_buy,
             ask_spread,
             price_ticker_ws_url: defaults.price_ticker_ws_url,
+            resume_only: false,
+            limits: Limits::default(),
+            timelocks: None,
+            quote_validity_secs: None,
+            price_feed: None,
         },
+        hooks: vec![],
     })
 }
 
@@ -331,12 +467,15 @@ mod tests {
         let expected = Config {
             data: Data {
                 dir: Default::default(),
+                database: Default::default(),
+                seed_passphrase_file: None,
             },
             bitcoin: Bitcoin {
                 electrum_rpc_url: defaults.electrum_rpc_url,
                 target_block: defaults.bitcoin_confirmation_target,
                 finality_confirmations: None,
                 network: bitcoin::Network::Testnet,
+                cold_storage: None,
             },
             network: Network {
                 listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
@@ -346,6 +485,7 @@ mod tests {
                 wallet_rpc_url: defaults.monero_wallet_rpc_url,
                 finality_confirmations: None,
                 network: monero::Network::Stagenet,
+                refill: None,
             },
             tor: Default::default(),
             maker: Maker {
@@ -353,7 +493,13 @@ mod tests {
                 max_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MAX_BUY_AMOUNT).unwrap(),
                 ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
                 price_ticker_ws_url: defaults.price_ticker_ws_url,
+                resume_only: false,
+                limits: Limits::default(),
+                timelocks: None,
+                quote_validity_secs: None,
+                price_feed: None,
             },
+            hooks: vec![],
         };
 
         initial_setup(config_path.clone(), expected.clone()).unwrap();
@@ -372,12 +518,15 @@ mod tests {
         let expected = Config {
             data: Data {
                 dir: Default::default(),
+                database: Default::default(),
+                seed_passphrase_file: None,
             },
             bitcoin: Bitcoin {
                 electrum_rpc_url: defaults.electrum_rpc_url,
                 target_block: defaults.bitcoin_confirmation_target,
                 finality_confirmations: None,
                 network: bitcoin::Network::Bitcoin,
+                cold_storage: None,
             },
             network: Network {
                 listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
@@ -387,6 +536,7 @@ mod tests {
                 wallet_rpc_url: defaults.monero_wallet_rpc_url,
                 finality_confirmations: None,
                 network: monero::Network::Mainnet,
+                refill: None,
             },
             tor: Default::default(),
             maker: Maker {
@@ -394,7 +544,13 @@ mod tests {
                 max_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MAX_BUY_AMOUNT).unwrap(),
                 ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
                 price_ticker_ws_url: defaults.price_ticker_ws_url,
+                resume_only: false,
+                limits: Limits::default(),
+                timelocks: None,
+                quote_validity_secs: None,
+                price_feed: None,
             },
+            hooks: vec![],
         };
 
         initial_setup(config_path.clone(), expected.clone()).unwrap();
@@ -0,0 +1,644 @@
+//! Hooks that notify external systems about the progress of swaps.
+//!
+//! A hook is either an HTTP endpoint that receives the payload as JSON in a
+//! POST request or a command that receives it on stdin. Every hook is
+//! triggered for the events it is configured with, failed deliveries are
+//! retried with exponential backoff in the background so a slow or
+//! unreachable receiver never holds up a swap.
+
+use crate::asb::config;
+use crate::bitcoin::UtxoLabel;
+use crate::database::{
+    Alice, AliceEndState, Database, Identity, LoggedState, Storage, Swap, SwapDetails,
+};
+use crate::monero::TransferProof;
+use crate::{history, monero};
+use ::bitcoin::Script;
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use backoff::ExponentialBackoff;
+use libp2p::PeerId;
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use url::Url;
+use uuid::Uuid;
+
+/// How long failed deliveries are retried.
+const MAX_RETRY_DURATION: Duration = Duration::from_secs(60 * 60);
+const INITIAL_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+/// How long a single delivery may take before it counts as failed.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The events a hook can be triggered by.
+///
+/// Most events correspond to a state of a swap, [`Event::Error`] is
+/// triggered when a swap stops because of an error. The Monero events are
+/// triggered by the balance of the Monero wallet instead of a swap.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, strum::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Event {
+    Started,
+    BtcLockTransactionSeen,
+    BtcLocked,
+    XmrLockTransactionSent,
+    XmrLocked,
+    XmrLockTransferProofSent,
+    EncSigLearned,
+    BtcRedeemTransactionPublished,
+    BtcRedeemed,
+    CancelTimelockExpired,
+    BtcCancelled,
+    BtcRefunded,
+    BtcPunishable,
+    XmrRefunded,
+    BtcPunished,
+    SafelyAborted,
+    Error,
+    LowMoneroBalance,
+    MoneroRefillSent,
+    MoneroRefillExpired,
+}
+
+impl From<&Alice> for Event {
+    fn from(state: &Alice) -> Self {
+        match state {
+            Alice::Started { .. } => Event::Started,
+            Alice::BtcLockTransactionSeen { .. } => Event::BtcLockTransactionSeen,
+            Alice::BtcLocked { .. } => Event::BtcLocked,
+            Alice::XmrLockTransactionSent { .. } => Event::XmrLockTransactionSent,
+            Alice::XmrLocked { .. } => Event::XmrLocked,
+            Alice::XmrLockTransferProofSent { .. } => Event::XmrLockTransferProofSent,
+            Alice::EncSigLearned { .. } => Event::EncSigLearned,
+            Alice::BtcRedeemTransactionPublished { .. } => Event::BtcRedeemTransactionPublished,
+            Alice::CancelTimelockExpired { .. } => Event::CancelTimelockExpired,
+            Alice::BtcCancelled { .. } => Event::BtcCancelled,
+            Alice::BtcPunishable { .. } => Event::BtcPunishable,
+            Alice::BtcRefunded { .. } => Event::BtcRefunded,
+            Alice::Done(AliceEndState::BtcRedeemed) => Event::BtcRedeemed,
+            Alice::Done(AliceEndState::XmrRefunded) => Event::XmrRefunded,
+            Alice::Done(AliceEndState::BtcPunished) => Event::BtcPunished,
+            Alice::Done(AliceEndState::SafelyAborted) => Event::SafelyAborted,
+        }
+    }
+}
+
+/// What is sent to a hook.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct Payload {
+    pub event: Event,
+    /// The error the swap stopped with, only set for [`Event::Error`].
+    pub error: Option<String>,
+    #[serde(flatten)]
+    pub swap: Option<history::Entry>,
+    /// Only set for the Monero events.
+    pub monero_balance_piconero: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Target {
+    Url(Url),
+    Command(Vec<String>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Hook {
+    events: Vec<Event>,
+    target: Target,
+}
+
+#[derive(Clone, Debug)]
+pub struct Hooks {
+    hooks: Vec<Hook>,
+    client: reqwest::Client,
+    initial_retry_interval: Duration,
+    max_retry_duration: Duration,
+    delivery_timeout: Duration,
+}
+
+impl Hooks {
+    pub fn new(hooks: Vec<config::Hook>) -> Result<Self> {
+        let hooks = hooks
+            .into_iter()
+            .map(|hook| {
+                let target = match (hook.url, hook.command) {
+                    (Some(url), None) => Target::Url(url),
+                    (None, Some(command)) if !command.is_empty() => Target::Command(command),
+                    (None, Some(_)) => bail!("The command of a hook must not be empty"),
+                    _ => bail!("A hook needs either a url or a command"),
+                };
+
+                Ok(Hook {
+                    events: hook.events,
+                    target,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            hooks,
+            client: reqwest::Client::builder()
+                .timeout(DELIVERY_TIMEOUT)
+                .build()
+                .context("Failed to build HTTP client for hooks")?,
+            initial_retry_interval: INITIAL_RETRY_INTERVAL,
+            max_retry_duration: MAX_RETRY_DURATION,
+            delivery_timeout: DELIVERY_TIMEOUT,
+        })
+    }
+
+    /// Triggers the hooks of the event with the current details of the swap.
+    pub fn notify(&self, db: &dyn Storage, swap_id: Uuid, event: Event, error: Option<String>) {
+        if !self.is_triggered_by(event) {
+            return;
+        }
+
+        match payload(db, swap_id, event, error) {
+            Ok(payload) => self.trigger(payload),
+            Err(error) => {
+                tracing::warn!(%swap_id, %event, "Failed to assemble hook payload: {:#}", error);
+            }
+        }
+    }
+
+    /// Triggers the hooks of a Monero event with the balance of the Monero
+    /// wallet.
+    pub fn notify_monero_balance(&self, event: Event, balance: monero::Amount) {
+        if !self.is_triggered_by(event) {
+            return;
+        }
+
+        self.trigger(Payload {
+            event,
+            error: None,
+            swap: None,
+            monero_balance_piconero: Some(balance.as_piconero()),
+        })
+    }
+
+    fn is_triggered_by(&self, event: Event) -> bool {
+        self.hooks.iter().any(|hook| hook.events.contains(&event))
+    }
+
+    fn trigger(&self, payload: Payload) {
+        let event = payload.event;
+        let swap_id = payload.swap.as_ref().map(|swap| swap.swap_id);
+
+        for hook in self
+            .hooks
+            .iter()
+            .filter(|hook| hook.events.contains(&event))
+        {
+            let target = hook.target.clone();
+            let payload = payload.clone();
+            let client = self.client.clone();
+            let delivery_timeout = self.delivery_timeout;
+            let backoff = ExponentialBackoff {
+                initial_interval: self.initial_retry_interval,
+                max_elapsed_time: Some(self.max_retry_duration),
+                ..ExponentialBackoff::default()
+            };
+
+            tokio::spawn(async move {
+                let result = backoff::future::retry_notify(
+                    backoff,
+                    || async {
+                        deliver(&client, &target, &payload, delivery_timeout)
+                            .await
+                            .map_err(backoff::Error::Transient)
+                    },
+                    |error, next: Duration| {
+                        tracing::debug!(
+                            ?swap_id,
+                            %event,
+                            "Failed to deliver hook, retrying in {}ms. Error {:#}",
+                            next.as_millis(),
+                            error
+                        );
+                    },
+                )
+                .await;
+
+                if let Err(error) = result {
+                    tracing::warn!(?swap_id, %event, "Giving up on delivering hook: {:#}", error);
+                }
+            });
+        }
+    }
+}
+
+fn payload(
+    db: &dyn Storage,
+    swap_id: Uuid,
+    event: Event,
+    error: Option<String>,
+) -> Result<Payload> {
+    let state = db.get_state(swap_id)?;
+    let details = db.get_details(swap_id)?;
+    let counterparty_peer_id = db
+        .get_peer_id(swap_id)
+        .ok()
+        .map(|peer_id| peer_id.to_string());
+
+    Ok(Payload {
+        event,
+        error,
+        swap: Some(history::Entry::new(
+            swap_id,
+            state,
+            details,
+            counterparty_peer_id,
+        )),
+        monero_balance_piconero: None,
+    })
+}
+
+async fn deliver(
+    client: &reqwest::Client,
+    target: &Target,
+    payload: &Payload,
+    delivery_timeout: Duration,
+) -> Result<()> {
+    let body = serde_json::to_vec(payload)?;
+
+    match target {
+        Target::Url(url) => {
+            client
+                .post(url.clone())
+                .header(CONTENT_TYPE, "application/json")
+                .body(body)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Target::Command(command) => {
+            let mut child = tokio::process::Command::new(&command[0])
+                .args(&command[1..])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .kill_on_drop(true)
+                .spawn()
+                .with_context(|| format!("Failed to run hook command {}", command[0]))?;
+
+            let run = async {
+                let mut stdin = child
+                    .stdin
+                    .take()
+                    .context("Failed to open stdin of hook command")?;
+                stdin.write_all(&body).await?;
+                drop(stdin);
+
+                Ok::<_, anyhow::Error>(child.wait().await?)
+            };
+
+            // The child is killed when it is dropped after the timeout.
+            let status = tokio::time::timeout(delivery_timeout, run)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Hook command {} did not exit within {}s",
+                        command[0],
+                        delivery_timeout.as_secs()
+                    )
+                })??;
+            if !status.success() {
+                return Err(anyhow!(
+                    "Hook command {} exited with {}",
+                    command[0],
+                    status
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Wraps the database of the ASB to trigger hooks whenever the state of a
+/// swap is saved.
+pub struct HookedStorage {
+    inner: Database,
+    hooks: Arc<Hooks>,
+}
+
+impl HookedStorage {
+    pub fn new(inner: Database, hooks: Arc<Hooks>) -> Self {
+        Self { inner, hooks }
+    }
+}
+
+#[async_trait]
+impl Storage for HookedStorage {
+    async fn insert_peer_id(&self, swap_id: Uuid, peer_id: PeerId) -> Result<()> {
+        self.inner.insert_peer_id(swap_id, peer_id).await
+    }
+
+    fn get_peer_id(&self, swap_id: Uuid) -> Result<PeerId> {
+        self.inner.get_peer_id(swap_id)
+    }
+
+    async fn insert_identity(&self, swap_id: Uuid, identity: Identity) -> Result<()> {
+        self.inner.insert_identity(swap_id, identity).await
+    }
+
+    fn get_identity(&self, swap_id: Uuid) -> Result<Option<Identity>> {
+        self.inner.get_identity(swap_id)
+    }
+
+    async fn insert_latest_state(&self, swap_id: Uuid, state: Swap) -> Result<()> {
+        // Resuming a swap saves the state it was resumed from again, which must
+        // not trigger the hooks a second time.
+        let is_new_state = self.inner.get_state(swap_id).ok().as_ref() != Some(&state);
+        let event = match &state {
+            Swap::Alice(alice) if is_new_state => Some(Event::from(alice)),
+            _ => None,
+        };
+
+        self.inner.insert_latest_state(swap_id, state).await?;
+
+        if let Some(event) = event {
+            self.hooks.notify(&*self.inner, swap_id, event, None);
+        }
+
+        Ok(())
+    }
+
+    fn get_state(&self, swap_id: Uuid) -> Result<Swap> {
+        self.inner.get_state(swap_id)
+    }
+
+    fn get_state_log(&self, swap_id: Uuid) -> Result<Vec<LoggedState>> {
+        self.inner.get_state_log(swap_id)
+    }
+
+    fn get_details(&self, swap_id: Uuid) -> Result<SwapDetails> {
+        self.inner.get_details(swap_id)
+    }
+
+    fn all_swaps(&self) -> Result<Vec<(Uuid, Swap)>> {
+        self.inner.all_swaps()
+    }
+
+    async fn next_derivation_index(&self, descriptor: &str) -> Result<u32> {
+        self.inner.next_derivation_index(descriptor).await
+    }
+
+    fn derivation_indexes(&self) -> Result<Vec<(String, u32)>> {
+        self.inner.derivation_indexes()
+    }
+
+    async fn insert_pending_monero_refill(&self, refill: Option<TransferProof>) -> Result<()> {
+        self.inner.insert_pending_monero_refill(refill).await
+    }
+
+    fn get_pending_monero_refill(&self) -> Result<Option<TransferProof>> {
+        self.inner.get_pending_monero_refill()
+    }
+
+    async fn insert_utxo_label(&self, script: Script, label: UtxoLabel) -> Result<()> {
+        self.inner.insert_utxo_label(script, label).await
+    }
+
+    fn utxo_labels(&self) -> Result<Vec<(Script, UtxoLabel)>> {
+        self.inner.utxo_labels()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    /// Answers the first `failures` requests with an error and forwards the
+    /// bodies of all requests.
+    async fn http_receiver(failures: usize) -> (Url, mpsc::UnboundedReceiver<serde_json::Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/hook", listener.local_addr().unwrap())).unwrap();
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            for request in 0.. {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let body = read_body(&mut stream).await;
+                sender.send(serde_json::from_slice(&body).unwrap()).unwrap();
+
+                let response = if request < failures {
+                    "HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\n\r\n"
+                } else {
+                    "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n"
+                };
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        (url, receiver)
+    }
+
+    async fn read_body(stream: &mut tokio::net::TcpStream) -> Vec<u8> {
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 1024];
+
+        loop {
+            let read = stream.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..read]);
+
+            let text = String::from_utf8_lossy(&request).to_string();
+            if let Some(end_of_headers) = text.find("\r\n\r\n") {
+                let content_length = text[..end_of_headers]
+                    .lines()
+                    .find_map(|line| {
+                        let (name, value) = line.split_at(line.find(':')?);
+                        name.eq_ignore_ascii_case("content-length")
+                            .then(|| value[1..].trim().parse::<usize>().unwrap())
+                    })
+                    .unwrap();
+                let body_start = end_of_headers + 4;
+
+                if request.len() >= body_start + content_length {
+                    return request[body_start..body_start + content_length].to_vec();
+                }
+            }
+        }
+    }
+
+    fn hooks(hooks: Vec<config::Hook>) -> Hooks {
+        let mut hooks = Hooks::new(hooks).unwrap();
+        hooks.initial_retry_interval = Duration::from_millis(10);
+        hooks.max_retry_duration = Duration::from_secs(5);
+
+        hooks
+    }
+
+    async fn database_with_swap(swap_id: Uuid) -> (TempDir, Database) {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::open(dir.path()).unwrap();
+        db.insert_latest_state(
+            swap_id,
+            Swap::Alice(Alice::Done(AliceEndState::BtcRedeemed)),
+        )
+        .await
+        .unwrap();
+
+        (dir, db)
+    }
+
+    #[tokio::test]
+    async fn webhook_receives_payload_after_failed_delivery() {
+        let (url, mut received) = http_receiver(1).await;
+        let swap_id = Uuid::new_v4();
+        let (_dir, db) = database_with_swap(swap_id).await;
+        let hooks = hooks(vec![config::Hook {
+            events: vec![Event::BtcRedeemed],
+            url: Some(url),
+            command: None,
+        }]);
+
+        hooks.notify(&*db, swap_id, Event::BtcRedeemed, None);
+
+        let first = received.recv().await.unwrap();
+        let second = received.recv().await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(second["event"], "btc_redeemed");
+        assert_eq!(second["swap_id"], swap_id.to_string());
+        assert_eq!(second["state"], "Done: BtcRedeemed");
+    }
+
+    #[tokio::test]
+    async fn hooks_are_only_triggered_by_their_events() {
+        let (url, mut received) = http_receiver(0).await;
+        let swap_id = Uuid::new_v4();
+        let (_dir, db) = database_with_swap(swap_id).await;
+        let hooks = Arc::new(hooks(vec![config::Hook {
+            events: vec![Event::BtcCancelled, Event::Error],
+            url: Some(url),
+            command: None,
+        }]));
+        let hooked = HookedStorage::new(db, hooks.clone());
+
+        hooked
+            .insert_latest_state(
+                swap_id,
+                Swap::Alice(Alice::Done(AliceEndState::BtcPunished)),
+            )
+            .await
+            .unwrap();
+        hooks.notify(&hooked, swap_id, Event::Error, Some("failed".to_string()));
+
+        let payload = received.recv().await.unwrap();
+        assert_eq!(payload["event"], "error");
+        assert_eq!(payload["error"], "failed");
+        assert!(received.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn saving_the_latest_state_again_does_not_trigger_hooks() {
+        let (url, mut received) = http_receiver(0).await;
+        let swap_id = Uuid::new_v4();
+        let (_dir, db) = database_with_swap(swap_id).await;
+        let hooks = Arc::new(hooks(vec![config::Hook {
+            events: vec![Event::BtcRedeemed, Event::Error],
+            url: Some(url),
+            command: None,
+        }]));
+        let hooked = HookedStorage::new(db, hooks.clone());
+
+        hooked
+            .insert_latest_state(
+                swap_id,
+                Swap::Alice(Alice::Done(AliceEndState::BtcRedeemed)),
+            )
+            .await
+            .unwrap();
+        hooks.notify(&hooked, swap_id, Event::Error, Some("failed".to_string()));
+
+        let payload = received.recv().await.unwrap();
+        assert_eq!(payload["event"], "error");
+        assert!(received.try_recv().is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn command_receives_payload_on_stdin() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("payload.json");
+        let swap_id = Uuid::new_v4();
+        let (_dir, db) = database_with_swap(swap_id).await;
+        let hooks = hooks(vec![config::Hook {
+            events: vec![Event::BtcRedeemed],
+            url: None,
+            command: Some(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!("cat > {}", output.display()),
+            ]),
+        }]);
+
+        hooks.notify(&*db, swap_id, Event::BtcRedeemed, None);
+
+        for _ in 0..100 {
+            if let Ok(payload) = std::fs::read(&output) {
+                if let Ok(payload) = serde_json::from_slice::<serde_json::Value>(&payload) {
+                    assert_eq!(payload["swap_id"], swap_id.to_string());
+                    return;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("hook command did not write the payload");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn command_that_does_not_exit_in_time_is_retried() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("first-attempt");
+        let output = dir.path().join("payload.json");
+        let swap_id = Uuid::new_v4();
+        let (_dir, db) = database_with_swap(swap_id).await;
+        let mut hooks = hooks(vec![config::Hook {
+            events: vec![Event::BtcRedeemed],
+            url: None,
+            command: Some(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!(
+                    "if [ -e {marker} ]; then cat > {output}; else touch {marker}; sleep 60; fi",
+                    marker = marker.display(),
+                    output = output.display()
+                ),
+            ]),
+        }]);
+        hooks.delivery_timeout = Duration::from_millis(200);
+
+        hooks.notify(&*db, swap_id, Event::BtcRedeemed, None);
+
+        for _ in 0..100 {
+            if let Ok(payload) = std::fs::read(&output) {
+                if let Ok(payload) = serde_json::from_slice::<serde_json::Value>(&payload) {
+                    assert_eq!(payload["swap_id"], swap_id.to_string());
+                    return;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("hook command was not retried after the timeout");
+    }
+
+    #[test]
+    fn hook_without_target_is_rejected() {
+        let result = Hooks::new(vec![config::Hook {
+            events: vec![Event::Error],
+            url: None,
+            command: None,
+        }]);
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,282 @@
+//! Limits on how much the ASB trades.
+//!
+//! The limits are checked against the swaps in the database and the spot
+//! prices that were accepted since, whose swaps may not be saved yet.
+
+use crate::bitcoin;
+use crate::database::{Alice, AliceEndState, Storage};
+use crate::network::spot_price::TradingLimit;
+use anyhow::Result;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+/// The window the volume limits apply to.
+pub const VOLUME_WINDOW_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Limits {
+    /// The maximum BTC volume of the swaps started in the last 24 hours.
+    #[serde(
+        default,
+        with = "::bitcoin::util::amount::serde::as_btc::opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_volume_24h_btc: Option<bitcoin::Amount>,
+    /// The maximum number of swaps that are in progress at the same time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_swaps: Option<u64>,
+    /// The maximum BTC volume of the swaps started with a single counterparty
+    /// in the last 24 hours.
+    #[serde(
+        default,
+        with = "::bitcoin::util::amount::serde::as_btc::opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_volume_per_peer_24h_btc: Option<bitcoin::Amount>,
+}
+
+/// A swap as far as the limits are concerned.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Trade {
+    pub peer: Option<PeerId>,
+    pub btc: bitcoin::Amount,
+    /// Unix timestamp of the start of the swap.
+    pub started_at: Option<i64>,
+    pub finished: bool,
+}
+
+impl Trade {
+    /// Loads the trade of a swap in the given state from the database.
+    ///
+    /// Swaps that were safely aborted never locked any Bitcoin and do not
+    /// count as trades.
+    fn load(db: &dyn Storage, swap_id: Uuid, state: &Alice) -> Result<Option<Trade>> {
+        if let Alice::Done(AliceEndState::SafelyAborted) = state {
+            return Ok(None);
+        }
+
+        let details = db.get_details(swap_id)?;
+        let btc = match details.btc_amount {
+            Some(btc) => btc,
+            None => return Ok(None),
+        };
+
+        Ok(Some(Trade {
+            peer: db.get_peer_id(swap_id).ok(),
+            btc,
+            started_at: details.started_at,
+            finished: matches!(state, Alice::Done(_)),
+        }))
+    }
+}
+
+/// The trades of the swaps in the database.
+///
+/// The trades are loaded once and updated whenever the state of a swap is
+/// saved, so checking the limits does not read every swap from the database.
+#[derive(Clone, Debug, Default)]
+pub struct Trades {
+    inner: Arc<RwLock<HashMap<Uuid, Trade>>>,
+}
+
+impl Trades {
+    /// Loads the trades of all swaps in the database.
+    pub fn load(db: &dyn Storage) -> Result<Self> {
+        let mut trades = HashMap::new();
+
+        for (swap_id, state) in db.all_alice()? {
+            if let Some(trade) = Trade::load(db, swap_id, &state)? {
+                trades.insert(swap_id, trade);
+            }
+        }
+
+        Ok(Self {
+            inner: Arc::new(RwLock::new(trades)),
+        })
+    }
+
+    /// Updates the trade of a swap after its state was saved.
+    pub fn update(&self, db: &dyn Storage, swap_id: Uuid, state: &Alice) -> Result<()> {
+        let trade = Trade::load(db, swap_id, state)?;
+        let mut trades = self
+            .inner
+            .write()
+            .expect("no panic while holding the trades lock");
+
+        match trade {
+            Some(trade) => trades.insert(swap_id, trade),
+            None => trades.remove(&swap_id),
+        };
+
+        Ok(())
+    }
+
+    pub fn all(&self) -> Vec<Trade> {
+        self.inner
+            .read()
+            .expect("no panic while holding the trades lock")
+            .values()
+            .copied()
+            .collect()
+    }
+}
+
+impl Limits {
+    /// Checks whether buying `btc` from `peer` stays within the limits.
+    pub fn check<'a>(
+        &self,
+        trades: impl Iterator<Item = &'a Trade> + Clone,
+        peer: PeerId,
+        btc: bitcoin::Amount,
+        now: i64,
+    ) -> Result<(), TradingLimit> {
+        if let Some(max) = self.max_concurrent_swaps {
+            let in_progress = trades.clone().filter(|trade| !trade.finished).count();
+
+            if in_progress as u64 >= max {
+                return Err(TradingLimit::ConcurrentSwaps { max });
+            }
+        }
+
+        let recent = trades.filter(|trade| {
+            trade
+                .started_at
+                .map_or(false, |started_at| started_at > now - VOLUME_WINDOW_SECS)
+        });
+
+        if let Some(max) = self.max_volume_24h_btc {
+            let volume = sum(recent.clone());
+
+            if volume + btc > max {
+                return Err(TradingLimit::DailyVolume {
+                    max,
+                    remaining: remaining(max, volume),
+                });
+            }
+        }
+
+        if let Some(max) = self.max_volume_per_peer_24h_btc {
+            let volume = sum(recent.filter(|trade| trade.peer == Some(peer)));
+
+            if volume + btc > max {
+                return Err(TradingLimit::DailyVolumePerPeer {
+                    max,
+                    remaining: remaining(max, volume),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn sum<'a>(trades: impl Iterator<Item = &'a Trade>) -> bitcoin::Amount {
+    trades.fold(bitcoin::Amount::ZERO, |sum, trade| sum + trade.btc)
+}
+
+fn remaining(max: bitcoin::Amount, volume: bitcoin::Amount) -> bitcoin::Amount {
+    max.checked_sub(volume).unwrap_or(bitcoin::Amount::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_000_000;
+
+    fn btc(btc: f64) -> bitcoin::Amount {
+        bitcoin::Amount::from_btc(btc).unwrap()
+    }
+
+    fn trade(peer: PeerId, amount: f64, started_at: i64, finished: bool) -> Trade {
+        Trade {
+            peer: Some(peer),
+            btc: btc(amount),
+            started_at: Some(started_at),
+            finished,
+        }
+    }
+
+    #[test]
+    fn no_limits_accept_everything() {
+        let peer = PeerId::random();
+        let trades = vec![trade(peer, 100.0, NOW, false)];
+
+        assert_eq!(
+            Limits::default().check(trades.iter(), peer, btc(100.0), NOW),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn volume_limit_only_counts_the_last_24_hours() {
+        let peer = PeerId::random();
+        let limits = Limits {
+            max_volume_24h_btc: Some(btc(1.0)),
+            ..Limits::default()
+        };
+        let trades = vec![
+            trade(PeerId::random(), 0.8, NOW - VOLUME_WINDOW_SECS, true),
+            trade(PeerId::random(), 0.5, NOW - 60, true),
+        ];
+
+        assert_eq!(limits.check(trades.iter(), peer, btc(0.5), NOW), Ok(()));
+        assert_eq!(
+            limits.check(trades.iter(), peer, btc(0.6), NOW),
+            Err(TradingLimit::DailyVolume {
+                max: btc(1.0),
+                remaining: btc(0.5),
+            })
+        );
+    }
+
+    #[test]
+    fn concurrency_limit_only_counts_unfinished_swaps() {
+        let peer = PeerId::random();
+        let limits = Limits {
+            max_concurrent_swaps: Some(2),
+            ..Limits::default()
+        };
+        let mut trades = vec![
+            trade(PeerId::random(), 0.1, NOW, true),
+            trade(PeerId::random(), 0.1, NOW, false),
+        ];
+
+        assert_eq!(limits.check(trades.iter(), peer, btc(0.1), NOW), Ok(()));
+
+        trades.push(trade(PeerId::random(), 0.1, NOW, false));
+        assert_eq!(
+            limits.check(trades.iter(), peer, btc(0.1), NOW),
+            Err(TradingLimit::ConcurrentSwaps { max: 2 })
+        );
+    }
+
+    #[test]
+    fn peer_limit_only_counts_swaps_of_the_peer() {
+        let peer = PeerId::random();
+        let limits = Limits {
+            max_volume_per_peer_24h_btc: Some(btc(1.0)),
+            ..Limits::default()
+        };
+        let trades = vec![
+            trade(PeerId::random(), 5.0, NOW, true),
+            trade(peer, 0.7, NOW, true),
+        ];
+
+        assert_eq!(
+            limits.check(trades.iter(), PeerId::random(), btc(1.0), NOW),
+            Ok(())
+        );
+        assert_eq!(
+            limits.check(trades.iter(), peer, btc(0.5), NOW),
+            Err(TradingLimit::DailyVolumePerPeer {
+                max: btc(1.0),
+                remaining: btc(0.3),
+            })
+        );
+    }
+}
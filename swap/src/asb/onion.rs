@@ -0,0 +1,168 @@
+//! A persistent onion service for the ASB.
+//!
+//! The onion address is derived from the seed, so it stays the same across
+//! restarts. The service is registered detached and survives the control
+//! connection, but Tor forgets it whenever Tor itself restarts. The control
+//! connection is therefore supervised and the service registered again once
+//! Tor is back.
+
+use crate::seed::Seed;
+use crate::tor;
+use anyhow::Result;
+use libp2p::core::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+use std::net::{Ipv4Addr, SocketAddr};
+use torut::onion::TorSecretKeyV3;
+
+pub struct OnionService {
+    tor_client: tor::Client,
+    key: TorSecretKeyV3,
+    /// The onion ports and the local addresses they are forwarded to.
+    ports: Vec<(u16, SocketAddr)>,
+    /// The protocols after the TCP port of each listen address, e.g. `/ws`.
+    suffixes: Vec<Multiaddr>,
+}
+
+impl OnionService {
+    /// Forwards every TCP port the ASB listens on through the onion service.
+    pub fn new(tor_client: tor::Client, seed: &Seed, listen: &[Multiaddr]) -> Self {
+        let (ports, suffixes) = listen
+            .iter()
+            .filter_map(|address| {
+                let mut protocols = address.iter();
+                let port = protocols.find_map(|protocol| match protocol {
+                    Protocol::Tcp(port) => Some(port),
+                    _ => None,
+                })?;
+                let suffix = protocols.collect::<Multiaddr>();
+
+                Some((
+                    (port, SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port)),
+                    suffix,
+                ))
+            })
+            .unzip();
+
+        Self {
+            tor_client,
+            key: seed.derive_torv3_key(),
+            ports,
+            suffixes,
+        }
+    }
+
+    /// The onion addresses of the service, one per listen address.
+    pub fn multiaddrs(&self) -> Vec<Multiaddr> {
+        let onion_address = self
+            .key
+            .public()
+            .get_onion_address()
+            .get_address_without_dot_onion();
+
+        self.ports
+            .iter()
+            .zip(&self.suffixes)
+            .filter_map(|((port, _), suffix)| {
+                let address = format!("/onion3/{}:{}", onion_address, port)
+                    .parse::<Multiaddr>()
+                    .ok()?;
+
+                Some(suffix.iter().fold(address, Multiaddr::with))
+            })
+            .collect()
+    }
+
+    /// Registers the service with Tor.
+    pub async fn register(&self) -> Result<tor::AuthenticatedClient> {
+        let mut client = self.tor_client.into_authenticated_client().await?;
+        client.add_services(&self.ports, &self.key).await?;
+
+        Ok(client)
+    }
+
+    /// Registers the service again whenever the control connection is lost
+    /// until the ASB is stopped.
+    pub async fn supervise(self, client: tor::AuthenticatedClient) {
+        tor::supervise("onion service", Some(client), || self.register()).await
+    }
+}
+
+/// The onion addresses among the listen addresses.
+///
+/// The transport of the ASB registers the hidden service for these itself.
+pub fn listen_addresses(listen: &[Multiaddr]) -> Vec<Multiaddr> {
+    listen
+        .iter()
+        .filter(|address| is_onion(address))
+        .cloned()
+        .collect()
+}
+
+/// Whether the address is an onion address.
+pub fn is_onion(address: &Multiaddr) -> bool {
+    matches!(address.iter().next(), Some(Protocol::Onion3(_)))
+}
+
+/// Appends the peer id so the address can be dialed directly.
+pub fn with_peer_id(address: Multiaddr, peer_id: PeerId) -> Multiaddr {
+    address.with(Protocol::P2p(peer_id.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn onion_listen_addresses_are_filtered() {
+        let onion_address: Multiaddr =
+            "/onion3/oarchy4tamydxcitaki6bc2v4leza6v35iezmu2chg2bap63sv6f2did:9939"
+                .parse()
+                .unwrap();
+        let listen = vec![
+            "/ip4/0.0.0.0/tcp/9939".parse().unwrap(),
+            onion_address.clone(),
+        ];
+
+        assert_eq!(listen_addresses(&listen), vec![onion_address]);
+    }
+
+    #[test]
+    fn only_onion_addresses_are_onion() {
+        let onion_address: Multiaddr =
+            "/onion3/oarchy4tamydxcitaki6bc2v4leza6v35iezmu2chg2bap63sv6f2did:9939/ws"
+                .parse()
+                .unwrap();
+
+        assert!(is_onion(&onion_address));
+        assert!(!is_onion(&"/ip4/1.2.3.4/tcp/9939".parse().unwrap()));
+        assert!(!is_onion(&"/dns4/example.org/tcp/9939".parse().unwrap()));
+    }
+
+    #[test]
+    fn forwards_tcp_ports_of_all_listen_addresses_with_their_protocols() {
+        let seed = Seed::random().unwrap();
+        let listen = vec![
+            "/ip4/0.0.0.0/tcp/9939".parse().unwrap(),
+            "/ip4/0.0.0.0/tcp/9940/ws".parse().unwrap(),
+        ];
+
+        let service = OnionService::new(tor::Client::default(), &seed, &listen);
+        let onion_address = seed
+            .derive_torv3_key()
+            .public()
+            .get_onion_address()
+            .get_address_without_dot_onion();
+
+        assert_eq!(
+            service.multiaddrs(),
+            vec![
+                format!("/onion3/{}:9939", onion_address)
+                    .parse::<Multiaddr>()
+                    .unwrap(),
+                format!("/onion3/{}:9940/ws", onion_address)
+                    .parse::<Multiaddr>()
+                    .unwrap(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,383 @@
+//! A price feed that reads the ask price from a local source instead of the
+//! Kraken price ticker.
+//!
+//! The source is a JSON file, a Unix socket that writes the JSON and closes
+//! the connection, or a command that prints the JSON to stdout. It is read
+//! again every refresh interval, a read that takes longer is aborted. The JSON
+//! is an object with the ask price of 1 XMR in BTC and optionally the unix
+//! timestamp of the price, e.g. `{"ask": 0.0044, "timestamp": 1626000000}`.
+//!
+//! Like the Kraken price ticker, the rate is not available until the source
+//! was read successfully once. If reading it fails afterwards, the last price
+//! is kept until the next read succeeds or it exceeds the maximum age. Without
+//! a timestamp the age counts from the modification time of the file or the
+//! time the price was read.
+
+use crate::asb::config;
+use crate::bitcoin;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::sync::watch;
+
+/// Where the price feed reads the ask price from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    File(PathBuf),
+    Socket(PathBuf),
+    Command(Vec<String>),
+}
+
+impl Source {
+    pub fn from_config(price_feed: config::PriceFeed) -> Result<Self> {
+        let source = match (price_feed.file, price_feed.socket, price_feed.command) {
+            (Some(path), None, None) => Source::File(path),
+            (None, Some(path), None) => Source::Socket(path),
+            (None, None, Some(command)) if !command.is_empty() => Source::Command(command),
+            (None, None, Some(_)) => bail!("The command of the price feed must not be empty"),
+            _ => bail!("The price feed needs exactly one of a file, a socket or a command"),
+        };
+
+        Ok(source)
+    }
+}
+
+/// Reads the ask price from the source every `refresh_interval` for a
+/// constant stream of rate updates.
+///
+/// Prices older than `max_age` are reported as [`Error::Outdated`].
+pub fn connect(source: Source, refresh_interval: Duration, max_age: Duration) -> PriceUpdates {
+    let (price_update, price_update_receiver) = watch::channel(Err(Error::NotYetAvailable));
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(refresh_interval);
+
+        loop {
+            interval.tick().await;
+
+            match tokio::time::timeout(refresh_interval, read(&source)).await {
+                Ok(Ok(update)) => {
+                    if price_update.send(Ok(update)).is_err() {
+                        tracing::debug!("Price feed receiver disconnected, stop reading");
+                        return;
+                    }
+                }
+                Ok(Err(error)) => {
+                    tracing::warn!(
+                        "Failed to read the price feed, keeping the last price. Error {:#}",
+                        error
+                    );
+                }
+                Err(_) => {
+                    tracing::warn!(
+                        "Reading the price feed took longer than {}s, keeping the last price",
+                        refresh_interval.as_secs_f64()
+                    );
+                }
+            }
+        }
+    });
+
+    PriceUpdates {
+        inner: price_update_receiver,
+        max_age,
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PriceUpdates {
+    inner: watch::Receiver<Result<PriceUpdate, Error>>,
+    max_age: Duration,
+}
+
+impl PriceUpdates {
+    pub async fn wait_for_next_update(&mut self) -> Result<Result<PriceUpdate, Error>> {
+        self.inner.changed().await?;
+
+        Ok(self.latest_update())
+    }
+
+    pub fn latest_update(&mut self) -> Result<PriceUpdate, Error> {
+        let update = self.inner.borrow().clone()?;
+
+        let age = OffsetDateTime::now_utc().unix_timestamp() - update.timestamp;
+        if age > self.max_age.as_secs() as i64 {
+            return Err(Error::Outdated {
+                timestamp: update.timestamp,
+                max_age_secs: self.max_age.as_secs(),
+            });
+        }
+
+        Ok(update)
+    }
+}
+
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Rate is not yet available")]
+    NotYetAvailable,
+    #[error("Rate from {timestamp} is older than the maximum age of {max_age_secs} seconds")]
+    Outdated { timestamp: i64, max_age_secs: u64 },
+}
+
+/// The price read from the source.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PriceUpdate {
+    pub ask: bitcoin::Amount,
+    /// The unix timestamp the age of the price counts from.
+    pub timestamp: i64,
+}
+
+/// The JSON provided by the source.
+#[derive(Debug, Deserialize)]
+struct Price {
+    #[serde(with = "::bitcoin::util::amount::serde::as_btc")]
+    ask: bitcoin::Amount,
+    timestamp: Option<i64>,
+}
+
+async fn read(source: &Source) -> Result<PriceUpdate> {
+    let mut modified = None;
+    let json = match source {
+        Source::File(path) => {
+            let json = tokio::fs::read(path)
+                .await
+                .with_context(|| format!("Failed to read price feed file {}", path.display()))?;
+            modified = tokio::fs::metadata(path)
+                .await
+                .and_then(|metadata| metadata.modified())
+                .ok();
+
+            json
+        }
+        Source::Socket(path) => read_socket(path).await?,
+        Source::Command(command) => {
+            let output = tokio::process::Command::new(&command[0])
+                .args(&command[1..])
+                .stdin(Stdio::null())
+                .stderr(Stdio::null())
+                .kill_on_drop(true)
+                .output()
+                .await
+                .with_context(|| format!("Failed to run price feed command {}", command[0]))?;
+
+            if !output.status.success() {
+                bail!(
+                    "Price feed command {} exited with {}",
+                    command[0],
+                    output.status
+                )
+            }
+
+            output.stdout
+        }
+    };
+
+    let price: Price = serde_json::from_slice(&json).context("Failed to parse price feed")?;
+    let timestamp = match (price.timestamp, modified) {
+        (Some(timestamp), _) => timestamp,
+        (None, Some(modified)) => OffsetDateTime::from(modified).unix_timestamp(),
+        (None, None) => OffsetDateTime::now_utc().unix_timestamp(),
+    };
+
+    Ok(PriceUpdate {
+        ask: price.ask,
+        timestamp,
+    })
+}
+
+#[cfg(unix)]
+async fn read_socket(path: &std::path::Path) -> Result<Vec<u8>> {
+    use tokio::io::AsyncReadExt;
+
+    let mut stream = tokio::net::UnixStream::connect(path)
+        .await
+        .with_context(|| format!("Failed to connect to price feed socket {}", path.display()))?;
+    let mut json = Vec::new();
+    stream.read_to_end(&mut json).await?;
+
+    Ok(json)
+}
+
+/// Unix sockets only exist on unix.
+#[cfg(not(unix))]
+async fn read_socket(_: &std::path::Path) -> Result<Vec<u8>> {
+    bail!("Price feed sockets are not supported on this platform")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const REFRESH_INTERVAL: Duration = Duration::from_millis(10);
+    const MAX_AGE: Duration = Duration::from_secs(60);
+
+    #[tokio::test]
+    async fn reads_the_price_from_a_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("price.json");
+        std::fs::write(&path, r#"{"ask": 0.0044}"#).unwrap();
+
+        let mut price_updates = connect(Source::File(path.clone()), REFRESH_INTERVAL, MAX_AGE);
+        let update = price_updates.wait_for_next_update().await.unwrap().unwrap();
+        assert_eq!(update.ask, bitcoin::Amount::from_btc(0.0044).unwrap());
+
+        std::fs::write(&path, r#"{"ask": 0.005}"#).unwrap();
+        let update = wait_for_ask(
+            &mut price_updates,
+            bitcoin::Amount::from_btc(0.005).unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(update.ask, bitcoin::Amount::from_btc(0.005).unwrap());
+    }
+
+    #[tokio::test]
+    async fn rate_is_not_available_until_the_first_read() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("price.json");
+
+        let mut price_updates = connect(Source::File(path.clone()), REFRESH_INTERVAL, MAX_AGE);
+        tokio::time::sleep(REFRESH_INTERVAL * 5).await;
+        assert!(matches!(
+            price_updates.latest_update(),
+            Err(Error::NotYetAvailable)
+        ));
+
+        std::fs::write(&path, r#"{"ask": 0.0044}"#).unwrap();
+        let update = price_updates.wait_for_next_update().await.unwrap().unwrap();
+        assert_eq!(update.ask, bitcoin::Amount::from_btc(0.0044).unwrap());
+    }
+
+    #[tokio::test]
+    async fn failed_reads_keep_the_last_price() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("price.json");
+        std::fs::write(&path, r#"{"ask": 0.0044}"#).unwrap();
+
+        let mut price_updates = connect(Source::File(path.clone()), REFRESH_INTERVAL, MAX_AGE);
+        price_updates.wait_for_next_update().await.unwrap().unwrap();
+
+        std::fs::write(&path, "not a price").unwrap();
+        tokio::time::sleep(REFRESH_INTERVAL * 5).await;
+        std::fs::remove_file(&path).unwrap();
+        tokio::time::sleep(REFRESH_INTERVAL * 5).await;
+
+        let update = price_updates.latest_update().unwrap();
+        assert_eq!(update.ask, bitcoin::Amount::from_btc(0.0044).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reads_the_price_from_a_socket() {
+        use tokio::io::AsyncWriteExt;
+        use tokio::net::UnixListener;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("price.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                stream.write_all(br#"{"ask": 0.0044}"#).await.unwrap();
+            }
+        });
+
+        let mut price_updates = connect(Source::Socket(path), REFRESH_INTERVAL, MAX_AGE);
+        let update = price_updates.wait_for_next_update().await.unwrap().unwrap();
+
+        assert_eq!(update.ask, bitcoin::Amount::from_btc(0.0044).unwrap());
+    }
+
+    #[tokio::test]
+    async fn given_price_older_than_max_age_then_rate_is_outdated() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("price.json");
+        let timestamp = OffsetDateTime::now_utc().unix_timestamp() - 3600;
+        std::fs::write(
+            &path,
+            format!(r#"{{"ask": 0.0044, "timestamp": {}}}"#, timestamp),
+        )
+        .unwrap();
+
+        let mut price_updates = connect(Source::File(path), REFRESH_INTERVAL, MAX_AGE);
+        let update = price_updates.wait_for_next_update().await.unwrap();
+
+        assert!(matches!(
+            update,
+            Err(Error::Outdated {
+                timestamp: outdated,
+                max_age_secs: 60
+            }) if outdated == timestamp
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn given_socket_hangs_then_read_times_out() {
+        use tokio::io::AsyncWriteExt;
+        use tokio::net::UnixListener;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("price.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move {
+            // Never answer the first read
+            let (_hanging, _) = listener.accept().await.unwrap();
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                stream.write_all(br#"{"ask": 0.0044}"#).await.unwrap();
+            }
+        });
+
+        let mut price_updates = connect(Source::Socket(path), REFRESH_INTERVAL, MAX_AGE);
+        let update = price_updates.wait_for_next_update().await.unwrap().unwrap();
+
+        assert_eq!(update.ask, bitcoin::Amount::from_btc(0.0044).unwrap());
+    }
+
+    #[test]
+    fn price_feed_needs_exactly_one_source() {
+        let price_feed = config::PriceFeed {
+            file: Some(PathBuf::from("/var/lib/asb/price.json")),
+            socket: None,
+            command: None,
+            refresh_interval_secs: 10,
+            max_age_secs: 120,
+        };
+
+        assert_eq!(
+            Source::from_config(price_feed.clone()).unwrap(),
+            Source::File(PathBuf::from("/var/lib/asb/price.json"))
+        );
+        assert!(Source::from_config(config::PriceFeed {
+            socket: Some(PathBuf::from("/run/asb/price.sock")),
+            ..price_feed.clone()
+        })
+        .is_err());
+        assert!(Source::from_config(config::PriceFeed {
+            file: None,
+            ..price_feed
+        })
+        .is_err());
+    }
+
+    /// Waits until the ask of the latest update is `ask`, the file may be
+    /// read while it is written.
+    async fn wait_for_ask(
+        price_updates: &mut PriceUpdates,
+        ask: bitcoin::Amount,
+    ) -> Result<PriceUpdate> {
+        loop {
+            if let Ok(update) = price_updates.wait_for_next_update().await? {
+                if update.ask == ask {
+                    return Ok(update);
+                }
+            }
+        }
+    }
+}
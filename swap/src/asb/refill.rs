@@ -0,0 +1,284 @@
+//! Alerts about a low Monero balance and refills from a treasury wallet.
+//!
+//! Once the Monero balance of the ASB drops below the low watermark it can no
+//! longer sell the amounts it advertises. The ASB then triggers the
+//! [`Event::LowMoneroBalance`] hooks and, if a treasury wallet is configured,
+//! transfers the refill amount from the treasury into its own wallet.
+//!
+//! The refill is saved in the database until it is confirmed. The balance of
+//! the wallet does not include unconfirmed transfers, without tracking them
+//! every check would send another refill until the first one is confirmed.
+//! A refill stays pending as long as its transfer waits in the mempool. Only a
+//! refill whose transfer is unknown to the wallet for [`DROPPED_REFILL_TIMEOUT`]
+//! is given up on, the ASB triggers the [`Event::MoneroRefillExpired`] hooks
+//! and sends another refill on the next check.
+
+use crate::asb::hooks::{Event, Hooks};
+use crate::database::Database;
+use crate::env::Config;
+use crate::monero;
+use anyhow::{Context, Result};
+use monero_rpc::wallet;
+use monero_rpc::wallet::MoneroWalletRpc as _;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// How often the Monero balance is checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(2 * 60);
+
+/// How long the transfer of a refill may be unknown to the wallet before it is
+/// given up on.
+///
+/// A transfer that was dropped from the mempool is unknown, but so is one the
+/// wallet failed to check, the refill is only given up on if it stays unknown.
+const DROPPED_REFILL_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// A second Monero wallet the trading wallet is refilled from.
+#[derive(Debug)]
+pub struct Treasury {
+    wallet: monero::Wallet,
+    refill: monero::Amount,
+}
+
+impl Treasury {
+    /// Opens the treasury wallet in the given monero-wallet-rpc.
+    ///
+    /// Unlike the trading wallet the treasury wallet is never created.
+    pub async fn open(
+        url: Url,
+        name: String,
+        refill: monero::Amount,
+        env_config: Config,
+    ) -> Result<Self> {
+        let client = wallet::Client::new(url)?;
+        client
+            .open_wallet(name.clone())
+            .await
+            .with_context(|| format!("Failed to open Monero treasury wallet {}", name))?;
+
+        let wallet = monero::Wallet::connect(client, name, env_config).await?;
+
+        Ok(Self { wallet, refill })
+    }
+}
+
+pub struct Refill {
+    monero_wallet: Arc<monero::Wallet>,
+    low_watermark: monero::Amount,
+    treasury: Option<Treasury>,
+    db: Arc<Database>,
+    hooks: Arc<Hooks>,
+    /// Whether the alert for the current low balance was already triggered.
+    alerted: bool,
+    /// The refill in the database that is waiting for its confirmation.
+    pending: PendingRefill,
+}
+
+impl Refill {
+    pub fn new(
+        monero_wallet: Arc<monero::Wallet>,
+        low_watermark: monero::Amount,
+        treasury: Option<Treasury>,
+        db: Arc<Database>,
+        hooks: Arc<Hooks>,
+    ) -> Self {
+        Self {
+            monero_wallet,
+            low_watermark,
+            treasury,
+            db,
+            hooks,
+            alerted: false,
+            pending: PendingRefill::default(),
+        }
+    }
+
+    /// Checks the Monero balance until the ASB is stopped.
+    pub async fn run(mut self) {
+        loop {
+            if let Err(error) = self.check_balance().await {
+                tracing::warn!("Failed to check Monero balance for refill: {:#}", error);
+            }
+
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    }
+
+    async fn check_balance(&mut self) -> Result<()> {
+        let balance = self.monero_wallet.get_balance().await?;
+
+        if balance >= self.low_watermark {
+            self.alerted = false;
+            return Ok(());
+        }
+
+        if !self.alerted {
+            tracing::warn!(%balance, low_watermark = %self.low_watermark, "Monero balance is low");
+            self.hooks
+                .notify_monero_balance(Event::LowMoneroBalance, balance);
+            self.alerted = true;
+        }
+
+        let treasury = match &self.treasury {
+            Some(treasury) => treasury,
+            None => return Ok(()),
+        };
+        let address = self.monero_wallet.get_main_address();
+
+        if let Some(refill) = self.db.get_pending_monero_refill()? {
+            let confirmations = match self
+                .monero_wallet
+                .transfer_confirmations(&refill, address)
+                .await
+            {
+                Ok(confirmations) => Some(confirmations),
+                Err(error) => {
+                    tracing::warn!(txid = %refill.tx_hash(), "Failed to check Monero refill: {:#}", error);
+                    None
+                }
+            };
+
+            match self.pending.check(confirmations, Instant::now()) {
+                RefillCheck::Pending => {
+                    tracing::debug!(txid = %refill.tx_hash(), "Monero refill is not confirmed yet");
+                }
+                RefillCheck::Confirmed => {
+                    tracing::info!(txid = %refill.tx_hash(), "Monero refill confirmed");
+                    self.db.insert_pending_monero_refill(None).await?;
+
+                    // A confirmed refill only shows up in the balance of the next check.
+                }
+                RefillCheck::Expired => {
+                    tracing::error!(
+                        txid = %refill.tx_hash(),
+                        "Monero refill was unknown to the wallet for {} minutes, giving up on it",
+                        DROPPED_REFILL_TIMEOUT.as_secs() / 60
+                    );
+                    self.hooks
+                        .notify_monero_balance(Event::MoneroRefillExpired, balance);
+                    self.db.insert_pending_monero_refill(None).await?;
+
+                    // The next check sends another refill if the balance is still low.
+                }
+            }
+
+            return Ok(());
+        }
+
+        let refill = treasury
+            .wallet
+            .transfer_to(address, treasury.refill)
+            .await
+            .context("Failed to transfer Monero from treasury")?;
+        self.db
+            .insert_pending_monero_refill(Some(refill.clone()))
+            .await?;
+
+        tracing::info!(amount = %treasury.refill, txid = %refill.tx_hash(), "Sent Monero refill from treasury");
+        self.hooks
+            .notify_monero_balance(Event::MoneroRefillSent, balance);
+
+        Ok(())
+    }
+}
+
+/// Tracks whether the pending refill is still on its way.
+#[derive(Debug, Default)]
+struct PendingRefill {
+    /// Since when the transfer of the refill is unknown to the wallet.
+    unknown_since: Option<Instant>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RefillCheck {
+    Pending,
+    Confirmed,
+    Expired,
+}
+
+impl PendingRefill {
+    /// Decides about the pending refill given the confirmations of its
+    /// transfer, `None` if the transfer is unknown to the wallet.
+    fn check(&mut self, confirmations: Option<u64>, now: Instant) -> RefillCheck {
+        let unknown_since = match confirmations {
+            Some(0) => {
+                self.unknown_since = None;
+                return RefillCheck::Pending;
+            }
+            Some(_) => {
+                self.unknown_since = None;
+                return RefillCheck::Confirmed;
+            }
+            None => *self.unknown_since.get_or_insert(now),
+        };
+
+        if now.duration_since(unknown_since) < DROPPED_REFILL_TIMEOUT {
+            return RefillCheck::Pending;
+        }
+
+        self.unknown_since = None;
+        RefillCheck::Expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refill_in_mempool_stays_pending() {
+        let mut pending = PendingRefill::default();
+        let sent = Instant::now();
+
+        assert_eq!(pending.check(Some(0), sent), RefillCheck::Pending);
+        assert_eq!(
+            pending.check(Some(0), sent + DROPPED_REFILL_TIMEOUT * 3),
+            RefillCheck::Pending
+        );
+    }
+
+    #[test]
+    fn refill_with_confirmation_is_confirmed() {
+        let mut pending = PendingRefill::default();
+
+        assert_eq!(
+            pending.check(Some(1), Instant::now()),
+            RefillCheck::Confirmed
+        );
+    }
+
+    #[test]
+    fn unknown_refill_expires_after_timeout() {
+        let mut pending = PendingRefill::default();
+        let dropped = Instant::now();
+
+        assert_eq!(pending.check(None, dropped), RefillCheck::Pending);
+        assert_eq!(
+            pending.check(None, dropped + DROPPED_REFILL_TIMEOUT / 2),
+            RefillCheck::Pending
+        );
+        assert_eq!(
+            pending.check(None, dropped + DROPPED_REFILL_TIMEOUT),
+            RefillCheck::Expired
+        );
+    }
+
+    #[test]
+    fn refill_seen_again_restarts_the_timeout() {
+        let mut pending = PendingRefill::default();
+        let dropped = Instant::now();
+
+        assert_eq!(pending.check(None, dropped), RefillCheck::Pending);
+        let seen = dropped + DROPPED_REFILL_TIMEOUT / 2;
+        assert_eq!(pending.check(Some(0), seen), RefillCheck::Pending);
+        assert_eq!(
+            pending.check(None, dropped + DROPPED_REFILL_TIMEOUT),
+            RefillCheck::Pending
+        );
+        assert_eq!(
+            pending.check(None, dropped + DROPPED_REFILL_TIMEOUT * 2),
+            RefillCheck::Expired
+        );
+    }
+}
@@ -0,0 +1,324 @@
+//! Reloads the maker parameters from the config file while the ASB is running.
+//!
+//! On `SIGHUP` the config file is read again. Changes to the `[maker]` section
+//! are applied to new swaps right away, swaps in progress are not affected.
+//! All other sections, the price ticker and the price feed, are only read at
+//! startup, a config that changes them is rejected as a whole.
+
+use crate::asb::config::{read_config, Config, Maker};
+use crate::protocol::alice::event_loop::{MakerParams, MakerRate};
+use anyhow::{bail, Context, Result};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+pub struct Reload {
+    config_path: PathBuf,
+    config: Config,
+    /// Whether the ASB was started with `--resume-only`, which always wins over
+    /// the config.
+    resume_only: bool,
+    maker_rate: MakerRate,
+    maker_updates: mpsc::Sender<MakerParams>,
+}
+
+impl Reload {
+    pub fn new(
+        config_path: PathBuf,
+        config: Config,
+        resume_only: bool,
+        maker_rate: MakerRate,
+        maker_updates: mpsc::Sender<MakerParams>,
+    ) -> Self {
+        Self {
+            config_path,
+            config,
+            resume_only,
+            maker_rate,
+            maker_updates,
+        }
+    }
+
+    /// Reloads the config on every `SIGHUP` until the ASB is stopped.
+    #[cfg(unix)]
+    pub async fn run(mut self) {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(error) => {
+                tracing::warn!(
+                    "Failed to listen for SIGHUP, reloading the config is disabled: {:#}",
+                    error
+                );
+                return;
+            }
+        };
+
+        while hangup.recv().await.is_some() {
+            tracing::info!("Received SIGHUP, reloading config");
+
+            if let Err(error) = self.reload().await {
+                tracing::error!("Failed to reload config: {:#}", error);
+            }
+        }
+    }
+
+    /// Reloading is triggered by `SIGHUP` which only exists on unix.
+    #[cfg(not(unix))]
+    pub async fn run(self) {
+        tracing::debug!("Reloading the config is not supported on this platform");
+    }
+
+    async fn reload(&mut self) -> Result<()> {
+        let config = read_config(self.config_path.clone())?
+            .map_err(|_| anyhow::anyhow!("Config file was removed"))?;
+
+        check_reloadable(&self.config, &config)?;
+
+        self.maker_rate.update_ask_spread(config.maker.ask_spread);
+        self.maker_updates
+            .send(maker_params(&config, self.resume_only))
+            .await
+            .context("Event loop stopped")?;
+
+        self.config = config;
+
+        Ok(())
+    }
+}
+
+pub fn maker_params(config: &Config, resume_only: bool) -> MakerParams {
+    MakerParams {
+        min_buy: config.maker.min_buy_btc,
+        max_buy: config.maker.max_buy_btc,
+        resume_only: resume_only || config.maker.resume_only,
+        limits: config.maker.limits,
+        timelocks: config.maker.timelocks,
+        quote_validity: config.maker.quote_validity_secs.map(Duration::from_secs),
+    }
+}
+
+/// Checks that the new config only changes what can be applied while the ASB
+/// is running.
+pub fn check_reloadable(current: &Config, new: &Config) -> Result<()> {
+    let mut changed = Vec::new();
+
+    if current.data != new.data {
+        changed.push("[data]");
+    }
+    if current.network != new.network {
+        changed.push("[network]");
+    }
+    if current.bitcoin != new.bitcoin {
+        changed.push("[bitcoin]");
+    }
+    if current.monero != new.monero {
+        changed.push("[monero]");
+    }
+    if current.tor != new.tor {
+        changed.push("[tor]");
+    }
+    if current.hooks != new.hooks {
+        changed.push("[[hooks]]");
+    }
+    if current.maker.price_ticker_ws_url != new.maker.price_ticker_ws_url {
+        changed.push("price_ticker_ws_url of [maker]");
+    }
+    if current.maker.price_feed != new.maker.price_feed {
+        changed.push("price_feed of [maker]");
+    }
+
+    if !changed.is_empty() {
+        bail!(
+            "Changes to {} can only be applied by restarting the ASB, no changes were applied",
+            changed.join(", ")
+        )
+    }
+
+    check_maker(&new.maker)
+}
+
+/// Checks that the minimums of the `[maker]` section are not above the
+/// maximums and that the intervals of the price feed are usable, on startup
+/// as well as on reload.
+pub fn check_maker(maker: &Maker) -> Result<()> {
+    if maker.min_buy_btc > maker.max_buy_btc {
+        bail!(
+            "The minimum buy amount {} is above the maximum buy amount {}",
+            maker.min_buy_btc,
+            maker.max_buy_btc
+        )
+    }
+
+    if let Some(timelocks) = maker.timelocks {
+        if timelocks.min_cancel > timelocks.max_cancel
+            || timelocks.min_punish > timelocks.max_punish
+        {
+            bail!(
+                "The minimum timelocks are above the maximum timelocks: {}",
+                timelocks
+            )
+        }
+    }
+
+    if let Some(price_feed) = &maker.price_feed {
+        if price_feed.refresh_interval_secs == 0 {
+            bail!("The refresh interval of the price feed must be at least one second")
+        }
+        if price_feed.max_age_secs < price_feed.refresh_interval_secs {
+            bail!(
+                "The maximum age of the price feed of {} seconds is below its refresh interval of {} seconds",
+                price_feed.max_age_secs,
+                price_feed.refresh_interval_secs
+            )
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asb::config::{Bitcoin, Data, Maker, Monero, Network, PriceFeed, TorConf};
+    use crate::bitcoin::{CancelTimelock, PunishTimelock};
+    use crate::network::spot_price::TimelockRanges;
+    use rust_decimal::prelude::FromPrimitive;
+    use rust_decimal::Decimal;
+
+    fn config() -> Config {
+        Config {
+            data: Data {
+                dir: Default::default(),
+                database: Default::default(),
+                seed_passphrase_file: None,
+            },
+            bitcoin: Bitcoin {
+                electrum_rpc_url: "ssl://electrum.blockstream.info:60002".parse().unwrap(),
+                target_block: 3,
+                finality_confirmations: None,
+                network: ::bitcoin::Network::Testnet,
+                cold_storage: None,
+            },
+            network: Network {
+                listen: vec!["/ip4/0.0.0.0/tcp/9939".parse().unwrap()],
+            },
+            monero: Monero {
+                wallet_rpc_url: "http://127.0.0.1:38083/json_rpc".parse().unwrap(),
+                finality_confirmations: None,
+                network: ::monero::Network::Stagenet,
+                refill: None,
+            },
+            tor: TorConf::default(),
+            maker: Maker {
+                min_buy_btc: ::bitcoin::Amount::from_btc(0.002).unwrap(),
+                max_buy_btc: ::bitcoin::Amount::from_btc(0.02).unwrap(),
+                ask_spread: Decimal::from_f64(0.02).unwrap(),
+                price_ticker_ws_url: "wss://ws.kraken.com".parse().unwrap(),
+                resume_only: false,
+                limits: Default::default(),
+                timelocks: None,
+                quote_validity_secs: None,
+                price_feed: None,
+            },
+            hooks: vec![],
+        }
+    }
+
+    #[test]
+    fn maker_changes_are_reloadable() {
+        let current = config();
+        let mut new = config();
+        new.maker.ask_spread = Decimal::from_f64(0.05).unwrap();
+        new.maker.max_buy_btc = ::bitcoin::Amount::from_btc(1.0).unwrap();
+        new.maker.resume_only = true;
+        new.maker.limits.max_concurrent_swaps = Some(3);
+
+        assert!(check_reloadable(&current, &new).is_ok());
+    }
+
+    #[test]
+    fn other_changes_are_rejected() {
+        let current = config();
+        let mut new = config();
+        new.maker.ask_spread = Decimal::from_f64(0.05).unwrap();
+        new.data.dir = PathBuf::from("/tmp/asb");
+        new.tor.socks5_port = 9150;
+
+        let error = check_reloadable(&current, &new).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Changes to [data], [tor] can only be applied by restarting the ASB, no changes were applied"
+        );
+    }
+
+    #[test]
+    fn price_feed_changes_are_rejected() {
+        let current = config();
+        let mut new = config();
+        new.maker.price_feed = Some(PriceFeed {
+            file: Some(PathBuf::from("/var/lib/asb/price.json")),
+            socket: None,
+            command: None,
+            refresh_interval_secs: 10,
+            max_age_secs: 120,
+        });
+
+        let error = check_reloadable(&current, &new).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Changes to price_feed of [maker] can only be applied by restarting the ASB, no changes were applied"
+        );
+    }
+
+    #[test]
+    fn inverted_timelock_ranges_are_rejected() {
+        let mut config = config();
+        config.maker.timelocks = Some(TimelockRanges {
+            min_cancel: CancelTimelock::new(72),
+            max_cancel: CancelTimelock::new(12),
+            min_punish: PunishTimelock::new(6),
+            max_punish: PunishTimelock::new(72),
+        });
+
+        assert!(check_maker(&config.maker).is_err());
+    }
+
+    #[test]
+    fn unusable_price_feed_intervals_are_rejected() {
+        let mut config = config();
+        let price_feed = PriceFeed {
+            file: Some(PathBuf::from("/var/lib/asb/price.json")),
+            socket: None,
+            command: None,
+            refresh_interval_secs: 10,
+            max_age_secs: 120,
+        };
+
+        config.maker.price_feed = Some(price_feed.clone());
+        assert!(check_maker(&config.maker).is_ok());
+
+        config.maker.price_feed = Some(PriceFeed {
+            refresh_interval_secs: 0,
+            ..price_feed.clone()
+        });
+        assert!(check_maker(&config.maker).is_err());
+
+        config.maker.price_feed = Some(PriceFeed {
+            max_age_secs: 5,
+            ..price_feed
+        });
+        assert!(check_maker(&config.maker).is_err());
+    }
+
+    #[test]
+    fn cli_resume_only_wins_over_config() {
+        let config = config();
+
+        assert!(maker_params(&config, true).resume_only);
+        assert!(!maker_params(&config, false).resume_only);
+    }
+}
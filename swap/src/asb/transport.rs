@@ -1,19 +1,37 @@
+use crate::network::tor_transport::TorListenTransport;
 use crate::network::transport::authenticate_and_multiplex;
 use anyhow::Result;
 use libp2p::core::muxing::StreamMuxerBox;
-use libp2p::core::transport::Boxed;
+use libp2p::core::transport::{Boxed, OptionalTransport};
 use libp2p::dns::TokioDnsConfig;
 use libp2p::tcp::TokioTcpConfig;
 use libp2p::websocket::WsConfig;
 use libp2p::{identity, PeerId, Transport};
 
 /// Creates the libp2p transport for the ASB.
-pub fn new(identity: &identity::Keypair) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
+///
+/// The ASB's transport needs the following capabilities:
+/// - Listen on and dial TCP and websocket addresses
+/// - Resolve DNS entries
+/// - Listen on onion-addresses by registering a hidden service with a running
+///   Tor daemon. If no Tor transport is given, onion-addresses are not
+///   supported.
+pub fn new(
+    identity: &identity::Keypair,
+    maybe_tor_transport: Option<TorListenTransport>,
+) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
     let tcp = TokioTcpConfig::new().nodelay(true);
     let tcp_with_dns = TokioDnsConfig::system(tcp)?;
     let websocket_with_dns = WsConfig::new(tcp_with_dns.clone());
+    let maybe_tor_transport = match maybe_tor_transport {
+        Some(tor_transport) => OptionalTransport::some(tor_transport),
+        None => OptionalTransport::none(),
+    };
 
-    let transport = tcp_with_dns.or_transport(websocket_with_dns).boxed();
+    let transport = maybe_tor_transport
+        .or_transport(tcp_with_dns)
+        .or_transport(websocket_with_dns)
+        .boxed();
 
     authenticate_and_multiplex(transport, identity)
 }
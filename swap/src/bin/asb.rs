@@ -13,34 +13,37 @@
 #![allow(non_snake_case)]
 
 use anyhow::{bail, Context, Result};
-use libp2p::core::multiaddr::Protocol;
-use libp2p::core::Multiaddr;
+use libp2p::swarm::AddressScore;
 use libp2p::Swarm;
-use prettytable::{row, Table};
 use std::env;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 use structopt::clap;
 use structopt::clap::ErrorKind;
+use swap::asb::cold_storage::{self, ColdStorage};
 use swap::asb::command::{parse_args, Arguments, Command};
 use swap::asb::config::{
     initial_setup, query_user_for_initial_config, read_config, Config, ConfigNotInitialized,
 };
-use swap::database::Database;
+use swap::asb::hooks::{Event, HookedStorage, Hooks};
+use swap::asb::limits::Trades;
+use swap::asb::onion::{self, OnionService};
+use swap::asb::price_feed;
+use swap::asb::refill::{Refill, Treasury};
+use swap::asb::reload::{self, Reload};
+use swap::database::{Database, SwapExport};
 use swap::monero::Amount;
 use swap::network::swarm;
+use swap::network::tor_transport::TorListenTransport;
 use swap::protocol::alice;
-use swap::protocol::alice::event_loop::KrakenRate;
+use swap::protocol::alice::event_loop::{KrakenRate, LocalRate, MakerRate};
 use swap::protocol::alice::{redeem, run, EventLoop};
 use swap::seed::Seed;
-use swap::tor::AuthenticatedClient;
-use swap::{asb, bitcoin, kraken, monero, tor};
+use swap::{asb, bitcoin, database, encryption, history, kraken, monero, seed, tor};
+use tokio::sync::mpsc;
 use tracing::{debug, info, warn};
 use tracing_subscriber::filter::LevelFilter;
 
-#[macro_use]
-extern crate prettytable;
-
 const DEFAULT_WALLET_NAME: &str = "asb-wallet";
 
 #[tokio::main]
@@ -75,7 +78,7 @@ async fn main() -> Result<()> {
         Ok(config) => config,
         Err(ConfigNotInitialized {}) => {
             initial_setup(config_path.clone(), query_user_for_initial_config(testnet)?)?;
-            read_config(config_path)?.expect("after initial setup config can be read")
+            read_config(config_path.clone())?.expect("after initial setup config can be read")
         }
     };
 
@@ -97,16 +100,46 @@ async fn main() -> Result<()> {
         "Database and Seed will be stored in",
     );
 
-    let db_path = config.data.dir.join("database");
-
-    let db = Database::open(config.data.dir.join(db_path).as_path())
+    let db = Database::open_in(&config.data.dir, config.data.database)
         .context("Could not open database")?;
 
-    let seed =
-        Seed::from_file_or_generate(&config.data.dir).expect("Could not retrieve/initialize seed");
+    match cmd {
+        Command::EncryptSeed => {
+            let passphrase = encryption::prompt_new_passphrase()?;
+            Seed::encrypt_file(&config.data.dir, &passphrase)
+                .context("Failed to encrypt seed file")?;
+
+            tracing::info!("Encrypted seed file, the passphrase is needed whenever the ASB starts");
+            return Ok(());
+        }
+        Command::ChangeSeedPassphrase => {
+            let current = config.data.seed_passphrase().read()?;
+            println!("New passphrase of the seed file:");
+            let passphrase = encryption::prompt_new_passphrase()?;
+            Seed::change_passphrase(&config.data.dir, &current, &passphrase)
+                .context("Failed to change passphrase of seed file")?;
+
+            tracing::info!("Changed passphrase of seed file");
+            return Ok(());
+        }
+        Command::RestoreSeed => {
+            let words = seed::prompt_mnemonic()?;
+            Seed::restore_from_mnemonic(&config.data.dir, &words, &config.data.seed_passphrase())
+                .context("Failed to restore seed")?;
+
+            tracing::info!("Restored seed file from mnemonic");
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let seed = Seed::from_file_or_generate(&config.data.dir, &config.data.seed_passphrase())
+        .context("Could not retrieve/initialize seed")?;
 
     match cmd {
         Command::Start { resume_only } => {
+            reload::check_maker(&config.maker).context("Invalid [maker] section in config")?;
+
             let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
 
             let monero_wallet = init_monero_wallet(&config, env_config).await?;
@@ -125,18 +158,43 @@ async fn main() -> Result<()> {
                 info!(%monero_balance, "Initialized Monero wallet");
             }
 
-            let kraken_price_updates = kraken::connect(config.maker.price_ticker_ws_url)?;
+            let maker_rate = match &config.maker.price_feed {
+                Some(price_feed) => {
+                    let refresh_interval = Duration::from_secs(price_feed.refresh_interval_secs);
+                    let max_age = Duration::from_secs(price_feed.max_age_secs);
+                    let source = price_feed::Source::from_config(price_feed.clone())?;
+                    tracing::info!(?source, "Reading the ask price from a local price feed");
+                    let price_updates = price_feed::connect(source, refresh_interval, max_age);
+
+                    MakerRate::Local(LocalRate::new(config.maker.ask_spread, price_updates))
+                }
+                None => {
+                    let price_updates = kraken::connect(config.maker.price_ticker_ws_url.clone())?;
+
+                    MakerRate::Kraken(KrakenRate::new(config.maker.ask_spread, price_updates))
+                }
+            };
 
             // setup Tor hidden services
             let tor_client =
                 tor::Client::new(config.tor.socks5_port).with_control_port(config.tor.control_port);
-            let _ac = match tor_client.assert_tor_running().await {
+            let onion_listen = onion::listen_addresses(&config.network.listen);
+            let onion_service = match tor_client.assert_tor_running().await {
+                // The transport registers the hidden service for onion listen
+                // addresses, the TCP listen addresses are not forwarded then.
+                Ok(_) if !onion_listen.is_empty() => {
+                    tracing::info!("Tor found. Listening on onion addresses");
+                    None
+                }
+                Err(error) if !onion_listen.is_empty() => {
+                    return Err(error.context("Tor is required to listen on onion addresses"));
+                }
                 Ok(_) => {
                     tracing::info!("Tor found. Setting up hidden service");
-                    let ac =
-                        register_tor_services(config.network.clone().listen, tor_client, &seed)
-                            .await?;
-                    Some(ac)
+                    let onion_service =
+                        OnionService::new(tor_client, &seed, &config.network.listen);
+                    let ac = onion_service.register().await?;
+                    Some((onion_service, ac))
                 }
                 Err(_) => {
                     tracing::warn!("Tor not found. Running on clear net");
@@ -144,42 +202,149 @@ async fn main() -> Result<()> {
                 }
             };
 
+            let tor_transport = if onion_listen.is_empty() {
+                None
+            } else {
+                Some(TorListenTransport::new(tor_client, seed.derive_torv3_key()))
+            };
+
             let current_balance = monero_wallet.get_balance().await?;
             let lock_fee = monero_wallet.static_tx_fee_estimate();
-            let kraken_rate = KrakenRate::new(config.maker.ask_spread, kraken_price_updates);
+            let maker_params = reload::maker_params(&config, resume_only);
             let mut swarm = swarm::asb(
                 &seed,
                 current_balance,
                 lock_fee,
-                config.maker.min_buy_btc,
-                config.maker.max_buy_btc,
-                kraken_rate.clone(),
-                resume_only,
+                maker_params.min_buy,
+                maker_params.max_buy,
+                maker_rate.clone(),
+                maker_params.resume_only,
                 env_config,
+                tor_transport.clone(),
             )?;
+            let spot_price = &mut swarm.behaviour_mut().spot_price;
+            spot_price.update_limits(maker_params.limits);
+            spot_price.update_timelocks(maker_params.timelocks);
 
-            for listen in config.network.listen {
+            for listen in config.network.listen.clone() {
                 Swarm::listen_on(&mut swarm, listen.clone())
                     .with_context(|| format!("Failed to listen on network interface {}", listen))?;
             }
 
             tracing::info!(peer_id = %swarm.local_peer_id(), "Network layer initialized");
 
-            let (event_loop, mut swap_receiver) = EventLoop::new(
+            for address in onion_listen {
+                swarm.add_external_address(address.clone(), AddressScore::Infinite);
+
+                let address = onion::with_peer_id(address, *swarm.local_peer_id());
+                tracing::info!(%address, "Onion service available at");
+            }
+
+            if let Some((onion_service, ac)) = onion_service {
+                let peer_id = *swarm.local_peer_id();
+
+                for address in onion_service.multiaddrs() {
+                    swarm.add_external_address(address.clone(), AddressScore::Infinite);
+
+                    let address = onion::with_peer_id(address, peer_id);
+                    tracing::info!(%address, "Onion service available at");
+                }
+
+                tokio::spawn(onion_service.supervise(ac));
+            }
+
+            if let Some(tor_transport) = tor_transport {
+                tokio::spawn(tor_transport.supervise());
+            }
+
+            let hooks = Arc::new(Hooks::new(config.hooks.clone())?);
+            let trades = Trades::load(&*db).context("Failed to load trades for limits")?;
+            let db = Arc::new(Database::new(HookedStorage::new(db, hooks.clone())));
+
+            let bitcoin_wallet = Arc::new(bitcoin_wallet);
+            let monero_wallet = Arc::new(monero_wallet);
+            let (mut event_loop, mut swap_receiver) = EventLoop::new(
                 swarm,
                 env_config,
-                Arc::new(bitcoin_wallet),
-                Arc::new(monero_wallet),
-                Arc::new(db),
-                kraken_rate.clone(),
-                config.maker.min_buy_btc,
-                config.maker.max_buy_btc,
+                bitcoin_wallet.clone(),
+                monero_wallet.clone(),
+                db.clone(),
+                maker_rate.clone(),
+                maker_params.min_buy,
+                maker_params.max_buy,
             )
             .unwrap();
 
+            let (maker_updates, maker_updates_receiver) = mpsc::channel(1);
+            event_loop = event_loop
+                .with_maker_updates(maker_updates_receiver)
+                .with_trades(trades)
+                .with_binding_quotes(seed.derive_libp2p_identity(), maker_params.quote_validity);
+            tokio::spawn(
+                Reload::new(
+                    config_path,
+                    config.clone(),
+                    resume_only,
+                    maker_rate.clone(),
+                    maker_updates,
+                )
+                .run(),
+            );
+
+            if let Some(cold_storage_config) = &config.bitcoin.cold_storage {
+                let cold_storage = ColdStorage::new(
+                    &cold_storage_config.descriptor,
+                    env_config.bitcoin_network,
+                    db.clone(),
+                )?;
+
+                match cold_storage_config.sweep_threshold_btc {
+                    Some(threshold) => {
+                        tracing::info!(%threshold, "Sweeping Bitcoin to cold storage above threshold");
+                        tokio::spawn(cold_storage::sweep_above(
+                            cold_storage,
+                            bitcoin_wallet,
+                            threshold,
+                        ));
+                    }
+                    None => {
+                        tracing::info!("Redeeming and punishing to cold storage");
+                        event_loop = event_loop.with_cold_storage(cold_storage);
+                    }
+                }
+            }
+
+            if let Some(refill) = &config.monero.refill {
+                let treasury = match &refill.treasury {
+                    Some(treasury) => Some(
+                        Treasury::open(
+                            treasury.wallet_rpc_url.clone(),
+                            treasury.wallet_name.clone(),
+                            treasury.refill_xmr,
+                            env_config,
+                        )
+                        .await?,
+                    ),
+                    None => None,
+                };
+
+                tokio::spawn(
+                    Refill::new(
+                        monero_wallet.clone(),
+                        refill.low_watermark_xmr,
+                        treasury,
+                        db.clone(),
+                        hooks.clone(),
+                    )
+                    .run(),
+                );
+            }
+
             tokio::spawn(async move {
                 while let Some(swap) = swap_receiver.recv().await {
-                    let rate = kraken_rate.clone();
+                    let rate = maker_rate.clone();
+                    let hooks = hooks.clone();
+                    let db = db.clone();
                     tokio::spawn(async move {
                         let swap_id = swap.swap_id;
                         match run(swap, rate).await {
@@ -187,7 +352,13 @@ async fn main() -> Result<()> {
                                 tracing::debug!(%swap_id, %state, "Swap finished with state")
                             }
                             Err(error) => {
-                                tracing::error!(%swap_id, "Swap failed. Error {:#}", error)
+                                tracing::error!(%swap_id, "Swap failed. Error {:#}", error);
+                                hooks.notify(
+                                    &**db,
+                                    swap_id,
+                                    Event::Error,
+                                    Some(format!("{:#}", error)),
+                                );
                             }
                         }
                     });
@@ -196,20 +367,33 @@ async fn main() -> Result<()> {
 
             event_loop.run().await;
         }
-        Command::History => {
-            let mut table = Table::new();
-
-            table.add_row(row!["SWAP ID", "STATE"]);
-
-            for (swap_id, state) in db.all_alice()? {
-                table.add_row(row![swap_id, state]);
+        Command::History {
+            swap_id,
+            states,
+            format,
+        } => match (swap_id, states) {
+            (Some(swap_id), true) => {
+                let states = history::states(&db, swap_id)?;
+                history::print_states(&states, format)?;
             }
-
-            // Print the table to stdout
-            table.printstd();
-        }
-        Command::WithdrawBtc { amount, address } => {
-            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            (Some(swap_id), false) => {
+                let state = db.get_state(swap_id)?.try_into_alice()?;
+                let entries = history::entries(&db, vec![(swap_id, state)])?;
+                history::print(&entries, format)?;
+            }
+            (None, _) => {
+                let entries = history::entries(&db, db.all_alice()?)?;
+                history::print(&entries, format)?;
+            }
+        },
+        Command::WithdrawBtc {
+            amount,
+            address,
+            coin_control,
+        } => {
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config)
+                .await?
+                .with_coin_control(coin_control);
 
             let amount = match amount {
                 Some(amount) => amount,
@@ -237,6 +421,70 @@ async fn main() -> Result<()> {
                 %monero_balance,
                 "Current balance");
         }
+        Command::ListUtxos => {
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+
+            let utxos =
+                bitcoin::utxo::with_labels(bitcoin_wallet.utxos().await?, db.utxo_labels()?);
+            for utxo in utxos {
+                tracing::info!(outpoint = %utxo.outpoint, amount = %utxo.amount, origin = %utxo.origin(), "UTXO");
+            }
+        }
+        Command::ShowMnemonic => {
+            println!("{}", seed.to_mnemonic());
+        }
+        Command::EncryptSeed | Command::ChangeSeedPassphrase | Command::RestoreSeed => {
+            bail!("The seed commands are handled before the seed is read")
+        }
+        Command::MigrateDatabase => {
+            if config.data.database != database::Backend::Sled {
+                bail!("The ASB is configured to use the SQLite database already")
+            }
+
+            let migrated = database::migrate_sled_to_sqlite(&*db, &config.data.dir)?;
+
+            tracing::info!(
+                %migrated,
+                "Copied swaps into SQLite database, set `database = \"sqlite\"` in the data section of the config to use it");
+        }
+        Command::Export {
+            swap_id,
+            output,
+            encrypt,
+        } => {
+            db.get_state(swap_id)?.try_into_alice()?;
+            let passphrase = if encrypt {
+                Some(encryption::prompt_new_passphrase()?)
+            } else {
+                None
+            };
+
+            let pem = SwapExport::new(&*db, swap_id, None)?.to_pem(passphrase.as_deref())?;
+            std::fs::write(&output, pem)
+                .with_context(|| format!("Failed to write {}", output.display()))?;
+
+            tracing::info!(%swap_id, file = %output.display(), "Exported swap");
+        }
+        Command::Import { file } => {
+            let pem = std::fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let export = SwapExport::from_pem(&pem, encryption::prompt_passphrase)?;
+            let state = export.state().try_into_alice()?;
+            if state.needs_bob() {
+                bail!(
+                    "Swap {} is in state {} and still needs messages from Bob, who only reaches the ASB it was exported from",
+                    export.swap_id,
+                    state
+                )
+            }
+
+            let swap_id = export.swap_id;
+            export.import_into(&*db).await?;
+
+            tracing::info!(
+                %swap_id,
+                "Imported swap, the Bitcoin is still sent to the wallet of the ASB it was exported from");
+        }
         Command::Cancel { swap_id, force } => {
             let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
 
@@ -310,6 +558,7 @@ async fn init_bitcoin_wallet(
         seed.derive_extended_private_key(env_config.bitcoin_network)?,
         env_config,
         config.bitcoin.target_block,
+        None,
     )
     .await
     .context("Failed to initialize Bitcoin wallet")?;
@@ -333,46 +582,3 @@ async fn init_monero_wallet(
 
     Ok(wallet)
 }
-
-/// Registers a hidden service for each network.
-/// Note: Once ac goes out of scope, the services will be de-registered.
-async fn register_tor_services(
-    networks: Vec<Multiaddr>,
-    tor_client: tor::Client,
-    seed: &Seed,
-) -> Result<AuthenticatedClient> {
-    let mut ac = tor_client.into_authenticated_client().await?;
-
-    let hidden_services_details = networks
-        .iter()
-        .flat_map(|network| {
-            network.iter().map(|protocol| match protocol {
-                Protocol::Tcp(port) => Some((
-                    port,
-                    SocketAddr::new(IpAddr::from(Ipv4Addr::new(127, 0, 0, 1)), port),
-                )),
-                _ => {
-                    // We only care for Tcp for now.
-                    None
-                }
-            })
-        })
-        .flatten()
-        .collect::<Vec<_>>();
-
-    let key = seed.derive_torv3_key();
-
-    ac.add_services(&hidden_services_details, &key).await?;
-
-    let onion_address = key
-        .public()
-        .get_onion_address()
-        .get_address_without_dot_onion();
-
-    hidden_services_details.iter().for_each(|(port, _)| {
-        let onion_address = format!("/onion3/{}:{}", onion_address, port);
-        tracing::info!(%onion_address);
-    });
-
-    Ok(ac)
-}
@@ -12,10 +12,9 @@
 #![forbid(unsafe_code)]
 #![allow(non_snake_case)]
 
+use ::bitcoin::hashes::{sha256, Hash};
 use anyhow::{bail, Context, Result};
-use prettytable::{row, Table};
-use qrcode::render::unicode;
-use qrcode::QrCode;
+use libp2p::identity;
 use std::cmp::min;
 use std::env;
 use std::future::Future;
@@ -23,27 +22,31 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 use swap::bitcoin::TxLock;
-use swap::cli::command::{parse_args_and_apply_defaults, Arguments, Command, ParseResult};
-use swap::database::Database;
+use swap::cli::command::{
+    parse_args_and_apply_defaults, Arguments, Command, ParseResult, SwapAmount,
+};
+use swap::cli::psbt::PromptSigner;
+use swap::cli::qr_code;
+use swap::database::{Database, Identity, SwapExport};
+use swap::encryption::Passphrase;
 use swap::env::Config;
 use swap::network::quote::BidQuote;
+use swap::network::spot_price::{TimelockRanges, Timelocks};
 use swap::network::swarm;
 use swap::protocol::bob;
-use swap::protocol::bob::{EventLoop, Swap};
+use swap::protocol::bob::{EventLoop, LockSigning, Swap};
 use swap::seed::Seed;
-use swap::{bitcoin, cli, monero};
+use swap::{bitcoin, cli, database, encryption, history, monero, seed, tor};
 use tracing::{debug, error, info, warn};
 use url::Url;
 use uuid::Uuid;
 
-#[macro_use]
-extern crate prettytable;
-
 #[tokio::main]
 async fn main() -> Result<()> {
     let Arguments {
         env_config,
         data_dir,
+        database,
         debug,
         json,
         cmd,
@@ -64,28 +67,44 @@ async fn main() -> Result<()> {
             monero_receive_address,
             monero_daemon_address,
             tor_socks5_port,
+            tor_only,
+            external_wallet_descriptor,
+            timelocks,
+            confirmation,
+            amount: swap_amount,
+            coin_control,
         } => {
             let swap_id = Uuid::new_v4();
 
-            cli::tracing::init(debug, json, data_dir.join("logs"), swap_id)?;
-            let db = Database::open(data_dir.join("database").as_path())
-                .context("Failed to open database")?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
+            let db = Database::open_in(&data_dir, database).context("Failed to open database")?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), &Passphrase::Prompt)
                 .context("Failed to read in seed file")?;
 
-            let bitcoin_wallet = init_bitcoin_wallet(
+            let tor_proxy = check_tor(tor_socks5_port, tor_only).await?;
+
+            let (bitcoin_wallet, lock_signing) = init_bitcoin_wallet_for_swap(
                 bitcoin_electrum_rpc_url,
                 &seed,
                 data_dir.clone(),
                 env_config,
                 bitcoin_target_block,
+                external_wallet_descriptor,
+                json,
+                tor_proxy,
             )
             .await?;
             let (monero_wallet, _process) =
-                init_monero_wallet(data_dir, monero_daemon_address, env_config).await?;
-            let bitcoin_wallet = Arc::new(bitcoin_wallet);
+                init_monero_wallet(data_dir, monero_daemon_address, env_config, tor_proxy).await?;
+            let bitcoin_wallet = Arc::new(bitcoin_wallet.with_coin_control(coin_control));
 
-            let mut swarm = swarm::cli(&seed, seller_peer_id, tor_socks5_port).await?;
+            let mut swarm = swarm::cli(
+                seed.derive_libp2p_identity_for_swap(swap_id),
+                seller_peer_id,
+                tor_socks5_port,
+                tor_only,
+            )
+            .await?;
             swarm
                 .behaviour_mut()
                 .add_address(seller_peer_id, seller_addr);
@@ -102,10 +121,24 @@ async fn main() -> Result<()> {
             let event_loop = tokio::spawn(event_loop.run());
 
             let max_givable = || bitcoin_wallet.max_giveable(TxLock::script_size());
+            let new_deposit_address = async {
+                let address = bitcoin_wallet.new_address().await?;
+                db.insert_utxo_label(address.script_pubkey(), bitcoin::UtxoLabel::Deposit)
+                    .await?;
+
+                Ok::<_, anyhow::Error>(address)
+            };
+            let bid_quote = async {
+                let bid_quote = event_loop_handle.request_quote().await?;
+                check_timelocks(timelocks, &bid_quote, env_config)?;
+
+                Ok(bid_quote)
+            };
             let (amount, fees) = determine_btc_to_swap(
                 json,
-                event_loop_handle.request_quote(),
-                bitcoin_wallet.new_address(),
+                bid_quote,
+                swap_amount,
+                new_deposit_address,
                 || bitcoin_wallet.balance(),
                 max_givable,
                 || bitcoin_wallet.sync(),
@@ -115,6 +148,8 @@ async fn main() -> Result<()> {
             info!(%amount, %fees, %swap_id,  "Swapping");
 
             db.insert_peer_id(swap_id, seller_peer_id).await?;
+            db.insert_identity(swap_id, Identity::DerivedForSwap)
+                .await?;
 
             let swap = Swap::new(
                 db,
@@ -125,7 +160,10 @@ async fn main() -> Result<()> {
                 event_loop_handle,
                 monero_receive_address,
                 amount,
-            );
+                lock_signing,
+            )
+            .with_timelocks(timelocks)
+            .with_confirmation(Arc::new(confirmation));
 
             tokio::select! {
                 result = event_loop => {
@@ -137,20 +175,28 @@ async fn main() -> Result<()> {
                 }
             }
         }
-        Command::History => {
-            let db = Database::open(data_dir.join("database").as_path())
-                .context("Failed to open database")?;
-
-            let mut table = Table::new();
-
-            table.add_row(row!["SWAP ID", "STATE"]);
+        Command::History {
+            swap_id,
+            states,
+            format,
+        } => {
+            let db = Database::open_in(&data_dir, database).context("Failed to open database")?;
 
-            for (swap_id, state) in db.all_bob()? {
-                table.add_row(row![swap_id, state]);
+            match (swap_id, states) {
+                (Some(swap_id), true) => {
+                    let states = history::states(&db, swap_id)?;
+                    history::print_states(&states, format)?;
+                }
+                (Some(swap_id), false) => {
+                    let state = db.get_state(swap_id)?.try_into_bob()?;
+                    let entries = history::entries(&db, vec![(swap_id, state)])?;
+                    history::print(&entries, format)?;
+                }
+                (None, _) => {
+                    let entries = history::entries(&db, db.all_bob()?)?;
+                    history::print(&entries, format)?;
+                }
             }
-
-            // Print the table to stdout
-            table.printstd();
         }
         Command::Resume {
             swap_id,
@@ -160,32 +206,40 @@ async fn main() -> Result<()> {
             monero_receive_address,
             monero_daemon_address,
             tor_socks5_port,
+            tor_only,
+            external_wallet_descriptor,
+            coin_control,
         } => {
-            cli::tracing::init(debug, json, data_dir.join("logs"), swap_id)?;
-            let db = Database::open(data_dir.join("database").as_path())
-                .context("Failed to open database")?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
+            let db = Database::open_in(&data_dir, database).context("Failed to open database")?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), &Passphrase::Prompt)
                 .context("Failed to read in seed file")?;
 
             if monero_receive_address.network != env_config.monero_network {
                 bail!("The given monero address is on network {:?}, expected address of network {:?}.", monero_receive_address.network, env_config.monero_network)
             }
 
-            let bitcoin_wallet = init_bitcoin_wallet(
+            let tor_proxy = check_tor(tor_socks5_port, tor_only).await?;
+
+            let (bitcoin_wallet, lock_signing) = init_bitcoin_wallet_for_swap(
                 bitcoin_electrum_rpc_url,
                 &seed,
                 data_dir.clone(),
                 env_config,
                 bitcoin_target_block,
+                external_wallet_descriptor,
+                json,
+                tor_proxy,
             )
             .await?;
             let (monero_wallet, _process) =
-                init_monero_wallet(data_dir, monero_daemon_address, env_config).await?;
-            let bitcoin_wallet = Arc::new(bitcoin_wallet);
+                init_monero_wallet(data_dir, monero_daemon_address, env_config, tor_proxy).await?;
+            let bitcoin_wallet = Arc::new(bitcoin_wallet.with_coin_control(coin_control));
 
             let seller_peer_id = db.get_peer_id(swap_id)?;
+            let identity = identity::Keypair::Ed25519(swap_identity(&db, &seed, swap_id)?);
 
-            let mut swarm = swarm::cli(&seed, seller_peer_id, tor_socks5_port).await?;
+            let mut swarm = swarm::cli(identity, seller_peer_id, tor_socks5_port, tor_only).await?;
             let our_peer_id = swarm.local_peer_id();
             tracing::debug!(peer_id = %our_peer_id, "Initializing network module");
             swarm
@@ -209,6 +263,7 @@ async fn main() -> Result<()> {
                 env_config,
                 event_loop_handle,
                 monero_receive_address,
+                lock_signing,
             )?;
 
             tokio::select! {
@@ -225,19 +280,23 @@ async fn main() -> Result<()> {
             force,
             bitcoin_electrum_rpc_url,
             bitcoin_target_block,
+            tor_socks5_port,
+            tor_only,
         } => {
-            cli::tracing::init(debug, json, data_dir.join("logs"), swap_id)?;
-            let db = Database::open(data_dir.join("database").as_path())
-                .context("Failed to open database")?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
+            let db = Database::open_in(&data_dir, database).context("Failed to open database")?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), &Passphrase::Prompt)
                 .context("Failed to read in seed file")?;
 
+            let tor_proxy = check_tor(tor_socks5_port, tor_only).await?;
+
             let bitcoin_wallet = init_bitcoin_wallet(
                 bitcoin_electrum_rpc_url,
                 &seed,
                 data_dir,
                 env_config,
                 bitcoin_target_block,
+                tor_proxy,
             )
             .await?;
 
@@ -257,34 +316,237 @@ async fn main() -> Result<()> {
             force,
             bitcoin_electrum_rpc_url,
             bitcoin_target_block,
+            tor_socks5_port,
+            tor_only,
         } => {
-            cli::tracing::init(debug, json, data_dir.join("logs"), swap_id)?;
-            let db = Database::open(data_dir.join("database").as_path())
-                .context("Failed to open database")?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
+            let db = Database::open_in(&data_dir, database).context("Failed to open database")?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), &Passphrase::Prompt)
                 .context("Failed to read in seed file")?;
 
+            let tor_proxy = check_tor(tor_socks5_port, tor_only).await?;
+
             let bitcoin_wallet = init_bitcoin_wallet(
                 bitcoin_electrum_rpc_url,
                 &seed,
                 data_dir,
                 env_config,
                 bitcoin_target_block,
+                tor_proxy,
             )
             .await?;
 
             bob::refund(swap_id, Arc::new(bitcoin_wallet), db, force).await??;
         }
+        Command::Balance {
+            bitcoin_electrum_rpc_url,
+            bitcoin_target_block,
+            tor_socks5_port,
+            tor_only,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), &Passphrase::Prompt)
+                .context("Failed to read in seed file")?;
+
+            let tor_proxy = check_tor(tor_socks5_port, tor_only).await?;
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_url,
+                &seed,
+                data_dir,
+                env_config,
+                bitcoin_target_block,
+                tor_proxy,
+            )
+            .await?;
+
+            let bitcoin_balance = bitcoin_wallet.balance().await?;
+            info!(%bitcoin_balance, "Current balance");
+        }
+        Command::ListUtxos {
+            bitcoin_electrum_rpc_url,
+            bitcoin_target_block,
+            tor_socks5_port,
+            tor_only,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            let db = Database::open_in(&data_dir, database).context("Failed to open database")?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), &Passphrase::Prompt)
+                .context("Failed to read in seed file")?;
+
+            let tor_proxy = check_tor(tor_socks5_port, tor_only).await?;
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_url,
+                &seed,
+                data_dir,
+                env_config,
+                bitcoin_target_block,
+                tor_proxy,
+            )
+            .await?;
+
+            let utxos =
+                bitcoin::utxo::with_labels(bitcoin_wallet.utxos().await?, db.utxo_labels()?);
+            for utxo in utxos {
+                info!(outpoint = %utxo.outpoint, amount = %utxo.amount, origin = %utxo.origin(), "UTXO");
+            }
+        }
+        Command::WithdrawBtc {
+            amount,
+            address,
+            bitcoin_electrum_rpc_url,
+            bitcoin_target_block,
+            tor_socks5_port,
+            tor_only,
+            coin_control,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), &Passphrase::Prompt)
+                .context("Failed to read in seed file")?;
+
+            let tor_proxy = check_tor(tor_socks5_port, tor_only).await?;
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_url,
+                &seed,
+                data_dir,
+                env_config,
+                bitcoin_target_block,
+                tor_proxy,
+            )
+            .await?
+            .with_coin_control(coin_control);
+
+            let amount = match amount {
+                Some(amount) => amount,
+                None => {
+                    bitcoin_wallet
+                        .max_giveable(address.script_pubkey().len())
+                        .await?
+                }
+            };
+
+            let psbt = bitcoin_wallet.send_to_address(address, amount).await?;
+            let signed_tx = bitcoin_wallet.sign_and_finalize(psbt).await?;
+
+            bitcoin_wallet.broadcast(signed_tx, "withdraw").await?;
+        }
+        Command::MigrateDatabase => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            if database != database::Backend::Sled {
+                bail!("Only the sled database can be migrated to SQLite")
+            }
+
+            let db = Database::open_in(&data_dir, database).context("Failed to open database")?;
+            let migrated = database::migrate_sled_to_sqlite(&*db, &data_dir)?;
+
+            info!(
+                %migrated,
+                "Copied swaps into SQLite database, pass `--database sqlite` to use it"
+            );
+        }
+        Command::Export {
+            swap_id,
+            output,
+            encrypt,
+        } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            let db = Database::open_in(&data_dir, database).context("Failed to open database")?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), &Passphrase::Prompt)
+                .context("Failed to read in seed file")?;
+
+            db.get_state(swap_id)?.try_into_bob()?;
+            let identity = swap_identity(&db, &seed, swap_id)?;
+            let passphrase = if encrypt {
+                Some(encryption::prompt_new_passphrase()?)
+            } else {
+                None
+            };
+
+            let pem =
+                SwapExport::new(&*db, swap_id, Some(identity))?.to_pem(passphrase.as_deref())?;
+            std::fs::write(&output, pem)
+                .with_context(|| format!("Failed to write {}", output.display()))?;
+
+            info!(%swap_id, file = %output.display(), "Exported swap");
+        }
+        Command::Import { file } => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            let db = Database::open_in(&data_dir, database).context("Failed to open database")?;
+
+            let pem = std::fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let export = SwapExport::from_pem(&pem, encryption::prompt_passphrase)?;
+            export.state().try_into_bob()?;
+
+            let swap_id = export.swap_id;
+            export.import_into(&*db).await?;
+
+            info!(
+                %swap_id,
+                "Imported swap, the Bitcoin is still sent to the wallet of the machine it was exported from"
+            );
+        }
+        Command::EncryptSeed => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+
+            let passphrase = encryption::prompt_new_passphrase()?;
+            Seed::encrypt_file(&data_dir, &passphrase).context("Failed to encrypt seed file")?;
+
+            info!("Encrypted seed file, the passphrase is needed whenever the seed is used");
+        }
+        Command::ChangeSeedPassphrase => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+
+            println!("Current passphrase of the seed file:");
+            let current = encryption::prompt_passphrase()?;
+            println!("New passphrase of the seed file:");
+            let passphrase = encryption::prompt_new_passphrase()?;
+            Seed::change_passphrase(&data_dir, &current, &passphrase)
+                .context("Failed to change passphrase of seed file")?;
+
+            info!("Changed passphrase of seed file");
+        }
+        Command::ShowMnemonic => {
+            let seed = Seed::from_file_or_generate(data_dir.as_path(), &Passphrase::Prompt)
+                .context("Failed to read in seed file")?;
+
+            println!("{}", seed.to_mnemonic());
+        }
+        Command::RestoreSeed => {
+            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+
+            let words = seed::prompt_mnemonic()?;
+            Seed::restore_from_mnemonic(&data_dir, &words, &Passphrase::Prompt)
+                .context("Failed to restore seed")?;
+
+            info!("Restored seed file from mnemonic");
+        }
     };
     Ok(())
 }
 
+/// The libp2p keypair a swap of the CLI is executed with.
+fn swap_identity(db: &Database, seed: &Seed, swap_id: Uuid) -> Result<identity::ed25519::Keypair> {
+    let keypair = match db.get_identity(swap_id)? {
+        Some(Identity::DerivedForSwap) => seed.derive_libp2p_keypair_for_swap(swap_id),
+        Some(Identity::Keypair(keypair)) => keypair,
+        // Swaps started before every swap got its own identity were made with
+        // the identity of the seed.
+        None => seed.derive_libp2p_keypair(),
+    };
+
+    Ok(keypair)
+}
+
 async fn init_bitcoin_wallet(
     electrum_rpc_url: Url,
     seed: &Seed,
     data_dir: PathBuf,
     env_config: Config,
     bitcoin_target_block: usize,
+    tor_socks5_port: Option<u16>,
 ) -> Result<bitcoin::Wallet> {
     let wallet_dir = data_dir.join("wallet");
 
@@ -294,6 +556,7 @@ async fn init_bitcoin_wallet(
         seed.derive_extended_private_key(env_config.bitcoin_network)?,
         env_config,
         bitcoin_target_block,
+        tor_socks5_port,
     )
     .await
     .context("Failed to initialize Bitcoin wallet")?;
@@ -303,10 +566,110 @@ async fn init_bitcoin_wallet(
     Ok(wallet)
 }
 
+/// Checks whether Tor is running and reports which connections go through it.
+///
+/// With `--tor-only` the connections to Electrum and the Monero daemon go
+/// through Tor as well and the command fails without it. Returns the socks5
+/// port these connections use in that case.
+async fn check_tor(tor_socks5_port: u16, tor_only: bool) -> Result<Option<u16>> {
+    let tor_running = tor::Client::new(tor_socks5_port).assert_tor_running().await;
+
+    if tor_only {
+        tor_running.with_context(|| {
+            format!(
+                "Tor is required by --tor-only, but its socks5 proxy is not reachable on port {}",
+                tor_socks5_port
+            )
+        })?;
+
+        info!(
+            libp2p = "Tor",
+            electrum = "Tor",
+            monero_daemon = "Tor",
+            "All connections are routed through Tor"
+        );
+
+        return Ok(Some(tor_socks5_port));
+    }
+
+    let libp2p_route = if tor_running.is_ok() {
+        "Tor"
+    } else {
+        "clearnet"
+    };
+    info!(
+        libp2p = libp2p_route,
+        electrum = "clearnet",
+        monero_daemon = "clearnet",
+        "Connections to Electrum and the Monero daemon are not anonymised, use --tor-only to route them through Tor"
+    );
+
+    Ok(None)
+}
+
+/// Initializes the Bitcoin wallet that funds the swap.
+///
+/// If an external wallet descriptor is given, a watch-only wallet is used to
+/// build the lock transaction and the signing is left to the external wallet.
+async fn init_bitcoin_wallet_for_swap(
+    electrum_rpc_url: Url,
+    seed: &Seed,
+    data_dir: PathBuf,
+    env_config: Config,
+    bitcoin_target_block: usize,
+    external_wallet_descriptor: Option<String>,
+    json: bool,
+    tor_socks5_port: Option<u16>,
+) -> Result<(bitcoin::Wallet, LockSigning)> {
+    let descriptor = match external_wallet_descriptor {
+        Some(descriptor) => descriptor,
+        None => {
+            let wallet = init_bitcoin_wallet(
+                electrum_rpc_url,
+                seed,
+                data_dir,
+                env_config,
+                bitcoin_target_block,
+                tor_socks5_port,
+            )
+            .await?;
+
+            return Ok((wallet, LockSigning::InternalWallet));
+        }
+    };
+
+    // Each descriptor gets its own wallet database, bdk refuses to open a
+    // database that was created for a different descriptor.
+    let wallet_dir = data_dir
+        .join("external-wallets")
+        .join(sha256::Hash::hash(descriptor.as_bytes()).to_string());
+
+    let wallet = bitcoin::Wallet::new_watch_only(
+        electrum_rpc_url,
+        &wallet_dir,
+        &descriptor,
+        env_config,
+        bitcoin_target_block,
+        tor_socks5_port,
+    )
+    .await
+    .context("Failed to initialize watch-only Bitcoin wallet for external wallet")?;
+
+    wallet.sync().await?;
+
+    Ok((
+        wallet,
+        LockSigning::ExternalWallet(Arc::new(PromptSigner {
+            render_qr_code: !json,
+        })),
+    ))
+}
+
 async fn init_monero_wallet(
     data_dir: PathBuf,
     monero_daemon_address: String,
     env_config: Config,
+    tor_socks5_port: Option<u16>,
 ) -> Result<(monero::Wallet, monero::WalletRpcProcess)> {
     let network = env_config.monero_network;
 
@@ -315,7 +678,7 @@ async fn init_monero_wallet(
     let monero_wallet_rpc = monero::WalletRpc::new(data_dir.join("monero")).await?;
 
     let monero_wallet_rpc_process = monero_wallet_rpc
-        .run(network, monero_daemon_address.as_str())
+        .run(network, monero_daemon_address.as_str(), tor_socks5_port)
         .await?;
 
     let monero_wallet = monero::Wallet::open_or_create(
@@ -328,19 +691,36 @@ async fn init_monero_wallet(
     Ok((monero_wallet, monero_wallet_rpc_process))
 }
 
-fn qr_code(value: &impl ToString) -> Result<String> {
-    let code = QrCode::new(value.to_string())?;
-    let qr_code = code
-        .render::<unicode::Dense1x2>()
-        .dark_color(unicode::Dense1x2::Light)
-        .light_color(unicode::Dense1x2::Dark)
-        .build();
-    Ok(qr_code)
+/// Fails if the seller does not accept the timelocks that are proposed, or
+/// the defaults of the network if none are.
+///
+/// Sellers that do not advertise timelocks only accept the defaults of the
+/// network.
+fn check_timelocks(
+    proposed: Option<Timelocks>,
+    bid_quote: &BidQuote,
+    env_config: Config,
+) -> Result<()> {
+    let timelocks = proposed.unwrap_or_else(|| Timelocks::from(env_config));
+    let accepted = bid_quote
+        .timelocks
+        .unwrap_or_else(|| TimelockRanges::exactly(Timelocks::from(env_config)));
+
+    if !accepted.contains(timelocks) {
+        bail!(
+            "The seller does not accept the timelocks {}, it accepts {}",
+            timelocks,
+            accepted
+        )
+    }
+
+    Ok(())
 }
 
 async fn determine_btc_to_swap<FB, TB, FMG, TMG, FS, TS>(
     json: bool,
     bid_quote: impl Future<Output = Result<BidQuote>>,
+    swap_amount: Option<SwapAmount>,
     get_new_address: impl Future<Output = Result<bitcoin::Address>>,
     balance: FB,
     max_giveable_fn: FMG,
@@ -362,25 +742,75 @@ where
         maximum_amount = %bid_quote.max_quantity,
         "Received quote: 1 XMR ~ ",
     );
+    if !bid_quote.addresses.is_empty() {
+        debug!(addresses = ?bid_quote.addresses, "Seller can also be reached at");
+    }
+
+    if let Some(timelocks) = bid_quote.timelocks {
+        info!(%timelocks, "Seller accepts timelocks");
+    }
+
+    if let Some(binding) = &bid_quote.binding {
+        info!(
+            quote_id = %binding.id,
+            valid_until = binding.valid_until,
+            "Seller honours the price of the quote until it expires"
+        );
+    }
+
+    // Validated before any deposit so the seller is only contacted again for
+    // an amount it accepts.
+    let exact_amount = match swap_amount {
+        Some(swap_amount) => {
+            let amount = swap_amount.to_btc(bid_quote.price)?;
+
+            if amount < bid_quote.min_quantity {
+                bail!(
+                    "Swapping {} is below the minimum of {} the seller buys",
+                    amount,
+                    bid_quote.min_quantity
+                );
+            }
+            if amount > bid_quote.max_quantity {
+                bail!(
+                    "Swapping {} is above the maximum of {} the seller buys",
+                    amount,
+                    bid_quote.max_quantity
+                );
+            }
+
+            info!(%swap_amount, %amount, "Swapping exact amount");
+            Some(amount)
+        }
+        None => None,
+    };
 
     let mut max_giveable = max_giveable_fn().await?;
+    let minimum_amount = exact_amount.unwrap_or(bid_quote.min_quantity);
 
-    if max_giveable == bitcoin::Amount::ZERO || max_giveable < bid_quote.min_quantity {
+    if max_giveable == bitcoin::Amount::ZERO || max_giveable < minimum_amount {
         let deposit_address = get_new_address.await?;
-        let minimum_amount = bid_quote.min_quantity;
         let maximum_amount = bid_quote.max_quantity;
 
         if !json {
             eprintln!("{}", qr_code(&deposit_address)?);
         }
 
-        info!(
-            %deposit_address,
-            %max_giveable,
-            %minimum_amount,
-            %maximum_amount,
-            "Please deposit BTC you want to swap to",
-        );
+        match exact_amount {
+            Some(amount) => info!(
+                %deposit_address,
+                %max_giveable,
+                %amount,
+                "Please deposit enough BTC to swap the amount and pay the fees to",
+            ),
+            None => info!(
+                %deposit_address,
+                %max_giveable,
+                %minimum_amount,
+                %maximum_amount,
+                "Please deposit BTC you want to swap to",
+            ),
+        }
 
         loop {
             sync().await?;
@@ -397,7 +827,7 @@ where
                     "Received BTC",
                 );
 
-                if max_giveable >= bid_quote.min_quantity {
+                if max_giveable >= minimum_amount {
                     break;
                 } else {
                     tracing::info!(
@@ -417,7 +847,7 @@ where
 
     let max_accepted = bid_quote.max_quantity;
 
-    let btc_swap_amount = min(max_giveable, max_accepted);
+    let btc_swap_amount = exact_amount.unwrap_or_else(|| min(max_giveable, max_accepted));
 
     Ok((btc_swap_amount, fees))
 }
@@ -428,6 +858,7 @@ mod tests {
     use crate::determine_btc_to_swap;
     use ::bitcoin::Amount;
     use std::sync::Mutex;
+    use swap::env::GetConfig;
     use tracing::subscriber;
 
     struct MaxGiveable {
@@ -463,6 +894,7 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_max(0.01)) },
+            None,
             get_dummy_address(),
             || async { Ok(Amount::from_btc(0.001)?) },
             || async {
@@ -490,6 +922,7 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_max(0.01)) },
+            None,
             get_dummy_address(),
             || async { Ok(Amount::from_btc(0.1001)?) },
             || async {
@@ -518,6 +951,7 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_max(0.01)) },
+            None,
             async { panic!("should not request new address when initial balance  is > 0") },
             || async { Ok(Amount::from_btc(0.005)?) },
             || async {
@@ -546,6 +980,7 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_max(0.01)) },
+            None,
             async { panic!("should not request new address when initial balance is > 0") },
             || async { Ok(Amount::from_btc(0.1001)?) },
             || async {
@@ -574,6 +1009,7 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_min(0.01)) },
+            None,
             get_dummy_address(),
             || async { Ok(Amount::from_btc(0.0101)?) },
             || async {
@@ -602,6 +1038,7 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_min(0.01)) },
+            None,
             get_dummy_address(),
             || async { Ok(Amount::from_btc(0.0101)?) },
             || async {
@@ -635,6 +1072,7 @@ mod tests {
             determine_btc_to_swap(
                 true,
                 async { Ok(quote_with_min(0.1)) },
+                None,
                 get_dummy_address(),
                 || async { Ok(Amount::from_btc(0.0101)?) },
                 || async {
@@ -650,11 +1088,121 @@ mod tests {
         assert!(matches!(error, tokio::time::error::Elapsed { .. }))
     }
 
+    #[tokio::test]
+    async fn given_btc_amount_then_waits_for_amount_plus_fees() {
+        let _guard = subscriber::set_default(tracing_subscriber::fmt().with_test_writer().finish());
+        let givable = Arc::new(Mutex::new(MaxGiveable::new(vec![
+            Amount::ZERO,
+            Amount::from_btc(0.005).unwrap(),
+            Amount::from_btc(0.0101).unwrap(),
+        ])));
+
+        let (amount, fees) = determine_btc_to_swap(
+            true,
+            async { Ok(quote_with_max(0.1)) },
+            Some(SwapAmount::Btc(Amount::from_btc(0.01).unwrap())),
+            get_dummy_address(),
+            || async { Ok(Amount::from_btc(0.0102)?) },
+            || async {
+                let mut result = givable.lock().unwrap();
+                result.give()
+            },
+            || async { Ok(()) },
+        )
+        .await
+        .unwrap();
+
+        let expected_amount = Amount::from_btc(0.01).unwrap();
+        let expected_fees = Amount::from_btc(0.0001).unwrap();
+
+        assert_eq!((amount, fees), (expected_amount, expected_fees))
+    }
+
+    #[tokio::test]
+    async fn given_xmr_amount_then_swaps_btc_at_quoted_price() {
+        let _guard = subscriber::set_default(tracing_subscriber::fmt().with_test_writer().finish());
+        let givable = Arc::new(Mutex::new(MaxGiveable::new(vec![
+            Amount::from_btc(0.1).unwrap()
+        ])));
+
+        let (amount, _) = determine_btc_to_swap(
+            true,
+            async { Ok(quote_with_max(0.1)) },
+            Some(SwapAmount::Xmr(monero::Amount::from_monero(5.0).unwrap())),
+            async { panic!("should not request new address when initial balance is > 0") },
+            || async { Ok(Amount::from_btc(0.1001)?) },
+            || async {
+                let mut result = givable.lock().unwrap();
+                result.give()
+            },
+            || async { Ok(()) },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(amount, Amount::from_btc(0.005).unwrap())
+    }
+
+    #[tokio::test]
+    async fn given_btc_amount_above_max_quantity_then_fails_before_deposit() {
+        let _guard = subscriber::set_default(tracing_subscriber::fmt().with_test_writer().finish());
+
+        let result = determine_btc_to_swap(
+            true,
+            async { Ok(quote_with_max(0.01)) },
+            Some(SwapAmount::Btc(Amount::from_btc(0.02).unwrap())),
+            async { panic!("should not request new address for an invalid amount") },
+            || async { Ok(Amount::ZERO) },
+            || async { Ok(Amount::ZERO) },
+            || async { Ok(()) },
+        )
+        .await;
+
+        assert!(result.is_err())
+    }
+
+    #[test]
+    fn given_seller_without_timelocks_then_only_defaults_are_accepted() {
+        let env_config = swap::env::Testnet::get_config();
+        let quote = quote_with_max(0.01);
+        let proposed = Timelocks {
+            cancel: swap::bitcoin::CancelTimelock::new(24),
+            punish: env_config.bitcoin_punish_timelock,
+        };
+
+        assert!(check_timelocks(None, &quote, env_config).is_ok());
+        assert!(check_timelocks(Some(proposed), &quote, env_config).is_err());
+    }
+
+    #[test]
+    fn given_proposed_timelocks_in_advertised_ranges_then_accepted() {
+        let env_config = swap::env::Testnet::get_config();
+        let quote = BidQuote {
+            timelocks: Some(TimelockRanges {
+                min_cancel: swap::bitcoin::CancelTimelock::new(12),
+                max_cancel: swap::bitcoin::CancelTimelock::new(72),
+                min_punish: swap::bitcoin::PunishTimelock::new(6),
+                max_punish: swap::bitcoin::PunishTimelock::new(72),
+            }),
+            ..quote_with_max(0.01)
+        };
+        let proposed = |cancel| Timelocks {
+            cancel: swap::bitcoin::CancelTimelock::new(cancel),
+            punish: env_config.bitcoin_punish_timelock,
+        };
+
+        assert!(check_timelocks(Some(proposed(24)), &quote, env_config).is_ok());
+        assert!(check_timelocks(Some(proposed(144)), &quote, env_config).is_err());
+    }
+
     fn quote_with_max(btc: f64) -> BidQuote {
         BidQuote {
             price: Amount::from_btc(0.001).unwrap(),
             max_quantity: Amount::from_btc(btc).unwrap(),
             min_quantity: Amount::ZERO,
+            addresses: vec![],
+            timelocks: None,
+            binding: None,
         }
     }
 
@@ -663,6 +1211,9 @@ mod tests {
             price: Amount::from_btc(0.001).unwrap(),
             max_quantity: Amount::max_value(),
             min_quantity: Amount::from_btc(btc).unwrap(),
+            addresses: vec![],
+            timelocks: None,
+            binding: None,
         }
     }
 
@@ -1,3 +1,4 @@
+pub mod utxo;
 pub mod wallet;
 
 mod cancel;
@@ -13,6 +14,7 @@ pub use crate::bitcoin::punish::TxPunish;
 pub use crate::bitcoin::redeem::TxRedeem;
 pub use crate::bitcoin::refund::TxRefund;
 pub use crate::bitcoin::timelocks::{BlockHeight, ExpiredTimelocks};
+pub use crate::bitcoin::utxo::{CoinControl, Utxo, UtxoLabel};
 pub use ::bitcoin::util::amount::Amount;
 pub use ::bitcoin::util::psbt::PartiallySignedTransaction;
 pub use ::bitcoin::{Address, Network, Transaction, Txid};
@@ -246,6 +248,34 @@ pub fn current_epoch(
     ExpiredTimelocks::None
 }
 
+/// Ensures that the given address is on the Bitcoin network we are swapping
+/// on.
+pub fn validate_address(address: Address, is_testnet: bool) -> Result<Address> {
+    let network = if is_testnet {
+        Network::Testnet
+    } else {
+        Network::Bitcoin
+    };
+
+    if address.network != network {
+        bail!(BitcoinAddressNetworkMismatch {
+            expected: network,
+            actual: address.network
+        });
+    }
+
+    Ok(address)
+}
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Serialize)]
+#[error("Invalid Bitcoin address provided, expected address on network {expected:?}  but address provided is on {actual:?}")]
+pub struct BitcoinAddressNetworkMismatch {
+    #[serde(with = "crate::bitcoin::network")]
+    pub expected: Network,
+    #[serde(with = "crate::bitcoin::network")]
+    pub actual: Network,
+}
+
 #[derive(Clone, Copy, thiserror::Error, Debug)]
 #[error("transaction does not spend anything")]
 pub struct NoInputs;
@@ -265,10 +295,7 @@ pub struct NotThreeWitnesses(usize);
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::env::{GetConfig, Regtest};
-    use crate::protocol::{alice, bob};
-    use rand::rngs::OsRng;
-    use uuid::Uuid;
+    use crate::protocol::execution_setup;
 
     #[test]
     fn lock_confirmations_le_to_cancel_timelock_no_timelock_expired() {
@@ -317,67 +344,7 @@ mod tests {
 
     #[tokio::test]
     async fn calculate_transaction_weights() {
-        let alice_wallet = Wallet::new_funded_default_fees(Amount::ONE_BTC.as_sat());
-        let bob_wallet = Wallet::new_funded_default_fees(Amount::ONE_BTC.as_sat());
-        let spending_fee = Amount::from_sat(1_000);
-        let btc_amount = Amount::from_sat(500_000);
-        let xmr_amount = crate::monero::Amount::from_piconero(10000);
-
-        let tx_redeem_fee = alice_wallet
-            .estimate_fee(TxRedeem::weight(), btc_amount)
-            .await
-            .unwrap();
-        let tx_punish_fee = alice_wallet
-            .estimate_fee(TxPunish::weight(), btc_amount)
-            .await
-            .unwrap();
-        let redeem_address = alice_wallet.new_address().await.unwrap();
-        let punish_address = alice_wallet.new_address().await.unwrap();
-
-        let config = Regtest::get_config();
-        let alice_state0 = alice::State0::new(
-            btc_amount,
-            xmr_amount,
-            config,
-            redeem_address,
-            punish_address,
-            tx_redeem_fee,
-            tx_punish_fee,
-            &mut OsRng,
-        )
-        .unwrap();
-
-        let bob_state0 = bob::State0::new(
-            Uuid::new_v4(),
-            &mut OsRng,
-            btc_amount,
-            xmr_amount,
-            config.bitcoin_cancel_timelock,
-            config.bitcoin_punish_timelock,
-            bob_wallet.new_address().await.unwrap(),
-            config.monero_finality_confirmations,
-            spending_fee,
-            spending_fee,
-        );
-
-        let message0 = bob_state0.next_message();
-
-        let (_, alice_state1) = alice_state0.receive(message0).unwrap();
-        let alice_message1 = alice_state1.next_message();
-
-        let bob_state1 = bob_state0
-            .receive(&bob_wallet, alice_message1)
-            .await
-            .unwrap();
-        let bob_message2 = bob_state1.next_message();
-
-        let alice_state2 = alice_state1.receive(bob_message2).unwrap();
-        let alice_message3 = alice_state2.next_message();
-
-        let bob_state2 = bob_state1.receive(alice_message3).unwrap();
-        let bob_message4 = bob_state2.next_message();
-
-        let alice_state3 = alice_state2.receive(bob_message4).unwrap();
+        let (alice_state3, bob_state2) = execution_setup().await;
 
         let (bob_state3, _tx_lock) = bob_state2.lock_btc().await.unwrap();
         let bob_state4 = bob_state3.xmr_locked(monero_rpc::wallet::BlockHeight { height: 0 });
@@ -397,6 +364,32 @@ mod tests {
         assert_weight(refund_transaction, TxRefund::weight(), "TxRefund");
     }
 
+    #[tokio::test]
+    async fn given_externally_signed_agreed_lock_transaction_then_it_is_accepted() {
+        let (_, bob_state2) = execution_setup().await;
+        let (bob_state3, tx_lock) = bob_state2.lock_btc().await.unwrap();
+
+        let signed_tx_lock = bob_state3
+            .validate_signed_tx_lock(PartiallySignedTransaction::from(tx_lock.clone()))
+            .unwrap();
+
+        assert_eq!(signed_tx_lock.txid(), tx_lock.txid());
+    }
+
+    #[tokio::test]
+    async fn given_externally_signed_lock_transaction_with_other_txid_then_it_is_rejected() {
+        let (_, bob_state2) = execution_setup().await;
+        let (bob_state3, tx_lock) = bob_state2.lock_btc().await.unwrap();
+
+        // Same shared output, but a different transaction than agreed with Alice
+        let mut psbt = PartiallySignedTransaction::from(tx_lock);
+        psbt.global.unsigned_tx.lock_time += 1;
+
+        let result = bob_state3.validate_signed_tx_lock(psbt);
+
+        assert!(result.is_err());
+    }
+
     // Weights fluctuate because of the length of the signatures. Valid ecdsa
     // signatures can have 68, 69, 70, 71, or 72 bytes. Since most of our
     // transactions have 2 signatures the weight can be up to 8 bytes less than
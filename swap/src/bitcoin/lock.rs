@@ -42,48 +42,81 @@ impl TxLock {
         })
     }
 
+    /// Creates one lock transaction for several swaps, paying to the shared
+    /// output of each of the given amounts and keys.
+    ///
+    /// The returned instances share the same PSBT, one per swap in the given
+    /// order.
+    pub async fn new_batch<B, D, C>(
+        wallet: &Wallet<B, D, C>,
+        swaps: &[(Amount, PublicKey, PublicKey)],
+    ) -> Result<Vec<Self>>
+    where
+        C: EstimateFeeRate,
+        D: BatchDatabase,
+    {
+        let lock_output_descriptors = swaps
+            .iter()
+            .map(|(_, A, B)| build_shared_output_descriptor(A.0, B.0))
+            .collect::<Vec<_>>();
+        let recipients = lock_output_descriptors
+            .iter()
+            .zip(swaps)
+            .map(|(descriptor, (amount, _, _))| {
+                let address = descriptor
+                    .address(wallet.get_network())
+                    .expect("can derive address from descriptor");
+
+                (address, *amount)
+            })
+            .collect();
+
+        let psbt = wallet.send_to_addresses(recipients).await?;
+
+        Ok(lock_output_descriptors
+            .into_iter()
+            .map(|descriptor| Self {
+                inner: psbt.clone(),
+                output_descriptor: descriptor,
+            })
+            .collect())
+    }
+
     /// Creates an instance of `TxLock` from a PSBT, the public keys of the
     /// parties and the specified amount.
     ///
     /// This function validates that the given PSBT does indeed pay that
-    /// specified amount to a shared output.
+    /// specified amount to a shared output. The PSBT may lock the Bitcoin of
+    /// other swaps as well, only the shared output of the given keys is
+    /// validated.
     pub fn from_psbt(
         psbt: PartiallySignedTransaction,
         A: PublicKey,
         B: PublicKey,
         btc: Amount,
     ) -> Result<Self> {
-        let shared_output_candidate = match psbt.global.unsigned_tx.output.as_slice() {
-            [shared_output_candidate, _] if shared_output_candidate.value == btc.as_sat() => {
-                shared_output_candidate
-            }
-            [_, shared_output_candidate] if shared_output_candidate.value == btc.as_sat() => {
-                shared_output_candidate
-            }
-            // A single output is possible if Bob funds without any change necessary
-            [shared_output_candidate] if shared_output_candidate.value == btc.as_sat() => {
-                shared_output_candidate
-            }
-            [_, _] => {
-                bail!("Neither of the two provided outputs pays the right amount!");
-            }
-            [_] => {
-                bail!("The provided output does not pay the right amount!");
-            }
-            other => {
-                let num_outputs = other.len();
-                bail!(
-                    "PSBT has {} outputs, expected one or two. Something is fishy!",
-                    num_outputs
-                );
-            }
-        };
-
         let descriptor = build_shared_output_descriptor(A.0, B.0);
         let legit_shared_output_script = descriptor.script_pubkey();
 
-        if shared_output_candidate.script_pubkey != legit_shared_output_script {
-            bail!("Output script is not a shared output")
+        let shared_output_candidate = match psbt
+            .global
+            .unsigned_tx
+            .output
+            .iter()
+            .filter(|output| output.script_pubkey == legit_shared_output_script)
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            [shared_output_candidate] => *shared_output_candidate,
+            [] => bail!("None of the provided outputs is the shared output"),
+            other => bail!(
+                "PSBT has {} shared outputs, expected one. Something is fishy!",
+                other.len()
+            ),
+        };
+
+        if shared_output_candidate.value != btc.as_sat() {
+            bail!("The shared output does not pay the right amount!");
         }
 
         Ok(TxLock {
@@ -92,10 +125,48 @@ impl TxLock {
         })
     }
 
+    /// The weight of a lock transaction that spends a single P2WPKH output
+    /// and pays change, every further input adds to it.
+    pub fn weight() -> usize {
+        610
+    }
+
     pub fn lock_amount(&self) -> Amount {
         Amount::from_sat(self.inner.clone().extract_tx().output[self.lock_output_vout()].value)
     }
 
+    /// The fee paid by the lock transaction.
+    ///
+    /// Returns `None` if the PSBT does not carry the previous outputs of all
+    /// of its inputs.
+    pub fn fee(&self) -> Option<Amount> {
+        let unsigned_tx = &self.inner.global.unsigned_tx;
+
+        let input_value = self
+            .inner
+            .inputs
+            .iter()
+            .zip(unsigned_tx.input.iter())
+            .map(
+                |(input, txin)| match (&input.witness_utxo, &input.non_witness_utxo) {
+                    (Some(previous_output), _) => Some(previous_output.value),
+                    (None, Some(previous_tx)) => previous_tx
+                        .output
+                        .get(txin.previous_output.vout as usize)
+                        .map(|previous_output| previous_output.value),
+                    (None, None) => None,
+                },
+            )
+            .sum::<Option<u64>>()?;
+        let output_value = unsigned_tx
+            .output
+            .iter()
+            .map(|output| output.value)
+            .sum::<u64>();
+
+        input_value.checked_sub(output_value).map(Amount::from_sat)
+    }
+
     pub fn txid(&self) -> Txid {
         self.inner.clone().extract_tx().txid()
     }
@@ -216,6 +287,19 @@ mod tests {
         result.expect("PSBT to be valid");
     }
 
+    #[tokio::test]
+    async fn fee_is_the_difference_between_inputs_and_outputs() {
+        let (A, B) = alice_and_bob();
+        let fees = 610;
+        let agreed_amount = Amount::from_sat(10000);
+        let wallet = Wallet::new_funded_default_fees(agreed_amount.as_sat() + fees);
+
+        let psbt = bob_make_psbt(A, B, &wallet, agreed_amount).await;
+        let tx_lock = TxLock::from_psbt(psbt, A, B, agreed_amount).unwrap();
+
+        assert_eq!(tx_lock.fee(), Some(Amount::from_sat(fees)));
+    }
+
     #[tokio::test]
     async fn given_bob_is_sending_less_than_agreed_when_reconstructing_txlock_then_fails() {
         let (A, B) = alice_and_bob();
@@ -242,6 +326,52 @@ mod tests {
         result.expect_err("PSBT to be invalid");
     }
 
+    #[tokio::test]
+    async fn given_batched_psbt_when_reconstructing_then_each_swap_succeeds() {
+        let (A1, B1) = alice_and_bob();
+        let (A2, B2) = alice_and_bob();
+        let wallet = Wallet::new_funded_default_fees(50000);
+        let amount1 = Amount::from_sat(10000);
+        let amount2 = Amount::from_sat(20000);
+
+        let tx_locks = TxLock::new_batch(&wallet, &[(amount1, A1, B1), (amount2, A2, B2)])
+            .await
+            .unwrap();
+        let psbt = PartiallySignedTransaction::from(tx_locks[0].clone());
+        assert_eq!(
+            psbt.global.unsigned_tx.output.len(),
+            3,
+            "psbt should have two shared outputs and a change output"
+        );
+
+        let tx_lock1 = TxLock::from_psbt(psbt.clone(), A1, B1, amount1).unwrap();
+        let tx_lock2 = TxLock::from_psbt(psbt, A2, B2, amount2).unwrap();
+
+        assert_eq!(tx_lock1, tx_locks[0]);
+        assert_eq!(tx_lock2, tx_locks[1]);
+        assert_eq!(tx_lock1.txid(), tx_lock2.txid());
+        assert_eq!(tx_lock1.lock_amount(), amount1);
+        assert_eq!(tx_lock2.lock_amount(), amount2);
+        assert_ne!(tx_lock1.as_outpoint(), tx_lock2.as_outpoint());
+    }
+
+    #[tokio::test]
+    async fn given_batched_psbt_when_reconstructing_with_amount_of_other_swap_then_fails() {
+        let (A1, B1) = alice_and_bob();
+        let (A2, B2) = alice_and_bob();
+        let wallet = Wallet::new_funded_default_fees(50000);
+        let amount1 = Amount::from_sat(10000);
+        let amount2 = Amount::from_sat(20000);
+
+        let tx_locks = TxLock::new_batch(&wallet, &[(amount1, A1, B1), (amount2, A2, B2)])
+            .await
+            .unwrap();
+        let psbt = PartiallySignedTransaction::from(tx_locks[0].clone());
+        let result = TxLock::from_psbt(psbt, A1, B1, amount2);
+
+        result.expect_err("PSBT to be invalid");
+    }
+
     /// Helper function that represents Bob's action of constructing the PSBT.
     ///
     /// Extracting this allows us to keep the tests concise.
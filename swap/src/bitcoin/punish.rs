@@ -40,6 +40,14 @@ impl TxPunish {
         }
     }
 
+    pub fn txid(&self) -> Txid {
+        self.inner.txid()
+    }
+
+    pub fn amount(&self) -> Amount {
+        Amount::from_sat(self.inner.output[0].value)
+    }
+
     pub fn digest(&self) -> SigHash {
         self.digest
     }
@@ -48,6 +48,10 @@ impl TxRedeem {
         self.inner.txid()
     }
 
+    pub fn amount(&self) -> Amount {
+        Amount::from_sat(self.inner.output[0].value)
+    }
+
     pub fn digest(&self) -> SigHash {
         self.digest
     }
@@ -42,6 +42,10 @@ impl TxRefund {
         self.inner.txid()
     }
 
+    pub fn amount(&self) -> Amount {
+        Amount::from_sat(self.inner.output[0].value)
+    }
+
     pub fn digest(&self) -> SigHash {
         self.digest
     }
@@ -0,0 +1,158 @@
+//! Labels and coin control for the UTXOs of the Bitcoin wallet.
+//!
+//! A label is attached to the script of an address when the address is handed
+//! out, every UTXO received on that address carries the label. Change outputs
+//! are recognised by the keychain they were derived from.
+
+use crate::bitcoin::Amount;
+use ::bitcoin::{OutPoint, Script};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use uuid::Uuid;
+
+/// Where the UTXOs received on an address of the wallet come from.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "origin", rename_all = "lowercase")]
+pub enum UtxoLabel {
+    /// Deposited to fund a swap.
+    Deposit,
+    Redeem {
+        swap_id: Uuid,
+    },
+    Punish {
+        swap_id: Uuid,
+    },
+    Refund {
+        swap_id: Uuid,
+    },
+}
+
+impl fmt::Display for UtxoLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UtxoLabel::Deposit => write!(f, "deposit"),
+            UtxoLabel::Redeem { swap_id } => write!(f, "redeem of swap {}", swap_id),
+            UtxoLabel::Punish { swap_id } => write!(f, "punish of swap {}", swap_id),
+            UtxoLabel::Refund { swap_id } => write!(f, "refund of swap {}", swap_id),
+        }
+    }
+}
+
+/// Which UTXOs of the wallet may be spent when building a transaction.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CoinControl {
+    /// Any UTXO may be spent.
+    Any,
+    /// Only the given UTXOs are spent, all of them.
+    Only(Vec<OutPoint>),
+    /// Any UTXO but the given ones may be spent.
+    Exclude(Vec<OutPoint>),
+}
+
+impl Default for CoinControl {
+    fn default() -> Self {
+        CoinControl::Any
+    }
+}
+
+impl CoinControl {
+    pub fn from_flags(utxos: Vec<OutPoint>, exclude_utxos: Vec<OutPoint>) -> Self {
+        match (utxos.is_empty(), exclude_utxos.is_empty()) {
+            (false, _) => CoinControl::Only(utxos),
+            (true, false) => CoinControl::Exclude(exclude_utxos),
+            (true, true) => CoinControl::Any,
+        }
+    }
+}
+
+/// An unspent output of the wallet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Utxo {
+    pub outpoint: OutPoint,
+    pub amount: Amount,
+    pub script_pubkey: Script,
+    /// Whether the output is change of a transaction of the wallet.
+    pub change: bool,
+    pub label: Option<UtxoLabel>,
+}
+
+impl Utxo {
+    /// The origin of the UTXO, change or unlabelled if it carries no label.
+    pub fn origin(&self) -> String {
+        match (self.label, self.change) {
+            (Some(label), _) => label.to_string(),
+            (None, true) => "change".to_string(),
+            (None, false) => "unlabelled".to_string(),
+        }
+    }
+}
+
+/// Attaches the labels of the scripts the UTXOs were received on.
+pub fn with_labels(utxos: Vec<Utxo>, labels: Vec<(Script, UtxoLabel)>) -> Vec<Utxo> {
+    let labels = labels.into_iter().collect::<HashMap<_, _>>();
+
+    utxos
+        .into_iter()
+        .map(|utxo| Utxo {
+            label: labels.get(&utxo.script_pubkey).copied(),
+            ..utxo
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::bitcoin::hashes::Hash;
+    use ::bitcoin::Txid;
+
+    #[test]
+    fn utxos_carry_the_label_of_their_script() {
+        let swap_id = Uuid::new_v4();
+        let refund = utxo(0, Script::from(vec![1u8; 22]), false);
+        let change = utxo(1, Script::from(vec![2u8; 22]), true);
+        let unknown = utxo(2, Script::from(vec![3u8; 22]), false);
+
+        let utxos = with_labels(
+            vec![refund.clone(), change, unknown],
+            vec![(refund.script_pubkey, UtxoLabel::Refund { swap_id })],
+        );
+        let origins = utxos.iter().map(Utxo::origin).collect::<Vec<_>>();
+
+        assert_eq!(
+            origins,
+            vec![
+                format!("refund of swap {}", swap_id),
+                "change".to_string(),
+                "unlabelled".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn selected_utxos_take_precedence_over_excluded_ones() {
+        let selected = OutPoint::new(Txid::from_inner([1u8; 32]), 0);
+        let excluded = OutPoint::new(Txid::from_inner([2u8; 32]), 1);
+
+        assert_eq!(CoinControl::from_flags(vec![], vec![]), CoinControl::Any);
+        assert_eq!(
+            CoinControl::from_flags(vec![], vec![excluded]),
+            CoinControl::Exclude(vec![excluded])
+        );
+        assert_eq!(
+            CoinControl::from_flags(vec![selected], vec![excluded]),
+            CoinControl::Only(vec![selected])
+        );
+    }
+
+    fn utxo(vout: u32, script_pubkey: Script, change: bool) -> Utxo {
+        Utxo {
+            outpoint: OutPoint::new(Txid::default(), vout),
+            amount: Amount::from_sat(10_000),
+            script_pubkey,
+            change,
+            label: None,
+        }
+    }
+}
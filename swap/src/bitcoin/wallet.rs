@@ -1,14 +1,16 @@
 use crate::bitcoin::timelocks::BlockHeight;
-use crate::bitcoin::{Address, Amount, Transaction};
+use crate::bitcoin::{Address, Amount, CoinControl, Transaction, Utxo};
 use crate::env;
 use ::bitcoin::util::psbt::PartiallySignedTransaction;
 use ::bitcoin::Txid;
 use anyhow::{bail, Context, Result};
 use bdk::blockchain::{noop_progress, Blockchain, ElectrumBlockchain};
 use bdk::database::BatchDatabase;
-use bdk::descriptor::Segwitv0;
-use bdk::electrum_client::{ElectrumApi, GetHistoryRes};
+use bdk::descriptor::{IntoWalletDescriptor, Segwitv0};
+use bdk::electrum_client::{ConfigBuilder, ElectrumApi, GetHistoryRes, Socks5Config};
 use bdk::keys::DerivableKey;
+use bdk::wallet::coin_selection::DefaultCoinSelectionAlgorithm;
+use bdk::wallet::tx_builder::{CreateTx, TxBuilder, TxOrdering};
 use bdk::wallet::AddressIndex;
 use bdk::{FeeRate, KeychainKind, SignOptions};
 use bitcoin::{Network, Script};
@@ -20,6 +22,7 @@ use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap};
 use std::convert::TryFrom;
 use std::fmt;
+use std::net::{Ipv4Addr, SocketAddrV4};
 use std::path::Path;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -39,6 +42,8 @@ pub struct Wallet<B = ElectrumBlockchain, D = bdk::sled::Tree, C = Client> {
     finality_confirmations: u32,
     network: Network,
     target_block: usize,
+    /// Which UTXOs the transactions built by the wallet may spend.
+    coin_control: CoinControl,
 }
 
 impl Wallet {
@@ -48,22 +53,69 @@ impl Wallet {
         key: impl DerivableKey<Segwitv0> + Clone,
         env_config: env::Config,
         target_block: usize,
+        tor_socks5_port: Option<u16>,
     ) -> Result<Self> {
-        let client = bdk::electrum_client::Client::new(electrum_rpc_url.as_str())
-            .context("Failed to initialize Electrum RPC client")?;
+        Self::from_descriptors(
+            electrum_rpc_url,
+            wallet_dir,
+            bdk::template::Bip84(key.clone(), KeychainKind::External),
+            Some(bdk::template::Bip84(key, KeychainKind::Internal)),
+            env_config,
+            target_block,
+            tor_socks5_port,
+        )
+    }
+
+    /// Opens a watch-only wallet for the given output descriptor.
+    ///
+    /// The wallet tracks the funds of an external wallet and can build and
+    /// finalize transactions spending them, but the signatures have to be
+    /// provided by the external wallet.
+    pub async fn new_watch_only(
+        electrum_rpc_url: Url,
+        wallet_dir: &Path,
+        descriptor: &str,
+        env_config: env::Config,
+        target_block: usize,
+        tor_socks5_port: Option<u16>,
+    ) -> Result<Self> {
+        Self::from_descriptors(
+            electrum_rpc_url,
+            wallet_dir,
+            descriptor,
+            None,
+            env_config,
+            target_block,
+            tor_socks5_port,
+        )
+    }
+
+    /// Connects to Electrum through the Tor socks5 proxy if its port is given.
+    fn from_descriptors<E>(
+        electrum_rpc_url: Url,
+        wallet_dir: &Path,
+        descriptor: E,
+        change_descriptor: Option<E>,
+        env_config: env::Config,
+        target_block: usize,
+        tor_socks5_port: Option<u16>,
+    ) -> Result<Self>
+    where
+        E: IntoWalletDescriptor,
+    {
+        let client = electrum_client(&electrum_rpc_url, tor_socks5_port)?;
 
         let db = bdk::sled::open(wallet_dir)?.open_tree(SLED_TREE_NAME)?;
 
         let wallet = bdk::Wallet::new(
-            bdk::template::Bip84(key.clone(), KeychainKind::External),
-            Some(bdk::template::Bip84(key, KeychainKind::Internal)),
+            descriptor,
+            change_descriptor,
             env_config.bitcoin_network,
             db,
             ElectrumBlockchain::from(client),
         )?;
 
-        let electrum = bdk::electrum_client::Client::new(electrum_rpc_url.as_str())
-            .context("Failed to initialize Electrum RPC client")?;
+        let electrum = electrum_client(&electrum_rpc_url, tor_socks5_port)?;
 
         let network = wallet.network();
 
@@ -76,6 +128,7 @@ impl Wallet {
             finality_confirmations: env_config.bitcoin_finality_confirmations,
             network,
             target_block,
+            coin_control: CoinControl::Any,
         })
     }
 
@@ -265,6 +318,23 @@ where
         Ok(tx)
     }
 
+    /// Finalizes a PSBT that was signed by an external wallet.
+    ///
+    /// Some wallets finalize the inputs they signed themselves, in that case
+    /// the transaction is extracted as is.
+    pub async fn finalize(&self, psbt: PartiallySignedTransaction) -> Result<Transaction> {
+        let finalized_by_signer = psbt
+            .inputs
+            .iter()
+            .all(|input| input.final_script_witness.is_some() || input.final_script_sig.is_some());
+
+        if finalized_by_signer {
+            return Ok(psbt.extract_tx());
+        }
+
+        self.sign_and_finalize(psbt).await
+    }
+
     pub async fn balance(&self) -> Result<Amount> {
         let balance = self
             .wallet
@@ -276,6 +346,27 @@ where
         Ok(Amount::from_sat(balance))
     }
 
+    /// Lists the unspent outputs of the wallet, without labels.
+    pub async fn utxos(&self) -> Result<Vec<Utxo>> {
+        let utxos = self
+            .wallet
+            .lock()
+            .await
+            .list_unspent()
+            .context("Failed to list UTXOs of Bitcoin wallet")?
+            .into_iter()
+            .map(|utxo| Utxo {
+                outpoint: utxo.outpoint,
+                amount: Amount::from_sat(utxo.txout.value),
+                script_pubkey: utxo.txout.script_pubkey,
+                change: utxo.keychain == KeychainKind::Internal,
+                label: None,
+            })
+            .collect();
+
+        Ok(utxos)
+    }
+
     pub async fn new_address(&self) -> Result<Address> {
         let address = self
             .wallet
@@ -323,6 +414,7 @@ where
         let mut tx_builder = wallet.build_tx();
         tx_builder.add_recipient(script.clone(), amount.as_sat());
         tx_builder.fee_rate(fee_rate);
+        apply_coin_control(&mut tx_builder, &self.coin_control)?;
         let (psbt, _details) = tx_builder.finish()?;
         let mut psbt: PartiallySignedTransaction = psbt;
 
@@ -346,6 +438,41 @@ where
         Ok(psbt)
     }
 
+    /// Builds a partially signed transaction paying to all given addresses
+    ///
+    /// The outputs of the recipients are kept in the given order, any change
+    /// output comes last.
+    pub async fn send_to_addresses(
+        &self,
+        recipients: Vec<(Address, Amount)>,
+    ) -> Result<PartiallySignedTransaction> {
+        if recipients.is_empty() {
+            bail!("Cannot build PSBT without any recipients");
+        }
+
+        if let Some((address, _)) = recipients
+            .iter()
+            .find(|(address, _)| address.network != self.network)
+        {
+            bail!("Cannot build PSBT because network of given address is {} but wallet is on network {}", address.network, self.network);
+        }
+
+        let wallet = self.wallet.lock().await;
+        let client = self.client.lock().await;
+        let fee_rate = client.estimate_feerate(self.target_block)?;
+
+        let mut tx_builder = wallet.build_tx();
+        for (address, amount) in recipients {
+            tx_builder.add_recipient(address.script_pubkey(), amount.as_sat());
+        }
+        tx_builder.ordering(TxOrdering::Untouched);
+        tx_builder.fee_rate(fee_rate);
+        apply_coin_control(&mut tx_builder, &self.coin_control)?;
+        let (psbt, _details) = tx_builder.finish()?;
+
+        Ok(psbt)
+    }
+
     /// Calculates the maximum "giveable" amount of this wallet.
     ///
     /// We define this as the maximum amount we can pay to a single output,
@@ -370,8 +497,13 @@ where
 
         let dummy_script = Script::from(vec![0u8; locking_script_size]);
         tx_builder.set_single_recipient(dummy_script);
-        tx_builder.drain_wallet();
+        // Selected UTXOs are spent in full, draining the wallet would add all
+        // others.
+        if !matches!(self.coin_control, CoinControl::Only(_)) {
+            tx_builder.drain_wallet();
+        }
         tx_builder.fee_rate(fee_rate);
+        apply_coin_control(&mut tx_builder, &self.coin_control)?;
 
         let response = tx_builder.finish();
         match response {
@@ -402,6 +534,43 @@ where
     }
 }
 
+/// Restricts the UTXOs a transaction may spend to the ones allowed by the
+/// coin control.
+fn apply_coin_control<B, D>(
+    tx_builder: &mut TxBuilder<'_, B, D, DefaultCoinSelectionAlgorithm, CreateTx>,
+    coin_control: &CoinControl,
+) -> Result<()>
+where
+    D: BatchDatabase,
+{
+    match coin_control {
+        CoinControl::Any => {}
+        CoinControl::Only(utxos) => {
+            tx_builder
+                .add_utxos(utxos)
+                .context("Failed to select UTXOs, they are not spendable by the wallet")?
+                .manually_selected_only();
+        }
+        CoinControl::Exclude(utxos) => {
+            tx_builder.unspendable(utxos.clone());
+        }
+    }
+
+    Ok(())
+}
+
+fn electrum_client(
+    electrum_rpc_url: &Url,
+    tor_socks5_port: Option<u16>,
+) -> Result<bdk::electrum_client::Client> {
+    let socks5 =
+        tor_socks5_port.map(|port| Socks5Config::new(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port)));
+    let config = ConfigBuilder::new().socks5(socks5)?.build();
+
+    bdk::electrum_client::Client::from_config(electrum_rpc_url.as_str(), config)
+        .context("Failed to initialize Electrum RPC client")
+}
+
 fn estimate_fee(
     weight: usize,
     transfer_amount: Amount,
@@ -497,6 +666,12 @@ impl<B, D, C> Wallet<B, D, C> {
     pub fn get_network(&self) -> bitcoin::Network {
         self.network
     }
+
+    /// Restricts the UTXOs the transactions built by the wallet may spend.
+    pub fn with_coin_control(mut self, coin_control: CoinControl) -> Self {
+        self.coin_control = coin_control;
+        self
+    }
 }
 
 pub trait EstimateFeeRate {
@@ -541,6 +716,21 @@ impl Wallet<(), bdk::database::MemoryDatabase, StaticFeeRate> {
 
     /// Creates a new, funded wallet to be used within tests.
     pub fn new_funded(amount: u64, sats_per_vb: f32, min_relay_fee_sats: u64) -> Self {
+        Self::new_funded_with_keys(amount, sats_per_vb, min_relay_fee_sats, true)
+    }
+
+    /// Creates a new, funded wallet that only knows the public keys of its
+    /// descriptor, like the watch-only wallet used with an external wallet.
+    pub fn new_funded_watch_only(amount: u64) -> Self {
+        Self::new_funded_with_keys(amount, 1.0, 1000, false)
+    }
+
+    fn new_funded_with_keys(
+        amount: u64,
+        sats_per_vb: f32,
+        min_relay_fee_sats: u64,
+        private_keys: bool,
+    ) -> Self {
         use bdk::database::MemoryDatabase;
         use bdk::{LocalUtxo, TransactionDetails};
         use bitcoin::OutPoint;
@@ -557,8 +747,22 @@ impl Wallet<(), bdk::database::MemoryDatabase, StaticFeeRate> {
             Some(100)
         );
 
+        let descriptor = if private_keys {
+            descriptors.0.clone()
+        } else {
+            // The displayed descriptor only contains the public keys
+            let secp = bitcoin::secp256k1::Secp256k1::new();
+            let (descriptor, _) = descriptors
+                .0
+                .as_str()
+                .into_wallet_descriptor(&secp, Network::Regtest)
+                .unwrap();
+
+            descriptor.to_string()
+        };
+
         let wallet =
-            bdk::Wallet::new_offline(&descriptors.0, None, Network::Regtest, database).unwrap();
+            bdk::Wallet::new_offline(&descriptor, None, Network::Regtest, database).unwrap();
 
         Self {
             client: Arc::new(Mutex::new(StaticFeeRate {
@@ -569,6 +773,7 @@ impl Wallet<(), bdk::database::MemoryDatabase, StaticFeeRate> {
             finality_confirmations: 1,
             network: Network::Regtest,
             target_block: 1,
+            coin_control: CoinControl::Any,
         }
     }
 }
@@ -1042,6 +1247,75 @@ mod tests {
         assert!(amount.as_sat() > 0);
     }
 
+    #[tokio::test]
+    async fn given_only_utxo_excluded_then_nothing_can_be_spent() {
+        let wallet = Wallet::new_funded_default_fees(10_000);
+        let utxo = wallet.utxos().await.unwrap()[0].outpoint;
+        let wallet = wallet.with_coin_control(CoinControl::Exclude(vec![utxo]));
+
+        let amount = wallet.max_giveable(TxLock::script_size()).await.unwrap();
+        let (A, B) = (PublicKey::random(), PublicKey::random());
+        let result = TxLock::new(&wallet, Amount::from_sat(5_000), A, B).await;
+
+        assert_eq!(amount, Amount::ZERO);
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn given_selected_utxo_then_it_is_spent() {
+        let wallet = Wallet::new_funded_default_fees(10_000);
+        let utxo = wallet.utxos().await.unwrap()[0].outpoint;
+        let max_giveable = wallet.max_giveable(TxLock::script_size()).await.unwrap();
+        let wallet = wallet.with_coin_control(CoinControl::Only(vec![utxo]));
+
+        let amount = wallet.max_giveable(TxLock::script_size()).await.unwrap();
+        let (A, B) = (PublicKey::random(), PublicKey::random());
+        let tx_lock = TxLock::new(&wallet, Amount::from_sat(5_000), A, B)
+            .await
+            .unwrap();
+        let psbt = PartiallySignedTransaction::from(tx_lock);
+
+        assert_eq!(amount, max_giveable);
+        assert_eq!(psbt.global.unsigned_tx.input[0].previous_output, utxo);
+    }
+
+    #[tokio::test]
+    async fn given_unsigned_psbt_when_finalizing_then_fails() {
+        let wallet = Wallet::new_funded_watch_only(10_000);
+        let (A, B) = (PublicKey::random(), PublicKey::random());
+        let tx_lock = TxLock::new(&wallet, Amount::from_sat(5_000), A, B)
+            .await
+            .unwrap();
+
+        let result = wallet.finalize(tx_lock.into()).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn given_externally_signed_psbt_when_finalizing_then_signed_tx_is_extracted() {
+        let signer = Wallet::new_funded_default_fees(10_000);
+        let wallet = Wallet::new_funded_watch_only(10_000);
+        let (A, B) = (PublicKey::random(), PublicKey::random());
+        let tx_lock = TxLock::new(&wallet, Amount::from_sat(5_000), A, B)
+            .await
+            .unwrap();
+        let txid = tx_lock.txid();
+
+        let mut psbt = PartiallySignedTransaction::from(tx_lock);
+        let finalized = signer
+            .wallet
+            .lock()
+            .await
+            .sign(&mut psbt, SignOptions::default())
+            .unwrap();
+        let tx = wallet.finalize(psbt).await.unwrap();
+
+        assert!(finalized);
+        assert_eq!(tx.txid(), txid);
+        assert!(tx.input.iter().all(|input| !input.witness.is_empty()));
+    }
+
     /// This test ensures that the relevant script output of the transaction
     /// created out of the PSBT is at index 0. This is important because
     /// subscriptions to the transaction are on index `0` when broadcasting the
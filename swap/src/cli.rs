@@ -1,3 +1,9 @@
 pub mod command;
+pub mod confirm;
+pub mod psbt;
 pub mod tracing;
 pub mod transport;
+
+mod qr_code;
+
+pub use qr_code::qr_code;
@@ -1,10 +1,17 @@
+use crate::bitcoin::CoinControl;
+use crate::cli::confirm::Confirmation;
 use crate::env::GetConfig;
 use crate::fs::system_data_dir;
-use crate::{env, monero};
-use anyhow::{Context, Result};
+use crate::network::spot_price;
+use crate::{bitcoin, database, env, history, monero};
+use ::bitcoin::OutPoint;
+use anyhow::{bail, Context, Result};
 use libp2p::core::Multiaddr;
 use libp2p::PeerId;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
 use std::ffi::OsString;
+use std::fmt;
 use std::path::PathBuf;
 use std::str::FromStr;
 use structopt::{clap, StructOpt};
@@ -25,12 +32,17 @@ const DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET: usize = 1;
 
 const DEFAULT_TOR_SOCKS5_PORT: &str = "9050";
 
+/// How many blocks proposed timelocks have to exceed the Bitcoin finality
+/// confirmations by, so there is time to react once a transaction is final.
+const MIN_TIMELOCK_MARGIN: u32 = 3;
+
 #[derive(Debug, PartialEq)]
 pub struct Arguments {
     pub env_config: env::Config,
     pub debug: bool,
     pub json: bool,
     pub data_dir: PathBuf,
+    pub database: database::Backend,
     pub cmd: Command,
 }
 
@@ -65,6 +77,7 @@ where
     let json = args.json;
     let is_testnet = args.testnet;
     let data = args.data;
+    let database = args.database;
 
     let arguments = match args.cmd {
         RawCommand::BuyXmr {
@@ -80,12 +93,36 @@ where
                     monero_receive_address,
                     monero_daemon_address,
                 },
-            tor: Tor { tor_socks5_port },
+            tor: Tor {
+                tor_socks5_port,
+                tor_only,
+            },
+            external_wallet:
+                ExternalWallet {
+                    external_wallet_descriptor,
+                },
+            timelocks:
+                Timelocks {
+                    cancel_timelock,
+                    punish_timelock,
+                },
+            accept_terms: AcceptTerms { yes, max_slippage },
+            exact_amount:
+                ExactAmount {
+                    btc_amount,
+                    xmr_amount,
+                },
+            select_utxos:
+                SelectUtxos {
+                    utxos,
+                    exclude_utxos,
+                },
         } => Arguments {
             env_config: env_config_from(is_testnet),
             debug,
             json,
             data_dir: data::data_dir_from(data, is_testnet)?,
+            database,
             cmd: Command::BuyXmr {
                 seller_peer_id,
                 seller_addr,
@@ -103,14 +140,34 @@ where
                     is_testnet,
                 ),
                 tor_socks5_port,
+                tor_only,
+                external_wallet_descriptor,
+                timelocks: timelocks_from(
+                    cancel_timelock,
+                    punish_timelock,
+                    env_config_from(is_testnet),
+                )?,
+                confirmation: Confirmation { yes, max_slippage },
+                amount: swap_amount_from(btc_amount, xmr_amount),
+                coin_control: CoinControl::from_flags(utxos, exclude_utxos),
             },
         },
-        RawCommand::History => Arguments {
+        RawCommand::History {
+            swap_id,
+            states,
+            json: history_json,
+            csv,
+        } => Arguments {
             env_config: env_config_from(is_testnet),
             debug,
             json,
             data_dir: data::data_dir_from(data, is_testnet)?,
-            cmd: Command::History,
+            database,
+            cmd: Command::History {
+                swap_id,
+                states,
+                format: history::Format::from_flags(history_json, csv),
+            },
         },
         RawCommand::Resume {
             swap_id: SwapId { swap_id },
@@ -125,12 +182,25 @@ where
                     monero_receive_address,
                     monero_daemon_address,
                 },
-            tor: Tor { tor_socks5_port },
+            tor: Tor {
+                tor_socks5_port,
+                tor_only,
+            },
+            external_wallet:
+                ExternalWallet {
+                    external_wallet_descriptor,
+                },
+            select_utxos:
+                SelectUtxos {
+                    utxos,
+                    exclude_utxos,
+                },
         } => Arguments {
             env_config: env_config_from(is_testnet),
             debug,
             json,
             data_dir: data::data_dir_from(data, is_testnet)?,
+            database,
             cmd: Command::Resume {
                 swap_id,
                 seller_addr,
@@ -145,6 +215,9 @@ where
                     is_testnet,
                 ),
                 tor_socks5_port,
+                tor_only,
+                external_wallet_descriptor,
+                coin_control: CoinControl::from_flags(utxos, exclude_utxos),
             },
         },
         RawCommand::Cancel {
@@ -155,11 +228,16 @@ where
                     bitcoin_electrum_rpc_url,
                     bitcoin_target_block,
                 },
+            tor: Tor {
+                tor_socks5_port,
+                tor_only,
+            },
         } => Arguments {
             env_config: env_config_from(is_testnet),
             debug,
             json,
             data_dir: data::data_dir_from(data, is_testnet)?,
+            database,
             cmd: Command::Cancel {
                 swap_id,
                 force,
@@ -168,6 +246,8 @@ where
                     is_testnet,
                 )?,
                 bitcoin_target_block: bitcoin_target_block_from(bitcoin_target_block, is_testnet),
+                tor_socks5_port,
+                tor_only,
             },
         },
         RawCommand::Refund {
@@ -178,11 +258,16 @@ where
                     bitcoin_electrum_rpc_url,
                     bitcoin_target_block,
                 },
+            tor: Tor {
+                tor_socks5_port,
+                tor_only,
+            },
         } => Arguments {
             env_config: env_config_from(is_testnet),
             debug,
             json,
             data_dir: data::data_dir_from(data, is_testnet)?,
+            database,
             cmd: Command::Refund {
                 swap_id,
                 force,
@@ -191,6 +276,139 @@ where
                     is_testnet,
                 )?,
                 bitcoin_target_block: bitcoin_target_block_from(bitcoin_target_block, is_testnet),
+                tor_socks5_port,
+                tor_only,
+            },
+        },
+        RawCommand::Balance {
+            bitcoin:
+                Bitcoin {
+                    bitcoin_electrum_rpc_url,
+                    bitcoin_target_block,
+                },
+            tor: Tor {
+                tor_socks5_port,
+                tor_only,
+            },
+        } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            database,
+            cmd: Command::Balance {
+                bitcoin_electrum_rpc_url: bitcoin_electrum_rpc_url_from(
+                    bitcoin_electrum_rpc_url,
+                    is_testnet,
+                )?,
+                bitcoin_target_block: bitcoin_target_block_from(bitcoin_target_block, is_testnet),
+                tor_socks5_port,
+                tor_only,
+            },
+        },
+        RawCommand::ListUtxos {
+            bitcoin:
+                Bitcoin {
+                    bitcoin_electrum_rpc_url,
+                    bitcoin_target_block,
+                },
+            tor: Tor {
+                tor_socks5_port,
+                tor_only,
+            },
+        } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            database,
+            cmd: Command::ListUtxos {
+                bitcoin_electrum_rpc_url: bitcoin_electrum_rpc_url_from(
+                    bitcoin_electrum_rpc_url,
+                    is_testnet,
+                )?,
+                bitcoin_target_block: bitcoin_target_block_from(bitcoin_target_block, is_testnet),
+                tor_socks5_port,
+                tor_only,
+            },
+        },
+        RawCommand::MigrateDatabase => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            database,
+            cmd: Command::MigrateDatabase,
+        },
+        RawCommand::Export {
+            swap_id: SwapId { swap_id },
+            output,
+            encrypt,
+        } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            database,
+            cmd: Command::Export {
+                swap_id,
+                output,
+                encrypt,
+            },
+        },
+        RawCommand::Import { file } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            database,
+            cmd: Command::Import { file },
+        },
+        RawCommand::Seed {
+            restore_from_mnemonic,
+            cmd,
+        } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            database,
+            cmd: seed_command(restore_from_mnemonic, cmd)?,
+        },
+        RawCommand::WithdrawBtc {
+            amount,
+            address,
+            bitcoin:
+                Bitcoin {
+                    bitcoin_electrum_rpc_url,
+                    bitcoin_target_block,
+                },
+            tor: Tor {
+                tor_socks5_port,
+                tor_only,
+            },
+            select_utxos:
+                SelectUtxos {
+                    utxos,
+                    exclude_utxos,
+                },
+        } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            database,
+            cmd: Command::WithdrawBtc {
+                amount,
+                address: bitcoin::validate_address(address, is_testnet)?,
+                bitcoin_electrum_rpc_url: bitcoin_electrum_rpc_url_from(
+                    bitcoin_electrum_rpc_url,
+                    is_testnet,
+                )?,
+                bitcoin_target_block: bitcoin_target_block_from(bitcoin_target_block, is_testnet),
+                tor_socks5_port,
+                tor_only,
+                coin_control: CoinControl::from_flags(utxos, exclude_utxos),
             },
         },
     };
@@ -208,8 +426,18 @@ pub enum Command {
         monero_receive_address: monero::Address,
         monero_daemon_address: String,
         tor_socks5_port: u16,
+        tor_only: bool,
+        external_wallet_descriptor: Option<String>,
+        timelocks: Option<spot_price::Timelocks>,
+        confirmation: Confirmation,
+        amount: Option<SwapAmount>,
+        coin_control: CoinControl,
+    },
+    History {
+        swap_id: Option<Uuid>,
+        states: bool,
+        format: history::Format,
     },
-    History,
     Resume {
         swap_id: Uuid,
         seller_addr: Multiaddr,
@@ -218,19 +446,97 @@ pub enum Command {
         monero_receive_address: monero::Address,
         monero_daemon_address: String,
         tor_socks5_port: u16,
+        tor_only: bool,
+        external_wallet_descriptor: Option<String>,
+        coin_control: CoinControl,
     },
     Cancel {
         swap_id: Uuid,
         force: bool,
         bitcoin_electrum_rpc_url: Url,
         bitcoin_target_block: usize,
+        tor_socks5_port: u16,
+        tor_only: bool,
     },
     Refund {
         swap_id: Uuid,
         force: bool,
         bitcoin_electrum_rpc_url: Url,
         bitcoin_target_block: usize,
+        tor_socks5_port: u16,
+        tor_only: bool,
+    },
+    Balance {
+        bitcoin_electrum_rpc_url: Url,
+        bitcoin_target_block: usize,
+        tor_socks5_port: u16,
+        tor_only: bool,
+    },
+    ListUtxos {
+        bitcoin_electrum_rpc_url: Url,
+        bitcoin_target_block: usize,
+        tor_socks5_port: u16,
+        tor_only: bool,
+    },
+    WithdrawBtc {
+        amount: Option<bitcoin::Amount>,
+        address: bitcoin::Address,
+        bitcoin_electrum_rpc_url: Url,
+        bitcoin_target_block: usize,
+        tor_socks5_port: u16,
+        tor_only: bool,
+        coin_control: CoinControl,
+    },
+    MigrateDatabase,
+    Export {
+        swap_id: Uuid,
+        output: PathBuf,
+        encrypt: bool,
     },
+    Import {
+        file: PathBuf,
+    },
+    EncryptSeed,
+    ChangeSeedPassphrase,
+    ShowMnemonic,
+    RestoreSeed,
+}
+
+/// The amount to swap instead of the whole balance of the wallet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SwapAmount {
+    Btc(bitcoin::Amount),
+    /// The BTC that buys this amount of XMR at the price of the quote.
+    Xmr(monero::Amount),
+}
+
+impl SwapAmount {
+    /// The BTC to swap, an XMR amount is converted at the given price of 1
+    /// XMR and rounded up.
+    pub fn to_btc(self, price: bitcoin::Amount) -> Result<bitcoin::Amount> {
+        match self {
+            SwapAmount::Btc(btc) => Ok(btc),
+            SwapAmount::Xmr(xmr) => {
+                let sats = (xmr.as_piconero_decimal() * Decimal::from(price.as_sat()))
+                    .checked_div(monero::Amount::ONE_XMR.as_piconero_decimal())
+                    .context("Division overflow")?
+                    .ceil()
+                    .to_u64()
+                    .context("Failed to fit BTC amount into u64")?;
+
+                Ok(bitcoin::Amount::from_sat(sats))
+            }
+        }
+    }
+}
+
+impl fmt::Display for SwapAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SwapAmount::Btc(btc) => write!(f, "{}", btc),
+            SwapAmount::Xmr(xmr) => write!(f, "{}", xmr),
+        }
+    }
 }
 
 #[derive(structopt::StructOpt, Debug)]
@@ -250,6 +556,13 @@ pub struct RawArguments {
     )]
     pub data: Option<PathBuf>,
 
+    #[structopt(
+        long = "database",
+        help = "The database backend to store swaps in, either sled or sqlite",
+        default_value = "sled"
+    )]
+    pub database: database::Backend,
+
     #[structopt(long, help = "Activate debug logging.")]
     pub debug: bool,
 
@@ -282,9 +595,47 @@ pub enum RawCommand {
 
         #[structopt(flatten)]
         tor: Tor,
+
+        #[structopt(flatten)]
+        external_wallet: ExternalWallet,
+
+        #[structopt(flatten)]
+        timelocks: Timelocks,
+
+        #[structopt(flatten)]
+        accept_terms: AcceptTerms,
+
+        #[structopt(flatten)]
+        exact_amount: ExactAmount,
+
+        #[structopt(flatten)]
+        select_utxos: SelectUtxos,
     },
     /// Show a list of past ongoing and completed swaps
-    History,
+    History {
+        #[structopt(help = "Only show the swap with this id")]
+        swap_id: Option<Uuid>,
+
+        #[structopt(
+            long = "states",
+            help = "Show all states the swap went through and when it entered them",
+            requires = "swap-id"
+        )]
+        states: bool,
+
+        #[structopt(
+            long = "json",
+            help = "Print the history as JSON including all recorded details of each swap",
+            conflicts_with = "csv"
+        )]
+        json: bool,
+
+        #[structopt(
+            long = "csv",
+            help = "Print the history as CSV including all recorded details of each swap"
+        )]
+        csv: bool,
+    },
     /// Resume a swap
     Resume {
         #[structopt(flatten)]
@@ -301,6 +652,12 @@ pub enum RawCommand {
 
         #[structopt(flatten)]
         tor: Tor,
+
+        #[structopt(flatten)]
+        external_wallet: ExternalWallet,
+
+        #[structopt(flatten)]
+        select_utxos: SelectUtxos,
     },
     /// Try to cancel an ongoing swap (expert users only)
     Cancel {
@@ -312,6 +669,9 @@ pub enum RawCommand {
 
         #[structopt(flatten)]
         bitcoin: Bitcoin,
+
+        #[structopt(flatten)]
+        tor: Tor,
     },
     /// Try to cancel a swap and refund my BTC (expert users only)
     Refund {
@@ -323,7 +683,93 @@ pub enum RawCommand {
 
         #[structopt(flatten)]
         bitcoin: Bitcoin,
+
+        #[structopt(flatten)]
+        tor: Tor,
+    },
+    /// Prints the Bitcoin balance of the internal wallet
+    Balance {
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+
+        #[structopt(flatten)]
+        tor: Tor,
+    },
+    /// Prints the UTXOs of the internal wallet and where they came from
+    ListUtxos {
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+
+        #[structopt(flatten)]
+        tor: Tor,
+    },
+    /// Withdraw BTC from the internal Bitcoin wallet
+    WithdrawBtc {
+        #[structopt(
+            long = "amount",
+            help = "Optionally specify the amount of Bitcoin to be withdrawn. If not specified the wallet will be drained."
+        )]
+        amount: Option<bitcoin::Amount>,
+
+        #[structopt(long = "address", help = "The address to receive the Bitcoin.")]
+        address: bitcoin::Address,
+
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+
+        #[structopt(flatten)]
+        tor: Tor,
+
+        #[structopt(flatten)]
+        select_utxos: SelectUtxos,
+    },
+    /// Copy the swaps of the sled database into a new SQLite database, use
+    /// `--database sqlite` afterwards to use it
+    MigrateDatabase,
+    /// Write a swap into a recovery file that can be imported on another
+    /// machine
+    Export {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+
+        #[structopt(
+            long = "output",
+            help = "The file to write the swap to",
+            parse(from_os_str)
+        )]
+        output: PathBuf,
+
+        #[structopt(long = "encrypt", help = "Encrypt the recovery file with a passphrase")]
+        encrypt: bool,
+    },
+    /// Import a swap from a recovery file, use `resume` afterwards to continue
+    /// the swap
+    Import {
+        #[structopt(help = "The recovery file written by export", parse(from_os_str))]
+        file: PathBuf,
     },
+    /// Manage the seed all keys of the CLI are derived from
+    Seed {
+        #[structopt(
+            long = "restore-from-mnemonic",
+            help = "Restore the seed file from the BIP39 mnemonic shown by show-mnemonic"
+        )]
+        restore_from_mnemonic: bool,
+
+        #[structopt(subcommand)]
+        cmd: Option<SeedCommand>,
+    },
+}
+
+#[derive(structopt::StructOpt, Debug)]
+pub enum SeedCommand {
+    /// Print the seed as BIP39 mnemonic to back it up on paper
+    ShowMnemonic,
+    /// Encrypt the seed file with a passphrase, which will be asked for
+    /// whenever the seed is needed
+    Encrypt,
+    /// Change the passphrase of the encrypted seed file
+    ChangePassphrase,
 }
 
 #[derive(structopt::StructOpt, Debug)]
@@ -361,6 +807,88 @@ pub struct Tor {
         default_value = DEFAULT_TOR_SOCKS5_PORT
     )]
     pub tor_socks5_port: u16,
+
+    #[structopt(
+        long = "tor-only",
+        help = "Route all connections through Tor, including the ones to the Electrum server and the Monero daemon. Fails if Tor is not running."
+    )]
+    pub tor_only: bool,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+pub struct ExternalWallet {
+    #[structopt(
+        long = "external-wallet-descriptor",
+        help = "Fund the swap from an external wallet instead of the internal one. The Bitcoin lock transaction is built from the funds of the given output descriptor and handed out as unsigned PSBT to be signed by the external wallet."
+    )]
+    pub external_wallet_descriptor: Option<String>,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+pub struct Timelocks {
+    #[structopt(
+        long = "cancel-timelock",
+        help = "Propose a cancel timelock in Bitcoin blocks instead of the default of the network, e.g. a shorter window for small amounts. The swap can be cancelled and refunded once the lock transaction has this many confirmations. The quote of the seller shows which timelocks it accepts."
+    )]
+    pub cancel_timelock: Option<u32>,
+
+    #[structopt(
+        long = "punish-timelock",
+        help = "Propose a punish timelock in Bitcoin blocks instead of the default of the network. The seller can punish once the cancel transaction has this many confirmations and the Bitcoin was not refunded."
+    )]
+    pub punish_timelock: Option<u32>,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+pub struct AcceptTerms {
+    #[structopt(
+        short = "y",
+        long = "yes",
+        help = "Accept the terms of the swap without asking once the spot price is known, e.g. for automation."
+    )]
+    pub yes: bool,
+
+    #[structopt(
+        long = "max-slippage",
+        help = "Abort the swap if the spot price buys more than this many percent less XMR than the quoted price, e.g. 1.5"
+    )]
+    pub max_slippage: Option<Decimal>,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+pub struct ExactAmount {
+    #[structopt(
+        long = "btc-amount",
+        help = "Swap exactly this amount of BTC, e.g. 0.01, instead of the whole balance of the wallet. The fees of the lock transaction are paid on top.",
+        parse(try_from_str = parse_btc_amount),
+        conflicts_with = "xmr-amount"
+    )]
+    pub btc_amount: Option<bitcoin::Amount>,
+
+    #[structopt(
+        long = "xmr-amount",
+        help = "Swap the amount of BTC that buys this amount of XMR, e.g. 1.5, at the price of the seller's quote instead of the whole balance of the wallet.",
+        parse(try_from_str = monero::Amount::parse_monero)
+    )]
+    pub xmr_amount: Option<monero::Amount>,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+pub struct SelectUtxos {
+    #[structopt(
+        long = "utxo",
+        help = "Only spend this UTXO of the internal wallet, given as <txid>:<vout>. Can be given multiple times, list-utxos shows the UTXOs of the wallet.",
+        number_of_values = 1,
+        conflicts_with = "exclude-utxo"
+    )]
+    pub utxos: Vec<OutPoint>,
+
+    #[structopt(
+        long = "exclude-utxo",
+        help = "Never spend this UTXO of the internal wallet, given as <txid>:<vout>. Can be given multiple times.",
+        number_of_values = 1
+    )]
+    pub exclude_utxos: Vec<OutPoint>,
 }
 
 #[derive(structopt::StructOpt, Debug)]
@@ -406,6 +934,21 @@ mod data {
     }
 }
 
+fn seed_command(restore_from_mnemonic: bool, cmd: Option<SeedCommand>) -> Result<Command> {
+    let command = match (restore_from_mnemonic, cmd) {
+        (true, None) => Command::RestoreSeed,
+        (false, Some(SeedCommand::ShowMnemonic)) => Command::ShowMnemonic,
+        (false, Some(SeedCommand::Encrypt)) => Command::EncryptSeed,
+        (false, Some(SeedCommand::ChangePassphrase)) => Command::ChangeSeedPassphrase,
+        (true, Some(_)) => {
+            bail!("--restore-from-mnemonic cannot be combined with a seed sub-command")
+        }
+        (false, None) => bail!("Either pass --restore-from-mnemonic or a seed sub-command"),
+    };
+
+    Ok(command)
+}
+
 fn bitcoin_electrum_rpc_url_from(url: Option<Url>, testnet: bool) -> Result<Url> {
     if let Some(url) = url {
         Ok(url)
@@ -436,6 +979,56 @@ fn monero_daemon_address_from(address: Option<String>, testnet: bool) -> String
     }
 }
 
+/// The timelocks to propose, the defaults of the network fill in the one that
+/// is not given. Without any the seller picks the defaults of the network.
+///
+/// Timelocks that expire before a transaction is final plus a margin are
+/// rejected, they leave no time to react to the counterparty.
+fn timelocks_from(
+    cancel_timelock: Option<u32>,
+    punish_timelock: Option<u32>,
+    env_config: env::Config,
+) -> Result<Option<spot_price::Timelocks>> {
+    let min = env_config.bitcoin_finality_confirmations + MIN_TIMELOCK_MARGIN;
+
+    for &(name, timelock) in &[("cancel", cancel_timelock), ("punish", punish_timelock)] {
+        if let Some(timelock) = timelock {
+            if timelock < min {
+                bail!(
+                    "The {} timelock of {} blocks is too short, it has to be at least {} blocks",
+                    name,
+                    timelock,
+                    min
+                )
+            }
+        }
+    }
+
+    if cancel_timelock.is_none() && punish_timelock.is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(spot_price::Timelocks {
+        cancel: cancel_timelock
+            .map(bitcoin::CancelTimelock::new)
+            .unwrap_or(env_config.bitcoin_cancel_timelock),
+        punish: punish_timelock
+            .map(bitcoin::PunishTimelock::new)
+            .unwrap_or(env_config.bitcoin_punish_timelock),
+    }))
+}
+
+fn swap_amount_from(
+    btc_amount: Option<bitcoin::Amount>,
+    xmr_amount: Option<monero::Amount>,
+) -> Option<SwapAmount> {
+    match (btc_amount, xmr_amount) {
+        (Some(btc), _) => Some(SwapAmount::Btc(btc)),
+        (None, Some(xmr)) => Some(SwapAmount::Xmr(xmr)),
+        (None, None) => None,
+    }
+}
+
 fn env_config_from(testnet: bool) -> env::Config {
     if testnet {
         env::Testnet::get_config()
@@ -464,6 +1057,11 @@ fn validate_monero_address(
     Ok(address)
 }
 
+fn parse_btc_amount(s: &str) -> Result<bitcoin::Amount> {
+    bitcoin::Amount::from_str_in(s, ::bitcoin::Denomination::Bitcoin)
+        .with_context(|| format!("Failed to parse {} as an amount of BTC", s))
+}
+
 fn parse_monero_address(s: &str) -> Result<monero::Address> {
     monero::Address::from_str(s).with_context(|| {
         format!(
@@ -483,6 +1081,7 @@ pub struct MoneroAddressNetworkMismatch {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bitcoin::BitcoinAddressNetworkMismatch;
     use crate::tor::DEFAULT_SOCKS5_PORT;
 
     const BINARY_NAME: &str = "swap";
@@ -495,6 +1094,11 @@ mod tests {
     const MUTLI_ADDRESS: &str = "/ip4/127.0.0.1/tcp/9939";
     const PEER_ID: &str = "12D3KooWCdMKjesXMJz1SiZ7HgotrxuqhQJbP5sgBm2BwP1cqThi";
     const SWAP_ID: &str = "ea030832-3be9-454f-bb98-5ea9a788406b";
+    const BITCOIN_MAINNET_ADDRESS: &str = "1KFHE7w8BhaENAswwryaoccDb6qcT6DbYY";
+    const BITCOIN_TESTNET_ADDRESS: &str = "tb1qyccwk4yun26708qg5h6g6we8kxln232wclxf5a";
+    const UTXO: &str = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b:0";
+    const OTHER_UTXO: &str = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b:1";
+    const EXTERNAL_WALLET_DESCRIPTOR: &str = "wpkh(xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)";
 
     #[test]
     fn given_buy_xmr_on_mainnet_then_defaults_to_mainnet() {
@@ -777,12 +1381,571 @@ mod tests {
     }
 
     #[test]
-    fn given_with_debug_then_debug_set() {
-        let raw_ars = vec![
-            BINARY_NAME,
-            "--debug",
-            "buy-xmr",
-            "--receive-address",
+    fn given_balance_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "balance"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::balance_mainnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_balance_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "balance"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::balance_testnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_history_then_table_format() {
+        let raw_ars = vec![BINARY_NAME, "history"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::history_mainnet_defaults(history::Format::Table))
+        );
+    }
+
+    #[test]
+    fn given_history_with_json_then_json_format_and_plain_text_logs() {
+        let raw_ars = vec![BINARY_NAME, "history", "--json"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::history_mainnet_defaults(history::Format::Json))
+        );
+    }
+
+    #[test]
+    fn given_history_with_csv_then_csv_format() {
+        let raw_ars = vec![BINARY_NAME, "history", "--csv"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::history_mainnet_defaults(history::Format::Csv))
+        );
+    }
+
+    #[test]
+    fn given_history_with_swap_id_and_states_then_states_of_swap() {
+        let raw_ars = vec![BINARY_NAME, "history", SWAP_ID, "--states"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected = Arguments::history_mainnet_defaults(history::Format::Table);
+        expected.cmd = Command::History {
+            swap_id: Some(Uuid::from_str(SWAP_ID).unwrap()),
+            states: true,
+            format: history::Format::Table,
+        };
+        assert_eq!(args, ParseResult::Arguments(expected));
+    }
+
+    #[test]
+    fn given_history_with_states_but_without_swap_id_then_fails() {
+        let raw_ars = vec![BINARY_NAME, "history", "--states"];
+
+        let result = parse_args_and_apply_defaults(raw_ars);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn given_database_sqlite_then_sqlite_backend() {
+        let raw_ars = vec![BINARY_NAME, "--database", "sqlite", "history"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected = Arguments::history_mainnet_defaults(history::Format::Table);
+        expected.database = database::Backend::Sqlite;
+        assert_eq!(args, ParseResult::Arguments(expected));
+    }
+
+    #[test]
+    fn given_migrate_database_then_migrate_database_command() {
+        let raw_ars = vec![BINARY_NAME, "migrate-database"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected = Arguments::history_mainnet_defaults(history::Format::Table);
+        expected.cmd = Command::MigrateDatabase;
+        assert_eq!(args, ParseResult::Arguments(expected));
+    }
+
+    #[test]
+    fn given_export_with_encrypt_then_export_command() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "export",
+            "--swap-id",
+            SWAP_ID,
+            "--output",
+            "swap.pem",
+            "--encrypt",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected = Arguments::history_mainnet_defaults(history::Format::Table);
+        expected.cmd = Command::Export {
+            swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+            output: PathBuf::from("swap.pem"),
+            encrypt: true,
+        };
+        assert_eq!(args, ParseResult::Arguments(expected));
+    }
+
+    #[test]
+    fn given_import_then_import_command() {
+        let raw_ars = vec![BINARY_NAME, "import", "swap.pem"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected = Arguments::history_mainnet_defaults(history::Format::Table);
+        expected.cmd = Command::Import {
+            file: PathBuf::from("swap.pem"),
+        };
+        assert_eq!(args, ParseResult::Arguments(expected));
+    }
+
+    #[test]
+    fn given_seed_encrypt_then_encrypt_seed_command() {
+        let raw_ars = vec![BINARY_NAME, "seed", "encrypt"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected = Arguments::history_mainnet_defaults(history::Format::Table);
+        expected.cmd = Command::EncryptSeed;
+        assert_eq!(args, ParseResult::Arguments(expected));
+    }
+
+    #[test]
+    fn given_seed_change_passphrase_then_change_seed_passphrase_command() {
+        let raw_ars = vec![BINARY_NAME, "seed", "change-passphrase"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected = Arguments::history_mainnet_defaults(history::Format::Table);
+        expected.cmd = Command::ChangeSeedPassphrase;
+        assert_eq!(args, ParseResult::Arguments(expected));
+    }
+
+    #[test]
+    fn given_seed_show_mnemonic_then_show_mnemonic_command() {
+        let raw_ars = vec![BINARY_NAME, "seed", "show-mnemonic"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected = Arguments::history_mainnet_defaults(history::Format::Table);
+        expected.cmd = Command::ShowMnemonic;
+        assert_eq!(args, ParseResult::Arguments(expected));
+    }
+
+    #[test]
+    fn given_seed_restore_from_mnemonic_then_restore_seed_command() {
+        let raw_ars = vec![BINARY_NAME, "seed", "--restore-from-mnemonic"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected = Arguments::history_mainnet_defaults(history::Format::Table);
+        expected.cmd = Command::RestoreSeed;
+        assert_eq!(args, ParseResult::Arguments(expected));
+    }
+
+    #[test]
+    fn given_seed_restore_from_mnemonic_with_sub_command_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "seed",
+            "--restore-from-mnemonic",
+            "show-mnemonic",
+        ];
+
+        let result = parse_args_and_apply_defaults(raw_ars);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn given_history_with_json_and_csv_then_fails() {
+        let raw_ars = vec![BINARY_NAME, "history", "--json", "--csv"];
+
+        let result = parse_args_and_apply_defaults(raw_ars);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn given_withdraw_btc_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "withdraw-btc",
+            "--address",
+            BITCOIN_MAINNET_ADDRESS,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::withdraw_btc_mainnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_withdraw_btc_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "withdraw-btc",
+            "--address",
+            BITCOIN_TESTNET_ADDRESS,
+            "--amount",
+            "0.1 BTC",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(
+                Arguments::withdraw_btc_testnet_defaults()
+                    .with_amount(bitcoin::Amount::from_sat(10_000_000))
+            )
+        );
+    }
+
+    #[test]
+    fn given_withdraw_btc_on_testnet_with_mainnet_address_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "withdraw-btc",
+            "--address",
+            BITCOIN_MAINNET_ADDRESS,
+        ];
+
+        let err = parse_args_and_apply_defaults(raw_ars).unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<BitcoinAddressNetworkMismatch>().unwrap(),
+            &BitcoinAddressNetworkMismatch {
+                expected: bitcoin::Network::Testnet,
+                actual: bitcoin::Network::Bitcoin
+            }
+        );
+    }
+
+    #[test]
+    fn given_buy_xmr_with_external_wallet_descriptor_then_descriptor_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--external-wallet-descriptor",
+            EXTERNAL_WALLET_DESCRIPTOR,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(
+                Arguments::buy_xmr_mainnet_defaults()
+                    .with_external_wallet_descriptor(EXTERNAL_WALLET_DESCRIPTOR)
+            )
+        );
+    }
+
+    #[test]
+    fn given_resume_with_tor_only_then_tor_only_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--tor-only",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::resume_mainnet_defaults().with_tor_only())
+        );
+    }
+
+    #[test]
+    fn given_buy_xmr_with_too_short_punish_timelock_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--punish-timelock",
+            "2",
+        ];
+
+        let result = parse_args_and_apply_defaults(raw_ars);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn given_cancel_with_tor_only_then_tor_only_set() {
+        let raw_ars = vec![BINARY_NAME, "cancel", "--swap-id", SWAP_ID, "--tor-only"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::cancel_mainnet_defaults().with_tor_only())
+        );
+    }
+
+    #[test]
+    fn given_withdraw_btc_with_tor_only_then_tor_only_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "withdraw-btc",
+            "--address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--tor-only",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::withdraw_btc_mainnet_defaults().with_tor_only())
+        );
+    }
+
+    #[test]
+    fn given_buy_xmr_with_cancel_timelock_then_default_punish_timelock_proposed() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--cancel-timelock",
+            "12",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::buy_xmr_mainnet_defaults().with_timelocks(
+                spot_price::Timelocks {
+                    cancel: bitcoin::CancelTimelock::new(12),
+                    punish: env::Mainnet::get_config().bitcoin_punish_timelock,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn given_buy_xmr_with_yes_and_max_slippage_then_confirmation_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--yes",
+            "--max-slippage",
+            "1.5",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::buy_xmr_mainnet_defaults().with_confirmation(
+                Confirmation {
+                    yes: true,
+                    max_slippage: Some(Decimal::from_str("1.5").unwrap()),
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn given_buy_xmr_with_xmr_amount_then_amount_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--xmr-amount",
+            "1.5",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(
+                Arguments::buy_xmr_mainnet_defaults()
+                    .with_swap_amount(SwapAmount::Xmr(monero::Amount::from_monero(1.5).unwrap()))
+            )
+        );
+    }
+
+    #[test]
+    fn given_buy_xmr_with_btc_and_xmr_amount_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--btc-amount",
+            "0.01",
+            "--xmr-amount",
+            "1.5",
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_buy_xmr_with_utxo_and_exclude_utxo_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--seller-peer-id",
+            PEER_ID,
+            "--utxo",
+            UTXO,
+            "--exclude-utxo",
+            UTXO,
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_resume_with_exclude_utxo_then_it_is_not_spent() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller-addr",
+            MUTLI_ADDRESS,
+            "--exclude-utxo",
+            UTXO,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::resume_mainnet_defaults().with_coin_control(
+                CoinControl::Exclude(vec![OutPoint::from_str(UTXO).unwrap()])
+            ))
+        );
+    }
+
+    #[test]
+    fn given_withdraw_btc_with_utxos_then_only_those_are_spent() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "withdraw-btc",
+            "--address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--utxo",
+            UTXO,
+            "--utxo",
+            OTHER_UTXO,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(
+                Arguments::withdraw_btc_mainnet_defaults().with_coin_control(CoinControl::Only(
+                    vec![
+                        OutPoint::from_str(UTXO).unwrap(),
+                        OutPoint::from_str(OTHER_UTXO).unwrap(),
+                    ]
+                ))
+            )
+        );
+    }
+
+    #[test]
+    fn xmr_amount_is_converted_at_quoted_price_rounded_up() {
+        let amount = SwapAmount::Xmr(monero::Amount::from_piconero(1));
+        let price = bitcoin::Amount::from_btc(0.005).unwrap();
+
+        assert_eq!(amount.to_btc(price).unwrap(), bitcoin::Amount::from_sat(1));
+
+        let amount = SwapAmount::Xmr(monero::Amount::from_monero(2.0).unwrap());
+
+        assert_eq!(
+            amount.to_btc(price).unwrap(),
+            bitcoin::Amount::from_btc(0.01).unwrap()
+        );
+    }
+
+    #[test]
+    fn given_with_debug_then_debug_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--debug",
+            "buy-xmr",
+            "--receive-address",
             MONERO_MAINNET_ADDRESS,
             "--seller-addr",
             MUTLI_ADDRESS,
@@ -937,6 +2100,7 @@ mod tests {
                 debug: false,
                 json: false,
                 data_dir: data_dir_path_cli().join(TESTNET),
+                database: database::Backend::Sled,
                 cmd: Command::BuyXmr {
                     seller_peer_id: PeerId::from_str(PEER_ID).unwrap(),
                     seller_addr: Multiaddr::from_str(MUTLI_ADDRESS).unwrap(),
@@ -947,6 +2111,15 @@ mod tests {
                         .unwrap(),
                     monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string(),
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    tor_only: false,
+                    external_wallet_descriptor: None,
+                    timelocks: None,
+                    confirmation: Confirmation {
+                        yes: false,
+                        max_slippage: None,
+                    },
+                    amount: None,
+                    coin_control: CoinControl::Any,
                 },
             }
         }
@@ -957,6 +2130,7 @@ mod tests {
                 debug: false,
                 json: false,
                 data_dir: data_dir_path_cli().join(MAINNET),
+                database: database::Backend::Sled,
                 cmd: Command::BuyXmr {
                     seller_peer_id: PeerId::from_str(PEER_ID).unwrap(),
                     seller_addr: Multiaddr::from_str(MUTLI_ADDRESS).unwrap(),
@@ -966,6 +2140,15 @@ mod tests {
                         .unwrap(),
                     monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS.to_string(),
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    tor_only: false,
+                    external_wallet_descriptor: None,
+                    timelocks: None,
+                    confirmation: Confirmation {
+                        yes: false,
+                        max_slippage: None,
+                    },
+                    amount: None,
+                    coin_control: CoinControl::Any,
                 },
             }
         }
@@ -976,6 +2159,7 @@ mod tests {
                 debug: false,
                 json: false,
                 data_dir: data_dir_path_cli().join(TESTNET),
+                database: database::Backend::Sled,
                 cmd: Command::Resume {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
                     seller_addr: Multiaddr::from_str(MUTLI_ADDRESS).unwrap(),
@@ -986,6 +2170,9 @@ mod tests {
                         .unwrap(),
                     monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string(),
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    tor_only: false,
+                    external_wallet_descriptor: None,
+                    coin_control: CoinControl::Any,
                 },
             }
         }
@@ -996,6 +2183,7 @@ mod tests {
                 debug: false,
                 json: false,
                 data_dir: data_dir_path_cli().join(MAINNET),
+                database: database::Backend::Sled,
                 cmd: Command::Resume {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
                     seller_addr: Multiaddr::from_str(MUTLI_ADDRESS).unwrap(),
@@ -1005,6 +2193,9 @@ mod tests {
                         .unwrap(),
                     monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS.to_string(),
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    tor_only: false,
+                    external_wallet_descriptor: None,
+                    coin_control: CoinControl::Any,
                 },
             }
         }
@@ -1015,12 +2206,15 @@ mod tests {
                 debug: false,
                 json: false,
                 data_dir: data_dir_path_cli().join(TESTNET),
+                database: database::Backend::Sled,
                 cmd: Command::Cancel {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
                     force: false,
                     bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
                         .unwrap(),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    tor_only: false,
                 },
             }
         }
@@ -1031,11 +2225,14 @@ mod tests {
                 debug: false,
                 json: false,
                 data_dir: data_dir_path_cli().join(MAINNET),
+                database: database::Backend::Sled,
                 cmd: Command::Cancel {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
                     force: false,
                     bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    tor_only: false,
                 },
             }
         }
@@ -1046,12 +2243,15 @@ mod tests {
                 debug: false,
                 json: false,
                 data_dir: data_dir_path_cli().join(TESTNET),
+                database: database::Backend::Sled,
                 cmd: Command::Refund {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
                     force: false,
                     bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
                         .unwrap(),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    tor_only: false,
                 },
             }
         }
@@ -1062,15 +2262,132 @@ mod tests {
                 debug: false,
                 json: false,
                 data_dir: data_dir_path_cli().join(MAINNET),
+                database: database::Backend::Sled,
                 cmd: Command::Refund {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
                     force: false,
                     bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    tor_only: false,
                 },
             }
         }
 
+        pub fn balance_testnet_defaults() -> Self {
+            Self {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                database: database::Backend::Sled,
+                cmd: Command::Balance {
+                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
+                        .unwrap(),
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    tor_only: false,
+                },
+            }
+        }
+
+        pub fn history_mainnet_defaults(format: history::Format) -> Self {
+            Self {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                database: database::Backend::Sled,
+                cmd: Command::History {
+                    swap_id: None,
+                    states: false,
+                    format,
+                },
+            }
+        }
+
+        pub fn balance_mainnet_defaults() -> Self {
+            Self {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                database: database::Backend::Sled,
+                cmd: Command::Balance {
+                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    tor_only: false,
+                },
+            }
+        }
+
+        pub fn withdraw_btc_testnet_defaults() -> Self {
+            Self {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                database: database::Backend::Sled,
+                cmd: Command::WithdrawBtc {
+                    amount: None,
+                    address: bitcoin::Address::from_str(BITCOIN_TESTNET_ADDRESS).unwrap(),
+                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
+                        .unwrap(),
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    coin_control: CoinControl::Any,
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    tor_only: false,
+                },
+            }
+        }
+
+        pub fn withdraw_btc_mainnet_defaults() -> Self {
+            Self {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                database: database::Backend::Sled,
+                cmd: Command::WithdrawBtc {
+                    amount: None,
+                    address: bitcoin::Address::from_str(BITCOIN_MAINNET_ADDRESS).unwrap(),
+                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    coin_control: CoinControl::Any,
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    tor_only: false,
+                },
+            }
+        }
+
+        pub fn with_amount(mut self, amount: bitcoin::Amount) -> Self {
+            match &mut self.cmd {
+                Command::WithdrawBtc { amount: inner, .. } => *inner = Some(amount),
+                _ => panic!("command does not support an amount"),
+            }
+            self
+        }
+
+        pub fn with_coin_control(mut self, coin_control: CoinControl) -> Self {
+            match &mut self.cmd {
+                Command::BuyXmr {
+                    coin_control: inner,
+                    ..
+                }
+                | Command::Resume {
+                    coin_control: inner,
+                    ..
+                }
+                | Command::WithdrawBtc {
+                    coin_control: inner,
+                    ..
+                } => *inner = coin_control,
+                _ => panic!("command does not support coin control"),
+            }
+            self
+        }
+
         pub fn with_data_dir(mut self, data_dir: PathBuf) -> Self {
             self.data_dir = data_dir;
             self
@@ -1085,6 +2402,59 @@ mod tests {
             self.json = true;
             self
         }
+
+        pub fn with_external_wallet_descriptor(mut self, descriptor: &str) -> Self {
+            match &mut self.cmd {
+                Command::BuyXmr {
+                    external_wallet_descriptor,
+                    ..
+                }
+                | Command::Resume {
+                    external_wallet_descriptor,
+                    ..
+                } => *external_wallet_descriptor = Some(descriptor.to_string()),
+                _ => panic!("command does not support an external wallet"),
+            }
+            self
+        }
+
+        pub fn with_tor_only(mut self) -> Self {
+            match &mut self.cmd {
+                Command::BuyXmr { tor_only, .. }
+                | Command::Resume { tor_only, .. }
+                | Command::Cancel { tor_only, .. }
+                | Command::Refund { tor_only, .. }
+                | Command::Balance { tor_only, .. }
+                | Command::ListUtxos { tor_only, .. }
+                | Command::WithdrawBtc { tor_only, .. } => *tor_only = true,
+                _ => panic!("command does not support Tor"),
+            }
+            self
+        }
+
+        pub fn with_timelocks(mut self, proposed: spot_price::Timelocks) -> Self {
+            match &mut self.cmd {
+                Command::BuyXmr { timelocks, .. } => *timelocks = Some(proposed),
+                _ => panic!("command does not support timelocks"),
+            }
+            self
+        }
+
+        pub fn with_swap_amount(mut self, swap_amount: SwapAmount) -> Self {
+            match &mut self.cmd {
+                Command::BuyXmr { amount, .. } => *amount = Some(swap_amount),
+                _ => panic!("command does not support a swap amount"),
+            }
+            self
+        }
+
+        pub fn with_confirmation(mut self, accept: Confirmation) -> Self {
+            match &mut self.cmd {
+                Command::BuyXmr { confirmation, .. } => *confirmation = accept,
+                _ => panic!("command does not support confirmation"),
+            }
+            self
+        }
     }
 
     fn data_dir_path_cli() -> PathBuf {
@@ -0,0 +1,134 @@
+use crate::protocol::bob::{ConfirmTerms, Terms};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Confirm;
+use rust_decimal::Decimal;
+
+/// How the terms of a swap are confirmed before the Bitcoin is locked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Confirmation {
+    /// Accept the terms without asking.
+    pub yes: bool,
+    /// Abort if the XMR amount is more than this many percent below the
+    /// amount at the quoted price.
+    pub max_slippage: Option<Decimal>,
+}
+
+#[async_trait]
+impl ConfirmTerms for Confirmation {
+    /// Shows the terms and asks the user to accept them, unless `yes` is set.
+    ///
+    /// Fails if the terms exceed the maximum slippage or are not accepted.
+    async fn confirm(&self, terms: Terms) -> Result<()> {
+        let price = terms.price()?;
+        let slippage = terms.slippage()?;
+
+        tracing::info!(
+            btc = %terms.btc,
+            xmr = %terms.xmr,
+            price = %price,
+            quoted_xmr = ?terms.quoted_xmr.map(|xmr| xmr.to_string()),
+            slippage_percent = ?slippage.map(|slippage| slippage.to_string()),
+            lock_fee = %terms.lock_fee,
+            cancel_fee = %terms.cancel_fee,
+            refund_fee = %terms.refund_fee,
+            "Terms of the swap",
+        );
+
+        if let (Some(max_slippage), Some(slippage)) = (self.max_slippage, slippage) {
+            if slippage > max_slippage {
+                bail!(
+                    "The spot price buys {}% less XMR than quoted, more than the maximum slippage of {}%",
+                    slippage,
+                    max_slippage
+                );
+            }
+        }
+
+        if self.yes {
+            return Ok(());
+        }
+
+        let accepted = tokio::task::spawn_blocking(move || {
+            Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!(
+                    "Lock {} to receive {} (1 XMR ~ {})?",
+                    terms.btc, terms.xmr, price
+                ))
+                .default(false)
+                .interact()
+        })
+        .await?
+        .context("Failed to read confirmation")?;
+
+        if !accepted {
+            bail!("The terms of the swap were not accepted");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bitcoin, monero};
+
+    #[test]
+    fn slippage_is_relative_to_the_quoted_amount() {
+        let terms = terms(
+            monero::Amount::from_monero(0.99).unwrap(),
+            Some(monero::Amount::from_monero(1.0).unwrap()),
+        );
+
+        assert_eq!(terms.slippage().unwrap(), Some(Decimal::from(1u64)));
+        assert_eq!(terms.price().unwrap(), bitcoin::Amount::from_sat(1_010_101));
+    }
+
+    #[test]
+    fn more_xmr_than_quoted_is_negative_slippage() {
+        let terms = terms(
+            monero::Amount::from_monero(1.02).unwrap(),
+            Some(monero::Amount::from_monero(1.0).unwrap()),
+        );
+
+        assert_eq!(terms.slippage().unwrap(), Some(Decimal::from(-2i64)));
+    }
+
+    #[tokio::test]
+    async fn given_slippage_above_maximum_then_aborts_without_asking() {
+        let confirmation = Confirmation {
+            yes: false,
+            max_slippage: Some(Decimal::from(1u64)),
+        };
+        let terms = terms(
+            monero::Amount::from_monero(0.98).unwrap(),
+            Some(monero::Amount::from_monero(1.0).unwrap()),
+        );
+
+        assert!(confirmation.confirm(terms).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn given_yes_then_accepts_without_asking() {
+        let confirmation = Confirmation {
+            yes: true,
+            max_slippage: Some(Decimal::from(1u64)),
+        };
+        let terms = terms(monero::Amount::from_monero(1.0).unwrap(), None);
+
+        assert!(confirmation.confirm(terms).await.is_ok());
+    }
+
+    fn terms(xmr: monero::Amount, quoted_xmr: Option<monero::Amount>) -> Terms {
+        Terms {
+            btc: bitcoin::Amount::from_btc(0.01).unwrap(),
+            xmr,
+            quoted_xmr,
+            lock_fee: bitcoin::Amount::from_sat(1_000),
+            cancel_fee: bitcoin::Amount::from_sat(1_000),
+            refund_fee: bitcoin::Amount::from_sat(1_000),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::bitcoin::PartiallySignedTransaction;
+use crate::cli::qr_code;
+use crate::protocol::bob::SignLock;
+use ::bitcoin::consensus::encode::{deserialize, serialize};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Input;
+
+/// Hands out the unsigned Bitcoin lock transaction for signing with an
+/// external wallet and waits for the user to provide the signed PSBT.
+///
+/// The PSBT is exchanged in its base64 encoding, which is understood by most
+/// wallets that support PSBTs.
+#[derive(Debug, Clone, Copy)]
+pub struct PromptSigner {
+    pub render_qr_code: bool,
+}
+
+#[async_trait]
+impl SignLock for PromptSigner {
+    async fn sign(&self, psbt: PartiallySignedTransaction) -> Result<PartiallySignedTransaction> {
+        let unsigned = encode(&psbt);
+
+        if self.render_qr_code {
+            match qr_code(&unsigned) {
+                Ok(qr_code) => eprintln!("{}", qr_code),
+                Err(error) => {
+                    tracing::debug!("PSBT too large to be rendered as QR code: {:#}", error)
+                }
+            }
+        }
+
+        tracing::info!(
+            psbt = %unsigned,
+            "Please sign the Bitcoin lock transaction with your external wallet",
+        );
+
+        let signed = tokio::task::spawn_blocking(|| {
+            Input::<String>::with_theme(&ColorfulTheme::default())
+                .with_prompt("Enter the signed PSBT (base64)")
+                .interact_text()
+        })
+        .await?
+        .context("Failed to read signed PSBT")?;
+
+        decode(&signed)
+    }
+}
+
+pub fn encode(psbt: &PartiallySignedTransaction) -> String {
+    base64::encode(serialize(psbt))
+}
+
+pub fn decode(psbt: &str) -> Result<PartiallySignedTransaction> {
+    let bytes = base64::decode(psbt.trim()).context("PSBT is not valid base64")?;
+    let psbt = deserialize(&bytes).context("Failed to deserialize PSBT")?;
+
+    Ok(psbt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::{PublicKey, TxLock, Wallet};
+
+    #[tokio::test]
+    async fn psbt_survives_encoding_roundtrip() {
+        let wallet = Wallet::new_funded_default_fees(50000);
+        let tx_lock = TxLock::new(
+            &wallet,
+            bitcoin::Amount::from_sat(10000),
+            PublicKey::random(),
+            PublicKey::random(),
+        )
+        .await
+        .unwrap();
+        let psbt = PartiallySignedTransaction::from(tx_lock);
+
+        let decoded = decode(&encode(&psbt)).unwrap();
+
+        assert_eq!(decoded, psbt);
+    }
+}
@@ -8,7 +8,7 @@ use tracing_subscriber::layer::{Context, SubscriberExt};
 use tracing_subscriber::{fmt, EnvFilter, FmtSubscriber, Layer, Registry};
 use uuid::Uuid;
 
-pub fn init(debug: bool, json: bool, dir: impl AsRef<Path>, swap_id: Uuid) -> Result<()> {
+pub fn init(debug: bool, json: bool, dir: impl AsRef<Path>, swap_id: Option<Uuid>) -> Result<()> {
     if json {
         let level = if debug { Level::DEBUG } else { Level::INFO };
 
@@ -29,7 +29,11 @@ pub fn init(debug: bool, json: bool, dir: impl AsRef<Path>, swap_id: Uuid) -> Re
 
         let registry = Registry::default().with(level_filter);
 
-        let appender = tracing_appender::rolling::never(dir, format!("swap-{}.log", swap_id));
+        let file_name = match swap_id {
+            Some(swap_id) => format!("swap-{}.log", swap_id),
+            None => "swap-all.log".to_string(),
+        };
+        let appender = tracing_appender::rolling::never(dir, file_name);
         let (appender, guard) = tracing_appender::non_blocking(appender);
 
         std::mem::forget(guard);
@@ -30,3 +30,16 @@ pub fn new(
 
     authenticate_and_multiplex(transport, identity)
 }
+
+/// Creates the libp2p transport for the swap CLI that dials every address
+/// through the Tor socks5 port.
+///
+/// Unlike [`new`] there is no fallback to regular TCP connections.
+pub fn tor_only(
+    identity: &identity::Keypair,
+    tor_socks5_port: u16,
+) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
+    let transport = TorDialOnlyTransport::new(tor_socks5_port).boxed();
+
+    authenticate_and_multiplex(transport, identity)
+}
@@ -1,18 +1,38 @@
-pub use alice::Alice;
+pub use alice::{Alice, AliceEndState};
 pub use bob::Bob;
-
+pub use details::{Role, SwapDetails};
+pub use export::SwapExport;
+pub use schema::UnsupportedSchemaVersion;
+pub use sqlite::SqliteStorage;
+pub use state_log::LoggedState;
+
+use crate::bitcoin::UtxoLabel;
+use crate::database::schema::VersionedSwap;
+use crate::monero::TransferProof;
+use ::bitcoin::Script;
 use anyhow::{anyhow, bail, Context, Result};
-use itertools::Itertools;
+use async_trait::async_trait;
+use libp2p::identity::ed25519;
 use libp2p::PeerId;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use sled::transaction::{ConflictableTransactionError, TransactionError};
+use sled::Transactional;
 use std::fmt::Display;
-use std::path::Path;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 mod alice;
 mod bob;
+mod details;
+mod export;
+mod schema;
+mod sqlite;
+mod state_log;
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub enum Swap {
@@ -65,12 +85,176 @@ impl Swap {
     }
 }
 
+/// The storage of swaps, implemented by each database backend.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn insert_peer_id(&self, swap_id: Uuid, peer_id: PeerId) -> Result<()>;
+
+    fn get_peer_id(&self, swap_id: Uuid) -> Result<PeerId>;
+
+    /// Saves the libp2p identity a swap has to be resumed with.
+    ///
+    /// Swaps of Bob that were started before identities were saved use the
+    /// identity derived from the seed, swaps of Alice use the identity of the
+    /// ASB.
+    async fn insert_identity(&self, swap_id: Uuid, identity: Identity) -> Result<()>;
+
+    fn get_identity(&self, swap_id: Uuid) -> Result<Option<Identity>>;
+
+    /// Saves the latest state of a swap.
+    ///
+    /// The state is appended to the state log of the swap and the details of
+    /// the swap are updated with it.
+    async fn insert_latest_state(&self, swap_id: Uuid, state: Swap) -> Result<()>;
+
+    /// Returns the latest state of a swap.
+    fn get_state(&self, swap_id: Uuid) -> Result<Swap>;
+
+    /// Returns all states a swap went through, oldest first.
+    ///
+    /// Swaps that were saved before states were logged only have the states
+    /// that were saved since.
+    fn get_state_log(&self, swap_id: Uuid) -> Result<Vec<LoggedState>>;
+
+    /// Returns the details recorded for a swap.
+    ///
+    /// Swaps that were saved before details were recorded have empty details.
+    fn get_details(&self, swap_id: Uuid) -> Result<SwapDetails>;
+
+    /// Returns the latest state of all swaps.
+    fn all_swaps(&self) -> Result<Vec<(Uuid, Swap)>>;
+
+    /// Returns the next unused derivation index of the descriptor and marks it
+    /// as used.
+    async fn next_derivation_index(&self, descriptor: &str) -> Result<u32>;
+
+    /// Returns the next unused derivation index of every descriptor that
+    /// addresses were derived from.
+    fn derivation_indexes(&self) -> Result<Vec<(String, u32)>>;
+
+    /// Saves the refill of the Monero wallet that was sent but is not
+    /// confirmed yet, `None` once it is confirmed.
+    async fn insert_pending_monero_refill(&self, refill: Option<TransferProof>) -> Result<()>;
+
+    fn get_pending_monero_refill(&self) -> Result<Option<TransferProof>>;
+
+    /// Labels the UTXOs received on the given script with their origin.
+    async fn insert_utxo_label(&self, script: Script, label: UtxoLabel) -> Result<()>;
+
+    /// Returns the label of every script that was labelled.
+    fn utxo_labels(&self) -> Result<Vec<(Script, UtxoLabel)>>;
+
+    fn all_alice(&self) -> Result<Vec<(Uuid, Alice)>> {
+        self.all_swaps()?
+            .into_iter()
+            .map(|(swap_id, swap)| Ok((swap_id, swap.try_into_alice()?)))
+            .collect()
+    }
+
+    fn all_bob(&self) -> Result<Vec<(Uuid, Bob)>> {
+        self.all_swaps()?
+            .into_iter()
+            .map(|(swap_id, swap)| Ok((swap_id, swap.try_into_bob()?)))
+            .collect()
+    }
+
+    fn unfinished_alice(&self) -> Result<Vec<(Uuid, Alice)>> {
+        Ok(self
+            .all_alice()?
+            .into_iter()
+            .filter(|(_swap_id, alice)| !matches!(alice, Alice::Done(_)))
+            .collect())
+    }
+}
+
+/// The backends the database can be stored in.
+#[derive(
+    Clone, Copy, Debug, Deserialize, Serialize, PartialEq, strum::Display, strum::EnumString,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Backend {
+    Sled,
+    Sqlite,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Backend::Sled
+    }
+}
+
+impl Backend {
+    /// The location of the database of this backend in the data directory.
+    pub fn path(self, data_dir: &Path) -> PathBuf {
+        match self {
+            Backend::Sled => data_dir.join("database"),
+            Backend::Sqlite => data_dir.join("database.sqlite"),
+        }
+    }
+}
+
+/// The database of the CLI or the ASB, stored in one of the [`Backend`]s.
 pub struct Database {
+    storage: Arc<dyn Storage>,
+}
+
+impl Database {
+    /// Opens the sled database at the given path.
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self::new(SledStorage::open(path)?))
+    }
+
+    /// Opens the database of the given backend in the data directory.
+    pub fn open_in(data_dir: &Path, backend: Backend) -> Result<Self> {
+        let path = backend.path(data_dir);
+
+        match backend {
+            Backend::Sled => Ok(Self::new(SledStorage::open(&path)?)),
+            Backend::Sqlite => Ok(Self::new(SqliteStorage::open(&path)?)),
+        }
+    }
+
+    pub fn new(storage: impl Storage + 'static) -> Self {
+        Self {
+            storage: Arc::new(storage),
+        }
+    }
+}
+
+impl Deref for Database {
+    type Target = dyn Storage;
+
+    fn deref(&self) -> &Self::Target {
+        self.storage.as_ref()
+    }
+}
+
+/// Copies the swaps of a sled database into the SQLite database in the data
+/// directory.
+///
+/// Returns the number of copied swaps.
+pub fn migrate_sled_to_sqlite(from: &dyn Storage, data_dir: &Path) -> Result<usize> {
+    let path = Backend::Sqlite.path(data_dir);
+    let sqlite = SqliteStorage::open(&path)?;
+
+    sqlite
+        .import(from)
+        .with_context(|| format!("Failed to copy swaps into {}", path.display()))
+}
+
+pub struct SledStorage {
     swaps: sled::Tree,
     peers: sled::Tree,
+    details: sled::Tree,
+    metadata: sled::Tree,
+    states: sled::Tree,
+    identities: sled::Tree,
+    derivation_indexes: sled::Tree,
+    utxo_labels: sled::Tree,
 }
 
-impl Database {
+impl SledStorage {
     pub fn open(path: &Path) -> Result<Self> {
         tracing::debug!("Opening database at {}", path.display());
 
@@ -79,11 +263,47 @@ impl Database {
 
         let swaps = db.open_tree("swaps")?;
         let peers = db.open_tree("peers")?;
+        let details = db.open_tree("details")?;
+        let metadata = db.open_tree("metadata")?;
+        let states = db.open_tree("states")?;
+        let identities = db.open_tree("identities")?;
+        let derivation_indexes = db.open_tree("derivation_indexes")?;
+        let utxo_labels = db.open_tree("utxo_labels")?;
+
+        schema::migrate(&swaps, &metadata)?;
+
+        Ok(SledStorage {
+            swaps,
+            peers,
+            details,
+            metadata,
+            states,
+            identities,
+            derivation_indexes,
+            utxo_labels,
+        })
+    }
 
-        Ok(Database { swaps, peers })
+    fn append_to_state_log(&self, swap_key: &[u8], logged: LoggedState) -> Result<()> {
+        let index = match self.states.scan_prefix(swap_key).next_back() {
+            Some(last) => {
+                let (last_key, _) = last.context("Failed to retrieve state log entry from DB")?;
+                state_log::index(swap_key, &last_key)? + 1
+            }
+            None => 0,
+        };
+
+        let entry = serialize(&state_log::Entry::from(logged))
+            .context("Could not serialize state log entry")?;
+        self.states.insert(state_log::key(swap_key, index), entry)?;
+
+        Ok(())
     }
+}
 
-    pub async fn insert_peer_id(&self, swap_id: Uuid, peer_id: PeerId) -> Result<()> {
+#[async_trait]
+impl Storage for SledStorage {
+    async fn insert_peer_id(&self, swap_id: Uuid, peer_id: PeerId) -> Result<()> {
         let peer_id_str = peer_id.to_string();
 
         let key = serialize(&swap_id)?;
@@ -98,7 +318,7 @@ impl Database {
             .context("Could not flush db")
     }
 
-    pub fn get_peer_id(&self, swap_id: Uuid) -> Result<PeerId> {
+    fn get_peer_id(&self, swap_id: Uuid) -> Result<PeerId> {
         let key = serialize(&swap_id)?;
 
         let encoded = self
@@ -110,16 +330,64 @@ impl Database {
         Ok(PeerId::from_str(peer_id.as_str())?)
     }
 
-    pub async fn insert_latest_state(&self, swap_id: Uuid, state: Swap) -> Result<()> {
+    async fn insert_identity(&self, swap_id: Uuid, identity: Identity) -> Result<()> {
         let key = serialize(&swap_id)?;
-        let new_value = serialize(&state).context("Could not serialize new state value")?;
 
-        let old_value = self.swaps.get(&key)?;
+        self.identities.insert(key, identity.encode())?;
 
-        self.swaps
-            .compare_and_swap(key, old_value, Some(new_value))
-            .context("Could not write in the DB")?
-            .context("Stored swap somehow changed, aborting saving")?;
+        self.identities
+            .flush_async()
+            .await
+            .map(|_| ())
+            .context("Could not flush db")
+    }
+
+    fn get_identity(&self, swap_id: Uuid) -> Result<Option<Identity>> {
+        let key = serialize(&swap_id)?;
+
+        self.identities
+            .get(&key)?
+            .map(|encoded| Identity::decode(&encoded))
+            .transpose()
+    }
+
+    async fn insert_latest_state(&self, swap_id: Uuid, state: Swap) -> Result<()> {
+        let key = serialize(&swap_id)?;
+        let new_value = serialize(&VersionedSwap::from(state.clone()))
+            .context("Could not serialize new state value")?;
+
+        let old_value = self.swaps.get(&key)?;
+        let old_state = old_value
+            .as_ref()
+            .and_then(|old_value| deserialize::<VersionedSwap>(old_value).ok())
+            .map(Swap::from);
+
+        let timestamp = OffsetDateTime::now_utc().unix_timestamp();
+
+        let mut details = self.get_details(swap_id)?;
+        details.record(old_state.as_ref(), &state, timestamp);
+        let details = serialize(&details).context("Could not serialize swap details")?;
+
+        (&self.swaps, &self.details)
+            .transaction(|(swaps, swap_details)| {
+                if swaps.get(&key)? != old_value {
+                    return Err(ConflictableTransactionError::Abort(()));
+                }
+                swaps.insert(key.as_slice(), new_value.as_slice())?;
+                swap_details.insert(key.as_slice(), details.as_slice())?;
+
+                Ok(())
+            })
+            .map_err(|error| match error {
+                TransactionError::Abort(()) => {
+                    anyhow!("Stored swap somehow changed, aborting saving")
+                }
+                TransactionError::Storage(error) => {
+                    anyhow::Error::new(error).context("Could not write in the DB")
+                }
+            })?;
+
+        self.append_to_state_log(&key, LoggedState { timestamp, state })?;
 
         self.swaps
             .flush_async()
@@ -128,7 +396,7 @@ impl Database {
             .context("Could not flush db")
     }
 
-    pub fn get_state(&self, swap_id: Uuid) -> Result<Swap> {
+    fn get_state(&self, swap_id: Uuid) -> Result<Swap> {
         let key = serialize(&swap_id)?;
 
         let encoded = self
@@ -136,50 +404,174 @@ impl Database {
             .get(&key)?
             .ok_or_else(|| anyhow!("Swap with id {} not found in database", swap_id))?;
 
-        let state = deserialize(&encoded).context("Could not deserialize state")?;
-        Ok(state)
+        let state =
+            deserialize::<VersionedSwap>(&encoded).context("Could not deserialize state")?;
+        Ok(state.into())
     }
 
-    pub fn all_alice(&self) -> Result<Vec<(Uuid, Alice)>> {
-        self.all_alice_iter().collect()
+    fn get_state_log(&self, swap_id: Uuid) -> Result<Vec<LoggedState>> {
+        let key = serialize(&swap_id)?;
+
+        self.states
+            .scan_prefix(&key)
+            .map(|item| {
+                let (_, value) = item.context("Failed to retrieve state log entry from DB")?;
+                let entry = deserialize::<state_log::Entry>(&value)
+                    .context("Failed to deserialize state log entry")?;
+
+                Ok(entry.into())
+            })
+            .collect()
     }
 
-    fn all_alice_iter(&self) -> impl Iterator<Item = Result<(Uuid, Alice)>> {
-        self.all_swaps_iter().map(|item| {
-            let (swap_id, swap) = item?;
-            Ok((swap_id, swap.try_into_alice()?))
-        })
+    fn get_details(&self, swap_id: Uuid) -> Result<SwapDetails> {
+        let key = serialize(&swap_id)?;
+
+        let details = match self.details.get(&key)? {
+            Some(encoded) => deserialize(&encoded).context("Could not deserialize swap details")?,
+            None => SwapDetails::default(),
+        };
+
+        Ok(details)
+    }
+
+    fn all_swaps(&self) -> Result<Vec<(Uuid, Swap)>> {
+        self.swaps
+            .iter()
+            .map(|item| {
+                let (key, value) = item.context("Failed to retrieve swap from DB")?;
+
+                let swap_id = deserialize::<Uuid>(&key)?;
+                let swap =
+                    deserialize::<VersionedSwap>(&value).context("Failed to deserialize swap")?;
+
+                Ok((swap_id, swap.into()))
+            })
+            .collect()
     }
 
-    pub fn all_bob(&self) -> Result<Vec<(Uuid, Bob)>> {
-        self.all_bob_iter().collect()
+    async fn next_derivation_index(&self, descriptor: &str) -> Result<u32> {
+        let used = self
+            .derivation_indexes
+            .fetch_and_update(descriptor, |next| {
+                let next = next.map(decode_derivation_index).unwrap_or(0);
+                Some(next.saturating_add(1).to_be_bytes().to_vec())
+            })
+            .context("Could not write in the DB")?;
+
+        self.derivation_indexes
+            .flush_async()
+            .await
+            .context("Could not flush db")?;
+
+        Ok(used.as_deref().map(decode_derivation_index).unwrap_or(0))
     }
 
-    fn all_bob_iter(&self) -> impl Iterator<Item = Result<(Uuid, Bob)>> {
-        self.all_swaps_iter().map(|item| {
-            let (swap_id, swap) = item?;
-            Ok((swap_id, swap.try_into_bob()?))
-        })
+    fn derivation_indexes(&self) -> Result<Vec<(String, u32)>> {
+        self.derivation_indexes
+            .iter()
+            .map(|item| {
+                let (key, value) = item.context("Failed to retrieve derivation index from DB")?;
+                let descriptor =
+                    String::from_utf8(key.to_vec()).context("Descriptor is not valid UTF-8")?;
+
+                Ok((descriptor, decode_derivation_index(&value)))
+            })
+            .collect()
     }
 
-    fn all_swaps_iter(&self) -> impl Iterator<Item = Result<(Uuid, Swap)>> {
-        self.swaps.iter().map(|item| {
-            let (key, value) = item.context("Failed to retrieve swap from DB")?;
+    async fn insert_pending_monero_refill(&self, refill: Option<TransferProof>) -> Result<()> {
+        match refill {
+            Some(refill) => {
+                let value = serialize(&refill).context("Could not serialize Monero refill")?;
+                self.metadata.insert(PENDING_MONERO_REFILL_KEY, value)?;
+            }
+            None => {
+                self.metadata.remove(PENDING_MONERO_REFILL_KEY)?;
+            }
+        }
 
-            let swap_id = deserialize::<Uuid>(&key)?;
-            let swap = deserialize::<Swap>(&value).context("Failed to deserialize swap")?;
+        self.metadata
+            .flush_async()
+            .await
+            .map(|_| ())
+            .context("Could not flush db")
+    }
 
-            Ok((swap_id, swap))
-        })
+    fn get_pending_monero_refill(&self) -> Result<Option<TransferProof>> {
+        self.metadata
+            .get(PENDING_MONERO_REFILL_KEY)?
+            .map(|encoded| deserialize(&encoded).context("Could not deserialize Monero refill"))
+            .transpose()
+    }
+
+    async fn insert_utxo_label(&self, script: Script, label: UtxoLabel) -> Result<()> {
+        let value = serialize(&label).context("Could not serialize UTXO label")?;
+
+        self.utxo_labels.insert(script.as_bytes(), value)?;
+
+        self.utxo_labels
+            .flush_async()
+            .await
+            .map(|_| ())
+            .context("Could not flush db")
     }
 
-    pub fn unfinished_alice(&self) -> Result<Vec<(Uuid, Alice)>> {
-        self.all_alice_iter()
-            .filter_ok(|(_swap_id, alice)| !matches!(alice, Alice::Done(_)))
+    fn utxo_labels(&self) -> Result<Vec<(Script, UtxoLabel)>> {
+        self.utxo_labels
+            .iter()
+            .map(|item| {
+                let (key, value) = item.context("Failed to retrieve UTXO label from DB")?;
+                let label = deserialize(&value).context("Could not deserialize UTXO label")?;
+
+                Ok((Script::from(key.to_vec()), label))
+            })
             .collect()
     }
 }
 
+const PENDING_MONERO_REFILL_KEY: &str = "pending_monero_refill";
+
+fn decode_derivation_index(encoded: &[u8]) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&encoded[..4]);
+
+    u32::from_be_bytes(bytes)
+}
+
+/// The libp2p identity a swap of Bob is resumed with.
+#[derive(Clone, Debug)]
+pub enum Identity {
+    /// The identity derived from the seed for this swap. Only this marker is
+    /// stored, the keypair is derived from the seed again to resume the swap.
+    DerivedForSwap,
+    /// An identity that cannot be derived from the seed, like the one of a
+    /// swap imported from another machine.
+    Keypair(ed25519::Keypair),
+}
+
+impl Identity {
+    /// The marker is stored as empty value, a keypair in its libp2p encoding.
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            Identity::DerivedForSwap => Vec::new(),
+            Identity::Keypair(keypair) => keypair.encode().to_vec(),
+        }
+    }
+
+    fn decode(encoded: &[u8]) -> Result<Self> {
+        if encoded.is_empty() {
+            return Ok(Identity::DerivedForSwap);
+        }
+
+        decode_keypair(encoded).map(Identity::Keypair)
+    }
+}
+
+fn decode_keypair(encoded: &[u8]) -> Result<ed25519::Keypair> {
+    ed25519::Keypair::decode(&mut encoded.to_vec()).context("Could not decode identity")
+}
+
 pub fn serialize<T>(t: &T) -> Result<Vec<u8>>
 where
     T: Serialize,
@@ -334,6 +726,43 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn only_swaps_with_saved_identity_have_an_identity() -> Result<()> {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(db_dir.path()).unwrap();
+
+        let swap_id = Uuid::new_v4();
+        let other_swap_id = Uuid::new_v4();
+        let identity = Identity::Keypair(ed25519::Keypair::generate());
+
+        db.insert_identity(swap_id, identity.clone()).await?;
+
+        assert_eq!(
+            db.get_identity(swap_id)?.map(|identity| identity.encode()),
+            Some(identity.encode())
+        );
+        assert!(db.get_identity(other_swap_id)?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn identity_derived_for_swap_is_stored_as_marker() -> Result<()> {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(db_dir.path()).unwrap();
+        let swap_id = Uuid::new_v4();
+
+        db.insert_identity(swap_id, Identity::DerivedForSwap)
+            .await?;
+
+        assert!(matches!(
+            db.get_identity(swap_id)?,
+            Some(Identity::DerivedForSwap)
+        ));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_reopen_db() -> Result<()> {
         let db_dir = tempfile::tempdir().unwrap();
@@ -359,4 +788,60 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn saving_states_records_swap_details() -> Result<()> {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(db_dir.path()).unwrap();
+
+        let swap_id = Uuid::new_v4();
+        let btc_amount = ::bitcoin::Amount::from_sat(100_000);
+
+        assert_eq!(db.get_details(swap_id)?, SwapDetails::default());
+
+        db.insert_latest_state(swap_id, Swap::Bob(Bob::Started { btc_amount }))
+            .await?;
+        let details = db.get_details(swap_id)?;
+        assert_eq!(details.btc_amount, Some(btc_amount));
+        assert!(details.started_at.is_some());
+        assert!(details.finished_at.is_none());
+
+        db.insert_latest_state(swap_id, Swap::Bob(Bob::Done(BobEndState::SafelyAborted)))
+            .await?;
+        let details = db.get_details(swap_id)?;
+        assert_eq!(details.btc_amount, Some(btc_amount));
+        assert!(details.finished_at.is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn saving_states_appends_them_to_the_state_log() -> Result<()> {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(db_dir.path()).unwrap();
+
+        let swap_id = Uuid::new_v4();
+        let other_swap_id = Uuid::new_v4();
+        let started = Swap::Bob(Bob::Started {
+            btc_amount: ::bitcoin::Amount::from_sat(100_000),
+        });
+        let done = Swap::Bob(Bob::Done(BobEndState::SafelyAborted));
+
+        db.insert_latest_state(swap_id, started.clone()).await?;
+        db.insert_latest_state(other_swap_id, started.clone())
+            .await?;
+        db.insert_latest_state(swap_id, done.clone()).await?;
+
+        let states = db
+            .get_state_log(swap_id)?
+            .into_iter()
+            .map(|logged| logged.state)
+            .collect::<Vec<_>>();
+
+        assert_eq!(states, vec![started, done.clone()]);
+        assert_eq!(db.get_state_log(other_swap_id)?.len(), 1);
+        assert_eq!(db.get_state(swap_id)?, done);
+
+        Ok(())
+    }
 }
@@ -78,6 +78,66 @@ pub enum AliceEndState {
     BtcPunished,
 }
 
+impl Alice {
+    pub fn state3(&self) -> Option<&alice::State3> {
+        match self {
+            Alice::Started { state3 }
+            | Alice::BtcLockTransactionSeen { state3 }
+            | Alice::BtcLocked { state3 }
+            | Alice::XmrLockTransactionSent { state3, .. }
+            | Alice::XmrLocked { state3, .. }
+            | Alice::XmrLockTransferProofSent { state3, .. }
+            | Alice::EncSigLearned { state3, .. }
+            | Alice::BtcRedeemTransactionPublished { state3 }
+            | Alice::CancelTimelockExpired { state3, .. }
+            | Alice::BtcCancelled { state3, .. }
+            | Alice::BtcPunishable { state3, .. }
+            | Alice::BtcRefunded { state3, .. } => Some(state3),
+            Alice::Done(_) => None,
+        }
+    }
+
+    pub fn transfer_proof(&self) -> Option<&TransferProof> {
+        match self {
+            Alice::XmrLockTransactionSent { transfer_proof, .. }
+            | Alice::XmrLocked { transfer_proof, .. }
+            | Alice::XmrLockTransferProofSent { transfer_proof, .. }
+            | Alice::EncSigLearned { transfer_proof, .. }
+            | Alice::CancelTimelockExpired { transfer_proof, .. }
+            | Alice::BtcCancelled { transfer_proof, .. }
+            | Alice::BtcPunishable { transfer_proof, .. }
+            | Alice::BtcRefunded { transfer_proof, .. } => Some(transfer_proof),
+            Alice::Started { .. }
+            | Alice::BtcLockTransactionSeen { .. }
+            | Alice::BtcLocked { .. }
+            | Alice::BtcRedeemTransactionPublished { .. }
+            | Alice::Done(_) => None,
+        }
+    }
+
+    /// Whether the swap still needs messages from Bob.
+    ///
+    /// Bob only reaches the ASB under the peer id the swap was started with,
+    /// the remaining states only need the blockchains.
+    pub fn needs_bob(&self) -> bool {
+        match self {
+            Alice::Started { .. }
+            | Alice::BtcLockTransactionSeen { .. }
+            | Alice::BtcLocked { .. }
+            | Alice::XmrLockTransactionSent { .. }
+            | Alice::XmrLocked { .. }
+            | Alice::XmrLockTransferProofSent { .. } => true,
+            Alice::EncSigLearned { .. }
+            | Alice::BtcRedeemTransactionPublished { .. }
+            | Alice::CancelTimelockExpired { .. }
+            | Alice::BtcCancelled { .. }
+            | Alice::BtcPunishable { .. }
+            | Alice::BtcRefunded { .. }
+            | Alice::Done(_) => false,
+        }
+    }
+}
+
 impl From<&AliceState> for Alice {
     fn from(alice_state: &AliceState) -> Self {
         match alice_state {
@@ -308,3 +368,21 @@ impl fmt::Display for Alice {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::execution_setup;
+
+    #[tokio::test]
+    async fn only_swaps_before_the_encrypted_signature_need_bob() {
+        let (state3, _) = execution_setup().await;
+
+        assert!(Alice::BtcLocked {
+            state3: state3.clone()
+        }
+        .needs_bob());
+        assert!(!Alice::BtcRedeemTransactionPublished { state3 }.needs_bob());
+        assert!(!Alice::Done(AliceEndState::BtcPunished).needs_bob());
+    }
+}
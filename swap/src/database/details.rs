@@ -0,0 +1,389 @@
+use crate::bitcoin::{Amount, TxCancel, TxLock, TxPunish, TxRedeem, TxRefund, Txid};
+use crate::database::alice::{Alice, AliceEndState};
+use crate::database::bob::{Bob, BobEndState};
+use crate::database::Swap;
+use crate::monero;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// Details of a swap that are collected while the swap progresses.
+///
+/// The swap states only carry what is needed to continue a swap, most of it
+/// is dropped once a swap is finished. Every time a state is saved the
+/// information it carries is merged into these details, which are stored
+/// alongside the states. Swaps that were started before the details were
+/// recorded simply have no details.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub enum Role {
+    Alice,
+    Bob,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct SwapDetails {
+    /// The role the swap was recorded in.
+    pub role: Option<Role>,
+    /// Unix timestamp of the first state that was saved.
+    pub started_at: Option<i64>,
+    /// Unix timestamp of the moment the swap reached an end state.
+    pub finished_at: Option<i64>,
+    /// Unix timestamp of the moment Bob finished the execution setup, Alice
+    /// only waits a limited time for the lock transaction after it.
+    pub execution_setup_done_at: Option<i64>,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat::opt")]
+    pub btc_amount: Option<Amount>,
+    pub xmr_amount: Option<monero::Amount>,
+    pub tx_lock_id: Option<Txid>,
+    pub tx_redeem_id: Option<Txid>,
+    pub tx_cancel_id: Option<Txid>,
+    pub tx_refund_id: Option<Txid>,
+    pub tx_punish_id: Option<Txid>,
+    pub xmr_lock_tx_hash: Option<String>,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat::opt")]
+    pub tx_lock_fee: Option<Amount>,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat::opt")]
+    pub tx_redeem_fee: Option<Amount>,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat::opt")]
+    pub tx_cancel_fee: Option<Amount>,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat::opt")]
+    pub tx_refund_fee: Option<Amount>,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat::opt")]
+    pub tx_punish_fee: Option<Amount>,
+}
+
+impl SwapDetails {
+    /// Merges the information of a newly saved state into the details.
+    ///
+    /// The previous state is needed because some end states do not carry
+    /// the data required to compute the transaction that led to them.
+    pub fn record(&mut self, previous: Option<&Swap>, current: &Swap, now: i64) {
+        self.started_at.get_or_insert(now);
+
+        match current {
+            Swap::Alice(current) => {
+                self.role = Some(Role::Alice);
+                let previous = previous.and_then(|previous| match previous {
+                    Swap::Alice(previous) => Some(previous),
+                    Swap::Bob(_) => None,
+                });
+                self.record_alice(previous, current);
+
+                if let Alice::Done(_) = current {
+                    self.finished_at.get_or_insert(now);
+                }
+            }
+            Swap::Bob(current) => {
+                self.role = Some(Role::Bob);
+                let previous = previous.and_then(|previous| match previous {
+                    Swap::Bob(previous) => Some(previous),
+                    Swap::Alice(_) => None,
+                });
+                self.record_bob(previous, current);
+
+                if let Bob::ExecutionSetupDone { .. } = current {
+                    self.execution_setup_done_at.get_or_insert(now);
+                }
+                if let Bob::Done(_) = current {
+                    self.finished_at.get_or_insert(now);
+                }
+            }
+        }
+    }
+
+    /// The price paid for one XMR.
+    pub fn price(&self) -> Option<Amount> {
+        let btc = u128::from(self.btc_amount?.as_sat());
+        let xmr = u128::from(self.xmr_amount?.as_piconero());
+
+        if xmr == 0 {
+            return None;
+        }
+
+        let sats_per_xmr = btc * u128::from(monero::Amount::ONE_XMR.as_piconero()) / xmr;
+
+        Some(Amount::from_sat(u64::try_from(sats_per_xmr).ok()?))
+    }
+
+    /// The sum of the fees of the recorded Bitcoin transactions paid by the
+    /// role the swap was recorded in.
+    ///
+    /// Bob pays for the lock and refund transactions, Alice for the redeem and
+    /// punish transactions. The fee of the cancel transaction is paid by
+    /// whoever ends up with its output, which is Alice if Bob was punished and
+    /// Bob otherwise.
+    pub fn bitcoin_fees(&self) -> Option<Amount> {
+        let punished = self.tx_punish_id.is_some();
+        let fees = match self.role? {
+            Role::Alice => [
+                self.tx_redeem_fee,
+                self.tx_punish_fee,
+                self.tx_cancel_fee.filter(|_| punished),
+            ],
+            Role::Bob => [
+                self.tx_lock_fee,
+                self.tx_refund_fee,
+                self.tx_cancel_fee.filter(|_| !punished),
+            ],
+        };
+
+        fees.iter()
+            .flatten()
+            .fold(None, |sum, fee| Some(sum.unwrap_or(Amount::ZERO) + *fee))
+    }
+
+    fn record_alice(&mut self, previous: Option<&Alice>, current: &Alice) {
+        if let Some(state3) = current.state3() {
+            self.btc_amount = Some(state3.btc());
+            self.xmr_amount = Some(state3.xmr());
+            self.tx_lock_id = Some(state3.tx_lock.txid());
+
+            // Before the lock transaction was seen it may never be published
+            if !matches!(current, Alice::Started { .. }) {
+                if let Some(fee) = state3.tx_lock.fee() {
+                    self.tx_lock_fee = Some(fee);
+                }
+            }
+        }
+
+        if let Some(transfer_proof) = current.transfer_proof() {
+            self.xmr_lock_tx_hash = Some(transfer_proof.tx_hash().0);
+        }
+
+        match current {
+            Alice::BtcRedeemTransactionPublished { state3 } => {
+                self.record_redeem(&state3.tx_redeem());
+            }
+            Alice::BtcCancelled { state3, .. } | Alice::BtcPunishable { state3, .. } => {
+                self.record_cancel(&state3.tx_cancel());
+            }
+            Alice::BtcRefunded { state3, .. } => {
+                let tx_cancel = state3.tx_cancel();
+                self.record_cancel(&tx_cancel);
+                self.record_refund(&tx_cancel, &state3.tx_refund());
+            }
+            Alice::Done(AliceEndState::BtcRedeemed) => {
+                if let Some(state3) = previous.and_then(Alice::state3) {
+                    self.record_redeem(&state3.tx_redeem());
+                }
+            }
+            Alice::Done(AliceEndState::BtcPunished) => {
+                if let Some(state3) = previous.and_then(Alice::state3) {
+                    let tx_cancel = state3.tx_cancel();
+                    self.record_cancel(&tx_cancel);
+                    self.record_punish(&tx_cancel, &state3.tx_punish());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn record_bob(&mut self, previous: Option<&Bob>, current: &Bob) {
+        match current {
+            Bob::Started { btc_amount } => {
+                self.btc_amount = Some(*btc_amount);
+            }
+            Bob::ExecutionSetupDone { state2 } => {
+                self.record_lock(state2.tx_lock());
+                self.xmr_amount = Some(state2.xmr());
+            }
+            Bob::BtcLocked { state3 } => {
+                self.record_lock(&state3.tx_lock);
+                self.xmr_amount = Some(state3.xmr());
+            }
+            Bob::XmrLockProofReceived {
+                state,
+                lock_transfer_proof,
+                ..
+            } => {
+                self.record_lock(&state.tx_lock);
+                self.xmr_amount = Some(state.xmr());
+                self.xmr_lock_tx_hash = Some(lock_transfer_proof.tx_hash().0);
+            }
+            Bob::XmrLocked { state4 } | Bob::EncSigSent { state4 } => {
+                self.record_lock(&state4.tx_lock);
+            }
+            Bob::BtcRedeemed(state5) => {
+                self.tx_lock_id = Some(state5.tx_lock_id());
+
+                match previous {
+                    Some(Bob::XmrLocked { state4 }) | Some(Bob::EncSigSent { state4 }) => {
+                        self.record_redeem(&state4.tx_redeem());
+                    }
+                    _ => {}
+                }
+            }
+            Bob::CancelTimelockExpired(state6) => {
+                self.tx_lock_id = Some(state6.tx_lock_id());
+            }
+            Bob::BtcCancelled(state6) => {
+                self.tx_lock_id = Some(state6.tx_lock_id());
+                self.record_cancel(&state6.tx_cancel());
+            }
+            Bob::Done(BobEndState::BtcRefunded(state6)) => {
+                let tx_cancel = state6.tx_cancel();
+                self.tx_lock_id = Some(state6.tx_lock_id());
+                self.record_cancel(&tx_cancel);
+                self.record_refund(&tx_cancel, &state6.tx_refund());
+            }
+            Bob::Done(BobEndState::XmrRedeemed { tx_lock_id })
+            | Bob::Done(BobEndState::BtcPunished { tx_lock_id }) => {
+                self.tx_lock_id = Some(*tx_lock_id);
+            }
+            Bob::Done(BobEndState::SafelyAborted) => {}
+        }
+    }
+
+    fn record_lock(&mut self, tx_lock: &TxLock) {
+        self.btc_amount = Some(tx_lock.lock_amount());
+        self.tx_lock_id = Some(tx_lock.txid());
+
+        if let Some(fee) = tx_lock.fee() {
+            self.tx_lock_fee = Some(fee);
+        }
+    }
+
+    fn record_redeem(&mut self, tx_redeem: &TxRedeem) {
+        self.tx_redeem_id = Some(tx_redeem.txid());
+        self.tx_redeem_fee = self
+            .btc_amount
+            .and_then(|btc| btc.checked_sub(tx_redeem.amount()));
+    }
+
+    fn record_cancel(&mut self, tx_cancel: &TxCancel) {
+        self.tx_cancel_id = Some(tx_cancel.txid());
+        self.tx_cancel_fee = self
+            .btc_amount
+            .and_then(|btc| btc.checked_sub(tx_cancel.amount()));
+    }
+
+    fn record_refund(&mut self, tx_cancel: &TxCancel, tx_refund: &TxRefund) {
+        self.tx_refund_id = Some(tx_refund.txid());
+        self.tx_refund_fee = tx_cancel.amount().checked_sub(tx_refund.amount());
+    }
+
+    fn record_punish(&mut self, tx_cancel: &TxCancel, tx_punish: &TxPunish) {
+        self.tx_punish_id = Some(tx_punish.txid());
+        self.tx_punish_fee = tx_cancel.amount().checked_sub(tx_punish.amount());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_start_and_end_of_a_swap() {
+        let mut details = SwapDetails::default();
+        let btc_amount = Amount::from_sat(100_000);
+
+        details.record(None, &Swap::Bob(Bob::Started { btc_amount }), 1_000);
+        details.record(
+            Some(&Swap::Bob(Bob::Started { btc_amount })),
+            &Swap::Bob(Bob::Done(BobEndState::SafelyAborted)),
+            2_000,
+        );
+
+        assert_eq!(details.started_at, Some(1_000));
+        assert_eq!(details.finished_at, Some(2_000));
+        assert_eq!(details.btc_amount, Some(btc_amount));
+    }
+
+    #[test]
+    fn saving_an_end_state_again_keeps_the_original_timestamps() {
+        let mut details = SwapDetails::default();
+        let done = Swap::Alice(Alice::Done(AliceEndState::BtcRedeemed));
+
+        details.record(None, &done, 1_000);
+        details.record(Some(&done), &done, 2_000);
+
+        assert_eq!(details.started_at, Some(1_000));
+        assert_eq!(details.finished_at, Some(1_000));
+    }
+
+    #[tokio::test]
+    async fn records_when_the_execution_setup_was_done_once() {
+        let (_, state2) = crate::protocol::execution_setup().await;
+        let mut details = SwapDetails::default();
+        let setup_done = Swap::Bob(Bob::ExecutionSetupDone { state2 });
+
+        details.record(None, &setup_done, 1_000);
+        // Resuming the swap saves the state again
+        details.record(Some(&setup_done), &setup_done, 2_000);
+
+        assert_eq!(details.execution_setup_done_at, Some(1_000));
+    }
+
+    #[test]
+    fn price_is_sats_per_xmr() {
+        let details = SwapDetails {
+            btc_amount: Some(Amount::from_sat(1_000_000)),
+            xmr_amount: Some(monero::Amount::from_piconero(250_000_000_000)),
+            ..SwapDetails::default()
+        };
+
+        assert_eq!(details.price(), Some(Amount::from_sat(4_000_000)));
+    }
+
+    #[test]
+    fn bitcoin_fees_sum_up_recorded_fees_of_bob() {
+        let details = SwapDetails {
+            role: Some(Role::Bob),
+            tx_lock_fee: Some(Amount::from_sat(300)),
+            tx_cancel_fee: Some(Amount::from_sat(200)),
+            tx_refund_fee: Some(Amount::from_sat(100)),
+            ..SwapDetails::default()
+        };
+
+        assert_eq!(details.bitcoin_fees(), Some(Amount::from_sat(600)));
+        assert_eq!(SwapDetails::default().bitcoin_fees(), None);
+    }
+
+    #[test]
+    fn bitcoin_fees_only_include_fees_of_the_local_party() {
+        let redeemed = SwapDetails {
+            role: Some(Role::Alice),
+            tx_lock_fee: Some(Amount::from_sat(300)),
+            tx_redeem_fee: Some(Amount::from_sat(200)),
+            ..SwapDetails::default()
+        };
+        let refunded = SwapDetails {
+            role: Some(Role::Alice),
+            tx_lock_fee: Some(Amount::from_sat(300)),
+            tx_cancel_fee: Some(Amount::from_sat(200)),
+            tx_refund_fee: Some(Amount::from_sat(100)),
+            ..SwapDetails::default()
+        };
+        let punished = SwapDetails {
+            role: Some(Role::Bob),
+            tx_lock_fee: Some(Amount::from_sat(300)),
+            tx_cancel_fee: Some(Amount::from_sat(200)),
+            tx_punish_id: Some(Txid::default()),
+            tx_punish_fee: Some(Amount::from_sat(100)),
+            ..SwapDetails::default()
+        };
+
+        assert_eq!(redeemed.bitcoin_fees(), Some(Amount::from_sat(200)));
+        assert_eq!(refunded.bitcoin_fees(), None);
+        assert_eq!(punished.bitcoin_fees(), Some(Amount::from_sat(300)));
+        assert_eq!(
+            SwapDetails {
+                role: Some(Role::Alice),
+                ..punished
+            }
+            .bitcoin_fees(),
+            Some(Amount::from_sat(300))
+        );
+    }
+
+    #[test]
+    fn details_without_fields_can_be_deserialized() {
+        #[derive(Serialize)]
+        struct Empty {}
+
+        let encoded = serde_cbor::to_vec(&Empty {}).unwrap();
+        let details = serde_cbor::from_slice::<SwapDetails>(&encoded).unwrap();
+
+        assert_eq!(details, SwapDetails::default());
+    }
+}
@@ -0,0 +1,196 @@
+//! Export of a single swap into a recovery file that can be imported into the
+//! database on another machine.
+//!
+//! The states of a swap contain all keys needed to cancel, refund, redeem or
+//! punish as well as the Bitcoin addresses of the wallet the swap was started
+//! with. Importing a swap does not change these addresses, the Bitcoin stays
+//! spendable by the wallet of the exporting machine.
+
+use crate::database::schema::VersionedSwap;
+use crate::database::{decode_keypair, deserialize, serialize, Identity, Storage, Swap};
+use crate::encryption::Encrypted;
+use anyhow::{bail, Context, Result};
+use libp2p::identity::ed25519;
+use libp2p::PeerId;
+use pem::Pem;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use uuid::Uuid;
+
+const PEM_TAG: &str = "SWAP RECOVERY";
+const ENCRYPTED_PEM_TAG: &str = "ENCRYPTED SWAP RECOVERY";
+
+/// Everything needed to resume a swap on another machine.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct SwapExport {
+    pub swap_id: Uuid,
+    state: VersionedSwap,
+    peer_id: Option<String>,
+    /// The encoded libp2p keypair the swap was executed with.
+    ///
+    /// The counterparty only accepts messages from this identity. It is only
+    /// exported for the swaps of Bob, Alice's identity is the one of the ASB.
+    identity: Option<Vec<u8>>,
+}
+
+impl SwapExport {
+    pub fn new(
+        db: &dyn Storage,
+        swap_id: Uuid,
+        identity: Option<ed25519::Keypair>,
+    ) -> Result<Self> {
+        let state = db.get_state(swap_id)?;
+        let peer_id = db
+            .get_peer_id(swap_id)
+            .ok()
+            .map(|peer_id| peer_id.to_string());
+
+        Ok(Self {
+            swap_id,
+            state: state.into(),
+            peer_id,
+            identity: identity.map(|identity| identity.encode().to_vec()),
+        })
+    }
+
+    pub fn state(&self) -> Swap {
+        self.state.clone().into()
+    }
+
+    /// Encodes the export as PEM, encrypted if a passphrase is given.
+    pub fn to_pem(&self, passphrase: Option<&str>) -> Result<String> {
+        let export = serialize(self).context("Could not serialize swap export")?;
+
+        let pem = match passphrase {
+            Some(passphrase) => Pem {
+                tag: ENCRYPTED_PEM_TAG.to_string(),
+                contents: serialize(&Encrypted::encrypt(&export, passphrase)?)?,
+            },
+            None => Pem {
+                tag: PEM_TAG.to_string(),
+                contents: export,
+            },
+        };
+
+        Ok(pem::encode(&pem))
+    }
+
+    /// Decodes an export, asking for the passphrase if it is encrypted.
+    pub fn from_pem<P>(pem: &str, passphrase: P) -> Result<Self>
+    where
+        P: FnOnce() -> Result<String>,
+    {
+        let pem = pem::parse(pem).context("Swap recovery file is not valid PEM")?;
+
+        let export = match pem.tag.as_str() {
+            PEM_TAG => pem.contents,
+            ENCRYPTED_PEM_TAG => {
+                let encrypted = deserialize::<Encrypted>(&pem.contents)
+                    .context("Could not deserialize encrypted swap export")?;
+                encrypted.decrypt(&passphrase()?)?
+            }
+            other => bail!("Unexpected PEM tag {} in swap recovery file", other),
+        };
+
+        deserialize(&export).context("Could not deserialize swap export")
+    }
+
+    /// Saves the swap in the given database.
+    ///
+    /// Fails if the database already contains a swap with the same id.
+    pub async fn import_into(self, db: &dyn Storage) -> Result<()> {
+        if db.get_state(self.swap_id).is_ok() {
+            bail!("Swap {} already exists in the database", self.swap_id)
+        }
+
+        if let Some(peer_id) = &self.peer_id {
+            let peer_id = PeerId::from_str(peer_id).context("Could not parse peer-id")?;
+            db.insert_peer_id(self.swap_id, peer_id).await?;
+        }
+        if let Some(identity) = &self.identity {
+            db.insert_identity(self.swap_id, Identity::Keypair(decode_keypair(identity)?))
+                .await?;
+        }
+        db.insert_latest_state(self.swap_id, self.state.into())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::alice::{Alice, AliceEndState};
+    use crate::database::bob::{Bob, BobEndState};
+    use crate::database::Database;
+
+    #[tokio::test]
+    async fn imported_swap_equals_exported_swap() -> Result<()> {
+        let exporting_dir = tempfile::tempdir().unwrap();
+        let exporting = Database::open(exporting_dir.path())?;
+        let swap_id = Uuid::new_v4();
+        let peer_id = PeerId::random();
+        let identity = ed25519::Keypair::generate();
+        let state = Swap::Bob(Bob::Done(BobEndState::SafelyAborted));
+
+        exporting.insert_peer_id(swap_id, peer_id).await?;
+        exporting
+            .insert_latest_state(swap_id, state.clone())
+            .await?;
+
+        let pem = SwapExport::new(&*exporting, swap_id, Some(identity.clone()))?.to_pem(None)?;
+
+        let importing_dir = tempfile::tempdir().unwrap();
+        let importing = Database::open(importing_dir.path())?;
+        SwapExport::from_pem(&pem, || panic!("no passphrase needed"))?
+            .import_into(&*importing)
+            .await?;
+
+        assert_eq!(importing.get_state(swap_id)?, state);
+        assert_eq!(importing.get_peer_id(swap_id)?, peer_id);
+        assert_eq!(
+            importing
+                .get_identity(swap_id)?
+                .map(|identity| identity.encode()),
+            Some(identity.encode().to_vec())
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn encrypted_export_requires_the_passphrase() -> Result<()> {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(db_dir.path())?;
+        let swap_id = Uuid::new_v4();
+        let state = Swap::Alice(Alice::Done(AliceEndState::BtcRedeemed));
+        db.insert_latest_state(swap_id, state.clone()).await?;
+
+        let pem = SwapExport::new(&*db, swap_id, None)?.to_pem(Some("passphrase"))?;
+
+        assert!(SwapExport::from_pem(&pem, || Ok("wrong".to_string())).is_err());
+        let export = SwapExport::from_pem(&pem, || Ok("passphrase".to_string()))?;
+        assert_eq!(export.state(), state);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn refuses_to_import_existing_swap() -> Result<()> {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(db_dir.path())?;
+        let swap_id = Uuid::new_v4();
+        db.insert_latest_state(
+            swap_id,
+            Swap::Alice(Alice::Done(AliceEndState::BtcRedeemed)),
+        )
+        .await?;
+
+        let export = SwapExport::new(&*db, swap_id, None)?;
+
+        assert!(export.import_into(&*db).await.is_err());
+
+        Ok(())
+    }
+}
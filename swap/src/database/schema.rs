@@ -0,0 +1,326 @@
+//! Versioning of the records stored in the database.
+//!
+//! Every swap is stored wrapped in [`VersionedSwap`] and the database keeps
+//! track of the schema version its records are written in. When opening a
+//! database with an older schema version the migrations are applied in order
+//! until the records are in the current version.
+//!
+//! Changing the serialized representation of [`Swap`] (or any of the types it
+//! contains) requires a new schema version:
+//!
+//! 1. Freeze the current representation in a legacy type that is only used
+//!    for deserialization and make the existing variant of [`VersionedSwap`]
+//!    hold it.
+//! 2. Add a new variant to [`VersionedSwap`] and bump [`CURRENT_VERSION`].
+//! 3. Add a migration converting the records of the previous version.
+//! 4. Add fixtures of records in the new version to `tests/fixtures/database`,
+//!    the fixtures of the previous versions must not change.
+//!
+//! Keeping the old variants around allows reading records that are not
+//! migrated, like the entries of the state log.
+
+use crate::database::{deserialize, serialize, Swap};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sled::transaction::ConflictableTransactionError;
+use sled::Transactional;
+use uuid::Uuid;
+
+/// The schema version the records of this version of the software are
+/// written in.
+///
+/// Version 0 is the original format which stored the CBOR of [`Swap`]
+/// without a version tag.
+pub const CURRENT_VERSION: u32 = 1;
+
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// Converts a swap record of one schema version into the next one.
+type Migration = fn(Uuid, &[u8]) -> Result<Vec<u8>>;
+
+/// The migrations from each schema version to the next, the migration at
+/// index `n` upgrades records from version `n` to `n + 1`.
+const MIGRATIONS: [Migration; CURRENT_VERSION as usize] = [wrap_unversioned_swap];
+
+/// The representation of a swap in the database.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub enum VersionedSwap {
+    V1(Swap),
+}
+
+impl From<Swap> for VersionedSwap {
+    fn from(swap: Swap) -> Self {
+        VersionedSwap::V1(swap)
+    }
+}
+
+impl From<VersionedSwap> for Swap {
+    fn from(versioned: VersionedSwap) -> Self {
+        match versioned {
+            VersionedSwap::V1(swap) => swap,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+#[error("Database schema version {found} is newer than the supported version {supported}, please upgrade to a newer version of this software")]
+pub struct UnsupportedSchemaVersion {
+    pub found: u32,
+    pub supported: u32,
+}
+
+/// Brings the records of the database to the current schema version.
+///
+/// A database without a schema version is considered to be in version 0
+/// unless it is empty, in which case it is new and starts out in the current
+/// version.
+pub fn migrate(swaps: &sled::Tree, metadata: &sled::Tree) -> Result<()> {
+    let version = match read_version(metadata)? {
+        Some(version) => version,
+        None if swaps.is_empty() => CURRENT_VERSION,
+        None => 0,
+    };
+
+    if version > CURRENT_VERSION {
+        bail!(UnsupportedSchemaVersion {
+            found: version,
+            supported: CURRENT_VERSION,
+        })
+    }
+
+    for from in version..CURRENT_VERSION {
+        let to = from + 1;
+
+        tracing::info!(%from, %to, "Migrating database");
+        apply(swaps, metadata, MIGRATIONS[from as usize], to)
+            .with_context(|| format!("Failed to migrate database to schema version {}", to))?;
+    }
+
+    if read_version(metadata)? != Some(CURRENT_VERSION) {
+        write_version(metadata, CURRENT_VERSION)?;
+    }
+
+    Ok(())
+}
+
+/// Applies a migration to all swap records and bumps the schema version in a
+/// single transaction.
+fn apply(
+    swaps: &sled::Tree,
+    metadata: &sled::Tree,
+    migration: Migration,
+    version: u32,
+) -> Result<()> {
+    let migrated = swaps
+        .iter()
+        .map(|item| {
+            let (key, value) = item.context("Failed to retrieve swap from DB")?;
+            let swap_id = deserialize::<Uuid>(&key)?;
+            let value = migration(swap_id, &value)
+                .with_context(|| format!("Failed to migrate swap {}", swap_id))?;
+
+            Ok((key, value))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let version = serialize(&version)?;
+
+    (swaps, metadata)
+        .transaction(|(swaps, metadata)| {
+            for (key, value) in &migrated {
+                swaps.insert(key, value.as_slice())?;
+            }
+            metadata.insert(SCHEMA_VERSION_KEY, version.as_slice())?;
+
+            Ok::<_, ConflictableTransactionError<()>>(())
+        })
+        .map_err(|error| anyhow!("Failed to write migrated records: {:?}", error))?;
+
+    swaps.flush().context("Could not flush db")?;
+
+    Ok(())
+}
+
+fn read_version(metadata: &sled::Tree) -> Result<Option<u32>> {
+    metadata
+        .get(SCHEMA_VERSION_KEY)?
+        .map(|encoded| deserialize(&encoded).context("Could not deserialize schema version"))
+        .transpose()
+}
+
+fn write_version(metadata: &sled::Tree, version: u32) -> Result<()> {
+    metadata.insert(SCHEMA_VERSION_KEY, serialize(&version)?)?;
+    metadata.flush().context("Could not flush db")?;
+
+    Ok(())
+}
+
+/// Version 0 stored swaps without a version tag.
+fn wrap_unversioned_swap(_: Uuid, value: &[u8]) -> Result<Vec<u8>> {
+    let swap = deserialize::<Swap>(value)?;
+
+    serialize(&VersionedSwap::V1(swap))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::alice::{Alice, AliceEndState};
+    use crate::database::bob::{Bob, BobEndState};
+    use crate::database::{SledStorage, Storage};
+    use ::bitcoin::hashes::hex::FromHex;
+    use std::path::{Path, PathBuf};
+
+    const TX_LOCK_ID: &str = "9ed9d0e3b1ba8a1e3a62e3e1ab22d9a2d0f20f3b0f7ad8c1c0b0c5c7a5ef0b2e";
+
+    /// The names of the fixture files and the swaps they contain.
+    fn fixtures() -> Vec<(&'static str, Swap)> {
+        vec![
+            (
+                "alice_done_btc_redeemed",
+                Swap::Alice(Alice::Done(AliceEndState::BtcRedeemed)),
+            ),
+            (
+                "alice_done_btc_punished",
+                Swap::Alice(Alice::Done(AliceEndState::BtcPunished)),
+            ),
+            (
+                "alice_done_safely_aborted",
+                Swap::Alice(Alice::Done(AliceEndState::SafelyAborted)),
+            ),
+            (
+                "alice_done_xmr_refunded",
+                Swap::Alice(Alice::Done(AliceEndState::XmrRefunded)),
+            ),
+            (
+                "bob_started",
+                Swap::Bob(Bob::Started {
+                    btc_amount: ::bitcoin::Amount::from_sat(100_000),
+                }),
+            ),
+            (
+                "bob_done_xmr_redeemed",
+                Swap::Bob(Bob::Done(BobEndState::XmrRedeemed {
+                    tx_lock_id: ::bitcoin::Txid::from_hex(TX_LOCK_ID).unwrap(),
+                })),
+            ),
+            (
+                "bob_done_btc_punished",
+                Swap::Bob(Bob::Done(BobEndState::BtcPunished {
+                    tx_lock_id: ::bitcoin::Txid::from_hex(TX_LOCK_ID).unwrap(),
+                })),
+            ),
+            (
+                "bob_done_safely_aborted",
+                Swap::Bob(Bob::Done(BobEndState::SafelyAborted)),
+            ),
+        ]
+    }
+
+    fn fixture_path(version: u32, name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/database")
+            .join(format!("v{}", version))
+            .join(format!("{}.cbor", name))
+    }
+
+    fn read_fixture(version: u32, name: &str) -> Vec<u8> {
+        let path = fixture_path(version, name);
+
+        std::fs::read(&path).unwrap_or_else(|_| panic!("fixture {} to exist", path.display()))
+    }
+
+    fn migrate_fixture(version: u32, name: &str) -> Swap {
+        let mut record = read_fixture(version, name);
+
+        for migration in MIGRATIONS.iter().skip(version as usize) {
+            record = migration(Uuid::nil(), &record).unwrap();
+        }
+
+        Swap::from(deserialize::<VersionedSwap>(&record).unwrap())
+    }
+
+    #[test]
+    fn current_version_serializes_like_fixtures() {
+        for (name, swap) in fixtures() {
+            let fixture = read_fixture(CURRENT_VERSION, name);
+
+            assert_eq!(
+                serialize(&VersionedSwap::from(swap.clone())).unwrap(),
+                fixture,
+                "serialized {} differs from fixture",
+                name
+            );
+            assert_eq!(
+                Swap::from(deserialize::<VersionedSwap>(&fixture).unwrap()),
+                swap
+            );
+        }
+    }
+
+    #[test]
+    fn all_fixture_versions_migrate_to_current_version() {
+        for version in 0..=CURRENT_VERSION {
+            for (name, expected) in fixtures() {
+                let migrated = migrate_fixture(version, name);
+                assert_eq!(migrated, expected, "{} of version {}", name, version);
+            }
+        }
+    }
+
+    #[test]
+    fn opening_unversioned_database_migrates_records() {
+        let db_dir = tempfile::tempdir().unwrap();
+        let swap_ids = fixtures()
+            .into_iter()
+            .map(|(name, swap)| (Uuid::new_v4(), name, swap))
+            .collect::<Vec<_>>();
+
+        {
+            let db = sled::open(db_dir.path()).unwrap();
+            let swaps = db.open_tree("swaps").unwrap();
+
+            for (swap_id, name, _) in &swap_ids {
+                swaps
+                    .insert(serialize(swap_id).unwrap(), read_fixture(0, name))
+                    .unwrap();
+            }
+            db.flush().unwrap();
+        }
+
+        let db = SledStorage::open(db_dir.path()).unwrap();
+
+        for (swap_id, _, expected) in swap_ids {
+            assert_eq!(db.get_state(swap_id).unwrap(), expected);
+        }
+        assert_eq!(read_version(&db.metadata).unwrap(), Some(CURRENT_VERSION));
+    }
+
+    #[test]
+    fn new_database_starts_in_current_version() {
+        let db_dir = tempfile::tempdir().unwrap();
+
+        let db = SledStorage::open(db_dir.path()).unwrap();
+
+        assert_eq!(read_version(&db.metadata).unwrap(), Some(CURRENT_VERSION));
+    }
+
+    #[test]
+    fn refuses_to_open_database_of_newer_version() {
+        let db_dir = tempfile::tempdir().unwrap();
+
+        {
+            let db = SledStorage::open(db_dir.path()).unwrap();
+            write_version(&db.metadata, CURRENT_VERSION + 1).unwrap();
+        }
+
+        let error = SledStorage::open(db_dir.path()).err().unwrap();
+
+        assert_eq!(
+            error.downcast_ref::<UnsupportedSchemaVersion>().unwrap(),
+            &UnsupportedSchemaVersion {
+                found: CURRENT_VERSION + 1,
+                supported: CURRENT_VERSION
+            }
+        );
+    }
+}
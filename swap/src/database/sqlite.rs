@@ -0,0 +1,699 @@
+//! SQLite backend of the database.
+//!
+//! Unlike the sled backend, which stores opaque records, the swaps, peers and
+//! states are kept in tables that can be queried with any SQLite client. The
+//! states themselves are stored in the same CBOR encoding as in sled, next to
+//! their human readable description.
+
+use crate::bitcoin::UtxoLabel;
+use crate::database::schema::VersionedSwap;
+use crate::database::{
+    deserialize, serialize, Identity, LoggedState, Storage, Swap, SwapDetails,
+    UnsupportedSchemaVersion,
+};
+use crate::fs::ensure_directory_exists;
+use crate::monero::TransferProof;
+use ::bitcoin::Script;
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use libp2p::identity::ed25519;
+use libp2p::PeerId;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// The schema version of the tables created by this version of the software,
+/// tracked in the `user_version` of the database.
+const CURRENT_VERSION: u32 = 5;
+
+/// The migrations from each schema version to the next, the migration at
+/// index `n` upgrades the tables from version `n` to `n + 1`.
+const MIGRATIONS: [&str; CURRENT_VERSION as usize] = [
+    r#"
+CREATE TABLE swaps (
+    swap_id TEXT PRIMARY KEY NOT NULL,
+    role TEXT NOT NULL,
+    details TEXT NOT NULL
+);
+
+CREATE TABLE peers (
+    swap_id TEXT PRIMARY KEY NOT NULL,
+    peer_id TEXT NOT NULL
+);
+
+CREATE TABLE states (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    swap_id TEXT NOT NULL REFERENCES swaps (swap_id),
+    entered_at INTEGER,
+    description TEXT NOT NULL,
+    state BLOB NOT NULL
+);
+
+CREATE INDEX states_by_swap_id ON states (swap_id);
+"#,
+    r#"
+CREATE TABLE identities (
+    swap_id TEXT PRIMARY KEY NOT NULL,
+    keypair BLOB NOT NULL
+);
+"#,
+    r#"
+CREATE TABLE derivation_indexes (
+    descriptor TEXT PRIMARY KEY NOT NULL,
+    next_index INTEGER NOT NULL
+);
+"#,
+    r#"
+CREATE TABLE pending_monero_refill (
+    id INTEGER PRIMARY KEY CHECK (id = 0),
+    transfer_proof BLOB NOT NULL
+);
+"#,
+    r#"
+CREATE TABLE utxo_labels (
+    script BLOB PRIMARY KEY NOT NULL,
+    label TEXT NOT NULL
+);
+"#,
+];
+
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &Path) -> Result<Self> {
+        tracing::debug!("Opening SQLite database at {}", path.display());
+
+        ensure_directory_exists(path)?;
+        let mut connection = Connection::open(path)
+            .with_context(|| format!("Could not open the DB at {:?}", path))?;
+        connection.pragma_update(None, "foreign_keys", &true)?;
+
+        migrate(&mut connection)?;
+
+        Ok(SqliteStorage {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Copies all swaps of another database into this one.
+    ///
+    /// The complete state log of each swap is copied. If the log does not end
+    /// in the latest state, because the swap was saved before states were
+    /// logged, the latest state is added without a timestamp.
+    ///
+    /// Only an empty database can be imported into. Returns the number of
+    /// imported swaps.
+    pub fn import(&self, from: &dyn Storage) -> Result<usize> {
+        let swaps = from.all_swaps()?;
+
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+
+        let existing: i64 = transaction.query_row(
+            "SELECT (SELECT COUNT(*) FROM swaps) + (SELECT COUNT(*) FROM peers)",
+            params![],
+            |row| row.get(0),
+        )?;
+        if existing > 0 {
+            bail!("Cannot import swaps into a database that is not empty")
+        }
+
+        for (swap_id, state) in &swaps {
+            upsert_swap(&transaction, *swap_id, state, &from.get_details(*swap_id)?)?;
+
+            let log = from.get_state_log(*swap_id)?;
+            for logged in &log {
+                insert_state(
+                    &transaction,
+                    *swap_id,
+                    Some(logged.timestamp),
+                    &logged.state,
+                )?;
+            }
+            if log.last().map(|logged| &logged.state) != Some(state) {
+                insert_state(&transaction, *swap_id, None, state)?;
+            }
+
+            if let Ok(peer_id) = from.get_peer_id(*swap_id) {
+                upsert_peer(&transaction, *swap_id, peer_id)?;
+            }
+            if let Some(identity) = from.get_identity(*swap_id)? {
+                upsert_identity(&transaction, *swap_id, &identity)?;
+            }
+        }
+
+        for (descriptor, next_index) in from.derivation_indexes()? {
+            upsert_derivation_index(&transaction, &descriptor, next_index)?;
+        }
+        if let Some(refill) = from.get_pending_monero_refill()? {
+            upsert_pending_monero_refill(&transaction, &refill)?;
+        }
+        for (script, label) in from.utxo_labels()? {
+            upsert_utxo_label(&transaction, &script, &label)?;
+        }
+
+        transaction
+            .commit()
+            .context("Could not write imported swaps")?;
+
+        Ok(swaps.len())
+    }
+
+    fn connection(&self) -> Result<MutexGuard<'_, Connection>> {
+        self.connection
+            .lock()
+            .map_err(|_| anyhow!("SQLite connection was poisoned"))
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn insert_peer_id(&self, swap_id: Uuid, peer_id: PeerId) -> Result<()> {
+        let connection = self.connection()?;
+
+        upsert_peer(&connection, swap_id, peer_id)
+    }
+
+    fn get_peer_id(&self, swap_id: Uuid) -> Result<PeerId> {
+        let connection = self.connection()?;
+
+        let peer_id: String = connection
+            .query_row(
+                "SELECT peer_id FROM peers WHERE swap_id = ?1",
+                params![swap_id.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or_else(|| anyhow!("No peer-id found for swap id {} in database", swap_id))?;
+
+        Ok(PeerId::from_str(peer_id.as_str())?)
+    }
+
+    async fn insert_identity(&self, swap_id: Uuid, identity: Identity) -> Result<()> {
+        let connection = self.connection()?;
+
+        upsert_identity(&connection, swap_id, &identity)
+    }
+
+    fn get_identity(&self, swap_id: Uuid) -> Result<Option<Identity>> {
+        let connection = self.connection()?;
+
+        connection
+            .query_row(
+                "SELECT keypair FROM identities WHERE swap_id = ?1",
+                params![swap_id.to_string()],
+                |row| row.get::<_, Vec<u8>>(0),
+            )
+            .optional()?
+            .map(|encoded| Identity::decode(&encoded))
+            .transpose()
+    }
+
+    async fn insert_latest_state(&self, swap_id: Uuid, state: Swap) -> Result<()> {
+        let timestamp = OffsetDateTime::now_utc().unix_timestamp();
+
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+
+        let old_state = latest_state(&transaction, swap_id)?;
+        let mut details = details(&transaction, swap_id)?;
+        details.record(old_state.as_ref(), &state, timestamp);
+
+        upsert_swap(&transaction, swap_id, &state, &details)?;
+        insert_state(&transaction, swap_id, Some(timestamp), &state)?;
+
+        transaction.commit().context("Could not write in the DB")
+    }
+
+    fn get_state(&self, swap_id: Uuid) -> Result<Swap> {
+        let connection = self.connection()?;
+
+        latest_state(&connection, swap_id)?
+            .ok_or_else(|| anyhow!("Swap with id {} not found in database", swap_id))
+    }
+
+    fn get_state_log(&self, swap_id: Uuid) -> Result<Vec<LoggedState>> {
+        let connection = self.connection()?;
+
+        let mut statement = connection.prepare(
+            "SELECT entered_at, state FROM states
+             WHERE swap_id = ?1 AND entered_at IS NOT NULL
+             ORDER BY id",
+        )?;
+        let log = statement
+            .query_map(params![swap_id.to_string()], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?))
+            })?
+            .map(|row| {
+                let (timestamp, encoded) =
+                    row.context("Failed to retrieve state log entry from DB")?;
+
+                Ok(LoggedState {
+                    timestamp,
+                    state: decode_state(&encoded)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(log)
+    }
+
+    fn get_details(&self, swap_id: Uuid) -> Result<SwapDetails> {
+        let connection = self.connection()?;
+
+        details(&connection, swap_id)
+    }
+
+    fn all_swaps(&self) -> Result<Vec<(Uuid, Swap)>> {
+        let connection = self.connection()?;
+
+        let mut statement = connection.prepare(
+            "SELECT swap_id, state FROM states
+             WHERE id IN (SELECT MAX(id) FROM states GROUP BY swap_id)
+             ORDER BY id",
+        )?;
+        let swaps = statement
+            .query_map(params![], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?))
+            })?
+            .map(|row| {
+                let (swap_id, encoded) = row.context("Failed to retrieve swap from DB")?;
+                let swap_id = Uuid::parse_str(&swap_id).context("Failed to parse swap id")?;
+
+                Ok((swap_id, decode_state(&encoded)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(swaps)
+    }
+
+    async fn next_derivation_index(&self, descriptor: &str) -> Result<u32> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+
+        let index: u32 = transaction
+            .query_row(
+                "SELECT next_index FROM derivation_indexes WHERE descriptor = ?1",
+                params![descriptor],
+                |row| row.get(0),
+            )
+            .optional()?
+            .unwrap_or(0);
+        upsert_derivation_index(&transaction, descriptor, index.saturating_add(1))?;
+
+        transaction.commit().context("Could not write in the DB")?;
+
+        Ok(index)
+    }
+
+    fn derivation_indexes(&self) -> Result<Vec<(String, u32)>> {
+        let connection = self.connection()?;
+
+        let mut statement =
+            connection.prepare("SELECT descriptor, next_index FROM derivation_indexes")?;
+        let indexes = statement
+            .query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to retrieve derivation indexes from DB")?;
+
+        Ok(indexes)
+    }
+
+    async fn insert_pending_monero_refill(&self, refill: Option<TransferProof>) -> Result<()> {
+        let connection = self.connection()?;
+
+        match refill {
+            Some(refill) => upsert_pending_monero_refill(&connection, &refill),
+            None => {
+                connection.execute("DELETE FROM pending_monero_refill", params![])?;
+                Ok(())
+            }
+        }
+    }
+
+    fn get_pending_monero_refill(&self) -> Result<Option<TransferProof>> {
+        let connection = self.connection()?;
+
+        connection
+            .query_row(
+                "SELECT transfer_proof FROM pending_monero_refill WHERE id = 0",
+                params![],
+                |row| row.get::<_, Vec<u8>>(0),
+            )
+            .optional()?
+            .map(|encoded| deserialize(&encoded).context("Could not deserialize Monero refill"))
+            .transpose()
+    }
+
+    async fn insert_utxo_label(&self, script: Script, label: UtxoLabel) -> Result<()> {
+        let connection = self.connection()?;
+
+        upsert_utxo_label(&connection, &script, &label)
+    }
+
+    fn utxo_labels(&self) -> Result<Vec<(Script, UtxoLabel)>> {
+        let connection = self.connection()?;
+
+        let mut statement = connection.prepare("SELECT script, label FROM utxo_labels")?;
+        let labels = statement
+            .query_map(params![], |row| {
+                Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, String>(1)?))
+            })?
+            .map(|row| {
+                let (script, label) = row.context("Failed to retrieve UTXO label from DB")?;
+                let label =
+                    serde_json::from_str(&label).context("Could not deserialize UTXO label")?;
+
+                Ok((Script::from(script), label))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(labels)
+    }
+}
+
+/// Brings the tables of the database to the current schema version.
+fn migrate(connection: &mut Connection) -> Result<()> {
+    let version: u32 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    if version > CURRENT_VERSION {
+        bail!(UnsupportedSchemaVersion {
+            found: version,
+            supported: CURRENT_VERSION,
+        })
+    }
+
+    for from in version..CURRENT_VERSION {
+        let to = from + 1;
+
+        tracing::info!(%from, %to, "Migrating SQLite database");
+
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(MIGRATIONS[from as usize])?;
+        transaction.pragma_update(None, "user_version", &to)?;
+        transaction
+            .commit()
+            .with_context(|| format!("Failed to migrate database to schema version {}", to))?;
+    }
+
+    Ok(())
+}
+
+fn latest_state(connection: &Connection, swap_id: Uuid) -> Result<Option<Swap>> {
+    connection
+        .query_row(
+            "SELECT state FROM states WHERE swap_id = ?1 ORDER BY id DESC LIMIT 1",
+            params![swap_id.to_string()],
+            |row| row.get::<_, Vec<u8>>(0),
+        )
+        .optional()?
+        .map(|encoded| decode_state(&encoded))
+        .transpose()
+}
+
+fn details(connection: &Connection, swap_id: Uuid) -> Result<SwapDetails> {
+    let details = connection
+        .query_row(
+            "SELECT details FROM swaps WHERE swap_id = ?1",
+            params![swap_id.to_string()],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?;
+
+    match details {
+        Some(details) => {
+            serde_json::from_str(&details).context("Could not deserialize swap details")
+        }
+        None => Ok(SwapDetails::default()),
+    }
+}
+
+fn upsert_swap(
+    connection: &Connection,
+    swap_id: Uuid,
+    state: &Swap,
+    details: &SwapDetails,
+) -> Result<()> {
+    let role = match state {
+        Swap::Alice(_) => "alice",
+        Swap::Bob(_) => "bob",
+    };
+    let details = serde_json::to_string(details).context("Could not serialize swap details")?;
+
+    connection.execute(
+        "INSERT INTO swaps (swap_id, role, details) VALUES (?1, ?2, ?3)
+         ON CONFLICT (swap_id) DO UPDATE SET details = excluded.details",
+        params![swap_id.to_string(), role, details],
+    )?;
+
+    Ok(())
+}
+
+fn upsert_peer(connection: &Connection, swap_id: Uuid, peer_id: PeerId) -> Result<()> {
+    connection.execute(
+        "INSERT INTO peers (swap_id, peer_id) VALUES (?1, ?2)
+         ON CONFLICT (swap_id) DO UPDATE SET peer_id = excluded.peer_id",
+        params![swap_id.to_string(), peer_id.to_string()],
+    )?;
+
+    Ok(())
+}
+
+fn upsert_identity(connection: &Connection, swap_id: Uuid, identity: &Identity) -> Result<()> {
+    connection.execute(
+        "INSERT INTO identities (swap_id, keypair) VALUES (?1, ?2)
+         ON CONFLICT (swap_id) DO UPDATE SET keypair = excluded.keypair",
+        params![swap_id.to_string(), identity.encode()],
+    )?;
+
+    Ok(())
+}
+
+fn upsert_derivation_index(
+    connection: &Connection,
+    descriptor: &str,
+    next_index: u32,
+) -> Result<()> {
+    connection.execute(
+        "INSERT INTO derivation_indexes (descriptor, next_index) VALUES (?1, ?2)
+         ON CONFLICT (descriptor) DO UPDATE SET next_index = excluded.next_index",
+        params![descriptor, next_index],
+    )?;
+
+    Ok(())
+}
+
+fn upsert_pending_monero_refill(connection: &Connection, refill: &TransferProof) -> Result<()> {
+    let encoded = serialize(refill).context("Could not serialize Monero refill")?;
+
+    connection.execute(
+        "INSERT INTO pending_monero_refill (id, transfer_proof) VALUES (0, ?1)
+         ON CONFLICT (id) DO UPDATE SET transfer_proof = excluded.transfer_proof",
+        params![encoded],
+    )?;
+
+    Ok(())
+}
+
+fn upsert_utxo_label(connection: &Connection, script: &Script, label: &UtxoLabel) -> Result<()> {
+    let label = serde_json::to_string(label).context("Could not serialize UTXO label")?;
+
+    connection.execute(
+        "INSERT INTO utxo_labels (script, label) VALUES (?1, ?2)
+         ON CONFLICT (script) DO UPDATE SET label = excluded.label",
+        params![script.as_bytes(), label],
+    )?;
+
+    Ok(())
+}
+
+fn insert_state(
+    connection: &Connection,
+    swap_id: Uuid,
+    entered_at: Option<i64>,
+    state: &Swap,
+) -> Result<()> {
+    let encoded = serialize(&VersionedSwap::from(state.clone()))
+        .context("Could not serialize new state value")?;
+
+    connection.execute(
+        "INSERT INTO states (swap_id, entered_at, description, state)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![swap_id.to_string(), entered_at, state.to_string(), encoded],
+    )?;
+
+    Ok(())
+}
+
+fn decode_state(encoded: &[u8]) -> Result<Swap> {
+    let state = deserialize::<VersionedSwap>(encoded).context("Could not deserialize state")?;
+
+    Ok(state.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::alice::{Alice, AliceEndState};
+    use crate::database::bob::{Bob, BobEndState};
+    use crate::database::SledStorage;
+    use crate::monero;
+    use crate::monero::TxHash;
+
+    fn open() -> (tempfile::TempDir, SqliteStorage) {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = SqliteStorage::open(&db_dir.path().join("database.sqlite")).unwrap();
+
+        (db_dir, db)
+    }
+
+    #[tokio::test]
+    async fn can_save_swap_state_and_peer_id_with_same_swap_id() -> Result<()> {
+        let (_db_dir, db) = open();
+
+        let alice_id = Uuid::new_v4();
+        let alice_swap = Swap::Alice(Alice::Done(AliceEndState::BtcPunished));
+        let peer_id = PeerId::random();
+
+        db.insert_latest_state(alice_id, alice_swap.clone()).await?;
+        db.insert_peer_id(alice_id, peer_id).await?;
+
+        assert_eq!(db.get_state(alice_id)?, alice_swap);
+        assert_eq!(db.get_peer_id(alice_id)?, peer_id);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn latest_state_is_returned_and_all_states_are_logged() -> Result<()> {
+        let (_db_dir, db) = open();
+
+        let swap_id = Uuid::new_v4();
+        let btc_amount = ::bitcoin::Amount::from_sat(100_000);
+        let started = Swap::Bob(Bob::Started { btc_amount });
+        let done = Swap::Bob(Bob::Done(BobEndState::SafelyAborted));
+
+        db.insert_latest_state(swap_id, started.clone()).await?;
+        db.insert_latest_state(swap_id, done.clone()).await?;
+
+        let states = db
+            .get_state_log(swap_id)?
+            .into_iter()
+            .map(|logged| logged.state)
+            .collect::<Vec<_>>();
+
+        assert_eq!(db.get_state(swap_id)?, done);
+        assert_eq!(states, vec![started, done.clone()]);
+        assert_eq!(db.all_swaps()?, vec![(swap_id, done)]);
+
+        let details = db.get_details(swap_id)?;
+        assert_eq!(details.btc_amount, Some(btc_amount));
+        assert!(details.finished_at.is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reopen_db() -> Result<()> {
+        let db_dir = tempfile::tempdir().unwrap();
+        let path = db_dir.path().join("database.sqlite");
+        let alice_id = Uuid::new_v4();
+        let alice_swap = Swap::Alice(Alice::Done(AliceEndState::BtcPunished));
+
+        {
+            let db = SqliteStorage::open(&path)?;
+            db.insert_latest_state(alice_id, alice_swap.clone()).await?;
+        }
+
+        let db = SqliteStorage::open(&path)?;
+
+        assert_eq!(db.get_state(alice_id)?, alice_swap);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn import_copies_swaps_peers_and_states_from_sled() -> Result<()> {
+        let sled_dir = tempfile::tempdir().unwrap();
+        let sled = SledStorage::open(sled_dir.path())?;
+        let (_db_dir, db) = open();
+
+        let swap_id = Uuid::new_v4();
+        let peer_id = PeerId::random();
+        let started = Swap::Bob(Bob::Started {
+            btc_amount: ::bitcoin::Amount::from_sat(100_000),
+        });
+        let done = Swap::Bob(Bob::Done(BobEndState::SafelyAborted));
+
+        let identity = Identity::Keypair(ed25519::Keypair::generate());
+
+        sled.insert_peer_id(swap_id, peer_id).await?;
+        sled.insert_identity(swap_id, identity.clone()).await?;
+        sled.insert_latest_state(swap_id, started).await?;
+        sled.insert_latest_state(swap_id, done.clone()).await?;
+        sled.next_derivation_index("descriptor").await?;
+        let label = UtxoLabel::Refund { swap_id };
+        sled.insert_utxo_label(Script::from(vec![0u8; 22]), label)
+            .await?;
+
+        assert_eq!(db.import(&sled)?, 1);
+
+        assert_eq!(db.get_state(swap_id)?, done);
+        assert_eq!(db.get_peer_id(swap_id)?, peer_id);
+        assert_eq!(
+            db.get_identity(swap_id)?.map(|identity| identity.encode()),
+            Some(identity.encode())
+        );
+        assert_eq!(db.get_state_log(swap_id)?, sled.get_state_log(swap_id)?);
+        assert_eq!(db.get_details(swap_id)?, sled.get_details(swap_id)?);
+        assert_eq!(db.next_derivation_index("descriptor").await?, 1);
+        assert_eq!(
+            db.utxo_labels()?,
+            vec![(Script::from(vec![0u8; 22]), label)]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pending_monero_refill_is_kept_until_cleared() -> Result<()> {
+        let (_db_dir, db) = open();
+        let refill = TransferProof::new(
+            TxHash("refill".to_string()),
+            monero::PrivateKey::from_scalar(monero::Scalar::random(&mut rand::thread_rng())),
+        );
+
+        assert_eq!(db.get_pending_monero_refill()?, None);
+
+        db.insert_pending_monero_refill(Some(refill.clone()))
+            .await?;
+        assert_eq!(db.get_pending_monero_refill()?, Some(refill));
+
+        db.insert_pending_monero_refill(None).await?;
+        assert_eq!(db.get_pending_monero_refill()?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn import_refuses_database_that_is_not_empty() -> Result<()> {
+        let sled_dir = tempfile::tempdir().unwrap();
+        let sled = SledStorage::open(sled_dir.path())?;
+        let (_db_dir, db) = open();
+
+        db.insert_latest_state(
+            Uuid::new_v4(),
+            Swap::Alice(Alice::Done(AliceEndState::BtcPunished)),
+        )
+        .await?;
+
+        assert!(db.import(&sled).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+//! Append-only log of all states a swap went through.
+//!
+//! Entries are keyed by the serialized swap id followed by the big-endian
+//! index of the entry, which keeps the entries of a swap next to each other
+//! and in the order they were saved.
+
+use crate::database::schema::VersionedSwap;
+use crate::database::Swap;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+
+/// A state of a swap together with the time it was saved.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoggedState {
+    /// Unix timestamp of the moment the state was saved.
+    pub timestamp: i64,
+    pub state: Swap,
+}
+
+/// The representation of a [`LoggedState`] in the database.
+#[derive(Deserialize, Serialize)]
+pub(super) struct Entry {
+    timestamp: i64,
+    state: VersionedSwap,
+}
+
+impl From<LoggedState> for Entry {
+    fn from(logged: LoggedState) -> Self {
+        Entry {
+            timestamp: logged.timestamp,
+            state: logged.state.into(),
+        }
+    }
+}
+
+impl From<Entry> for LoggedState {
+    fn from(entry: Entry) -> Self {
+        LoggedState {
+            timestamp: entry.timestamp,
+            state: entry.state.into(),
+        }
+    }
+}
+
+pub(super) fn key(swap_key: &[u8], index: u64) -> Vec<u8> {
+    let mut key = swap_key.to_vec();
+    key.extend_from_slice(&index.to_be_bytes());
+
+    key
+}
+
+pub(super) fn index(swap_key: &[u8], key: &[u8]) -> Result<u64> {
+    let index = key
+        .get(swap_key.len()..)
+        .and_then(|index| index.try_into().ok())
+        .context("Malformed state log key")?;
+
+    Ok(u64::from_be_bytes(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_can_be_recovered_from_key() {
+        let swap_key = [1, 2, 3];
+
+        let key = key(&swap_key, 258);
+
+        assert_eq!(key, vec![1, 2, 3, 0, 0, 0, 0, 0, 0, 1, 2]);
+        assert_eq!(index(&swap_key, &key).unwrap(), 258);
+    }
+
+    #[test]
+    fn keys_sort_by_index() {
+        let swap_key = [1, 2, 3];
+
+        assert!(key(&swap_key, 255) < key(&swap_key, 256));
+    }
+}
@@ -0,0 +1,292 @@
+//! Encryption of secrets at rest with a key derived from a passphrase.
+//!
+//! The key is derived with Argon2id, a memory-hard key derivation function,
+//! the data is encrypted with ChaCha20-Poly1305. The parameters of the key
+//! derivation are stored next to the ciphertext so they can be raised in the
+//! future without breaking existing files.
+
+use anyhow::{anyhow, bail, Context, Result};
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Password;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const KEY_LENGTH: u32 = 32;
+
+/// Upper bounds of the key derivation parameters.
+///
+/// The parameters are read from the encrypted file, without bounds a
+/// tampered file could make deriving the key exhaust the memory or run
+/// practically forever.
+const MAX_MEM_COST_KIB: u32 = 1024 * 1024;
+const MAX_TIME_COST: u32 = 32;
+const MAX_LANES: u32 = 16;
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+#[error("Failed to decrypt, the passphrase is wrong or the data is corrupted")]
+pub struct WrongPassphrase;
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+#[error("Key derivation parameters {0:?} exceed the supported maximum")]
+pub struct KdfParamsTooCostly(pub KdfParams);
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+#[error("There is no terminal to ask for a passphrase to encrypt the new seed with")]
+pub struct NoTerminal;
+
+/// Parameters of the Argon2id key derivation.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct KdfParams {
+    pub mem_cost_kib: u32,
+    pub time_cost: u32,
+    pub lanes: u32,
+}
+
+impl KdfParams {
+    fn is_within_bounds(&self) -> bool {
+        self.mem_cost_kib <= MAX_MEM_COST_KIB
+            && self.time_cost <= MAX_TIME_COST
+            && self.lanes <= MAX_LANES
+    }
+}
+
+impl Default for KdfParams {
+    /// 64 MiB of memory and three passes, which takes well below a second on
+    /// current hardware.
+    fn default() -> Self {
+        Self {
+            mem_cost_kib: 64 * 1024,
+            time_cost: 3,
+            lanes: 4,
+        }
+    }
+}
+
+/// Data encrypted with a passphrase.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Encrypted {
+    kdf: KdfParams,
+    salt: [u8; SALT_LENGTH],
+    nonce: [u8; NONCE_LENGTH],
+    ciphertext: Vec<u8>,
+}
+
+impl Encrypted {
+    pub fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Self> {
+        Self::encrypt_with(plaintext, passphrase, KdfParams::default())
+    }
+
+    pub fn encrypt_with(plaintext: &[u8], passphrase: &str, kdf: KdfParams) -> Result<Self> {
+        let mut salt = [0u8; SALT_LENGTH];
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut salt);
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let key = derive_key(passphrase, &salt, kdf)?;
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&key))
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| anyhow!("Failed to encrypt"))?;
+
+        Ok(Self {
+            kdf,
+            salt,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    /// Decrypts the data, fails with [`WrongPassphrase`] if the passphrase
+    /// does not match.
+    pub fn decrypt(&self, passphrase: &str) -> Result<Vec<u8>> {
+        let key = derive_key(passphrase, &self.salt, self.kdf)?;
+
+        let plaintext = ChaCha20Poly1305::new(Key::from_slice(&key))
+            .decrypt(Nonce::from_slice(&self.nonce), self.ciphertext.as_slice())
+            .map_err(|_| WrongPassphrase)?;
+
+        Ok(plaintext)
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], kdf: KdfParams) -> Result<Vec<u8>> {
+    if !kdf.is_within_bounds() {
+        bail!(KdfParamsTooCostly(kdf))
+    }
+
+    let config = argon2::Config {
+        variant: argon2::Variant::Argon2id,
+        version: argon2::Version::Version13,
+        mem_cost: kdf.mem_cost_kib,
+        time_cost: kdf.time_cost,
+        lanes: kdf.lanes,
+        hash_length: KEY_LENGTH,
+        ..argon2::Config::default()
+    };
+
+    argon2::hash_raw(passphrase.as_bytes(), salt, &config)
+        .context("Failed to derive key from passphrase")
+}
+
+/// Where a passphrase is read from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Passphrase {
+    /// Ask the user on the terminal.
+    Prompt,
+    /// Read it from the environment variable with the given name.
+    Env(String),
+    /// Read it from the first line of the given file.
+    File(PathBuf),
+    /// There is no passphrase, new secrets are not encrypted.
+    None,
+}
+
+impl Passphrase {
+    /// Reads the passphrase of an existing secret.
+    pub fn read(&self) -> Result<String> {
+        match self {
+            Passphrase::Prompt => prompt_passphrase(),
+            Passphrase::None => bail!("The passphrase is needed but none was provided"),
+            Passphrase::Env(name) => std::env::var(name)
+                .with_context(|| format!("Failed to read passphrase from ${}", name)),
+            Passphrase::File(path) => {
+                let contents = std::fs::read_to_string(path).with_context(|| {
+                    format!("Failed to read passphrase from {}", path.display())
+                })?;
+
+                Ok(contents.lines().next().unwrap_or_default().to_string())
+            }
+        }
+    }
+
+    /// Reads the passphrase to encrypt a new secret with, `None` if it is not
+    /// to be encrypted.
+    ///
+    /// An empty passphrase means the secret is not to be encrypted. Asking the
+    /// user fails if there is no terminal to ask on, the secret is never
+    /// written unencrypted without being told so.
+    pub fn read_new(&self) -> Result<Option<String>> {
+        let passphrase = match self {
+            Passphrase::Prompt if atty::is(atty::Stream::Stdin) => {
+                prompt_optional_new_passphrase()?
+            }
+            Passphrase::Prompt => bail!(NoTerminal),
+            Passphrase::None => return Ok(None),
+            Passphrase::Env(_) | Passphrase::File(_) => self.read()?,
+        };
+
+        Ok(Some(passphrase).filter(|passphrase| !passphrase.is_empty()))
+    }
+}
+
+/// Asks the user for a new passphrase, which has to be entered twice.
+pub fn prompt_new_passphrase() -> Result<String> {
+    let passphrase = Password::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter passphrase")
+        .with_confirmation("Repeat passphrase", "The passphrases do not match")
+        .interact()?;
+
+    Ok(passphrase)
+}
+
+/// Asks the user for a new passphrase that may be left empty to not encrypt
+/// at all.
+pub fn prompt_optional_new_passphrase() -> Result<String> {
+    let passphrase = Password::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter passphrase to encrypt the seed with (empty for none)")
+        .with_confirmation("Repeat passphrase", "The passphrases do not match")
+        .allow_empty_password(true)
+        .interact()?;
+
+    Ok(passphrase)
+}
+
+/// Asks the user for an existing passphrase.
+pub fn prompt_passphrase() -> Result<String> {
+    let passphrase = Password::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter passphrase")
+        .interact()?;
+
+    Ok(passphrase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Keeps the tests fast, the parameters are irrelevant for correctness.
+    const CHEAP_KDF: KdfParams = KdfParams {
+        mem_cost_kib: 64,
+        time_cost: 1,
+        lanes: 1,
+    };
+
+    #[test]
+    fn decrypts_with_the_same_passphrase() {
+        let encrypted = Encrypted::encrypt_with(b"secret", "passphrase", CHEAP_KDF).unwrap();
+
+        assert_eq!(encrypted.decrypt("passphrase").unwrap(), b"secret");
+    }
+
+    #[test]
+    fn fails_to_decrypt_with_another_passphrase() {
+        let encrypted = Encrypted::encrypt_with(b"secret", "passphrase", CHEAP_KDF).unwrap();
+
+        let error = encrypted.decrypt("other passphrase").unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<WrongPassphrase>().unwrap(),
+            &WrongPassphrase
+        );
+    }
+
+    #[test]
+    fn encrypting_twice_yields_different_ciphertexts() {
+        let first = Encrypted::encrypt_with(b"secret", "passphrase", CHEAP_KDF).unwrap();
+        let second = Encrypted::encrypt_with(b"secret", "passphrase", CHEAP_KDF).unwrap();
+
+        assert_ne!(first.ciphertext, second.ciphertext);
+    }
+
+    #[test]
+    fn refuses_to_decrypt_with_too_costly_kdf_params() {
+        let mut encrypted = Encrypted::encrypt_with(b"secret", "passphrase", CHEAP_KDF).unwrap();
+        encrypted.kdf.mem_cost_kib = u32::MAX;
+
+        let error = encrypted.decrypt("passphrase").unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<KdfParamsTooCostly>().unwrap(),
+            &KdfParamsTooCostly(encrypted.kdf)
+        );
+    }
+
+    #[test]
+    fn default_kdf_params_are_within_bounds() {
+        assert!(KdfParams::default().is_within_bounds());
+    }
+
+    #[test]
+    fn passphrase_file_is_read_without_line_break() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passphrase");
+        std::fs::write(&path, "passphrase\n").unwrap();
+
+        let passphrase = Passphrase::File(path).read().unwrap();
+
+        assert_eq!(passphrase, "passphrase");
+    }
+
+    #[test]
+    fn empty_new_passphrase_does_not_encrypt() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passphrase");
+        std::fs::write(&path, "\n").unwrap();
+
+        assert_eq!(Passphrase::File(path).read_new().unwrap(), None);
+        assert_eq!(Passphrase::None.read_new().unwrap(), None);
+    }
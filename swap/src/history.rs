@@ -0,0 +1,386 @@
+use crate::bitcoin::Txid;
+use crate::database::{Database, LoggedState, SwapDetails};
+use anyhow::Result;
+use prettytable::{cell, row, Table};
+use serde::Serialize;
+use std::fmt::Display;
+use time::{Format as TimeFormat, OffsetDateTime};
+use uuid::Uuid;
+
+/// How the swap history is printed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+impl Format {
+    pub fn from_flags(json: bool, csv: bool) -> Self {
+        match (json, csv) {
+            (true, _) => Format::Json,
+            (false, true) => Format::Csv,
+            (false, false) => Format::Table,
+        }
+    }
+}
+
+/// A single swap of the history.
+///
+/// Amounts are given in their smallest unit so they can be processed
+/// without loss of precision.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Entry {
+    pub swap_id: Uuid,
+    pub state: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub btc_amount_sat: Option<u64>,
+    pub xmr_amount_piconero: Option<u64>,
+    pub price_sat_per_xmr: Option<u64>,
+    pub counterparty_peer_id: Option<String>,
+    pub tx_lock_id: Option<Txid>,
+    pub tx_redeem_id: Option<Txid>,
+    pub tx_cancel_id: Option<Txid>,
+    pub tx_refund_id: Option<Txid>,
+    pub tx_punish_id: Option<Txid>,
+    pub xmr_lock_tx_hash: Option<String>,
+    pub bitcoin_fees_sat: Option<u64>,
+}
+
+impl Entry {
+    pub fn new(
+        swap_id: Uuid,
+        state: impl Display,
+        details: SwapDetails,
+        counterparty_peer_id: Option<String>,
+    ) -> Self {
+        let price = details.price();
+        let bitcoin_fees = details.bitcoin_fees();
+
+        Self {
+            swap_id,
+            state: state.to_string(),
+            started_at: details.started_at.map(format_timestamp),
+            finished_at: details.finished_at.map(format_timestamp),
+            btc_amount_sat: details.btc_amount.map(|amount| amount.as_sat()),
+            xmr_amount_piconero: details.xmr_amount.map(|amount| amount.as_piconero()),
+            price_sat_per_xmr: price.map(|price| price.as_sat()),
+            counterparty_peer_id,
+            tx_lock_id: details.tx_lock_id,
+            tx_redeem_id: details.tx_redeem_id,
+            tx_cancel_id: details.tx_cancel_id,
+            tx_refund_id: details.tx_refund_id,
+            tx_punish_id: details.tx_punish_id,
+            xmr_lock_tx_hash: details.xmr_lock_tx_hash,
+            bitcoin_fees_sat: bitcoin_fees.map(|fees| fees.as_sat()),
+        }
+    }
+
+    fn csv_fields(&self) -> Vec<String> {
+        fn opt(value: &Option<impl ToString>) -> String {
+            value.as_ref().map(ToString::to_string).unwrap_or_default()
+        }
+
+        vec![
+            self.swap_id.to_string(),
+            self.state.clone(),
+            opt(&self.started_at),
+            opt(&self.finished_at),
+            opt(&self.btc_amount_sat),
+            opt(&self.xmr_amount_piconero),
+            opt(&self.price_sat_per_xmr),
+            opt(&self.counterparty_peer_id),
+            opt(&self.tx_lock_id),
+            opt(&self.tx_redeem_id),
+            opt(&self.tx_cancel_id),
+            opt(&self.tx_refund_id),
+            opt(&self.tx_punish_id),
+            opt(&self.xmr_lock_tx_hash),
+            opt(&self.bitcoin_fees_sat),
+        ]
+    }
+}
+
+const CSV_HEADER: [&str; 15] = [
+    "swap_id",
+    "state",
+    "started_at",
+    "finished_at",
+    "btc_amount_sat",
+    "xmr_amount_piconero",
+    "price_sat_per_xmr",
+    "counterparty_peer_id",
+    "tx_lock_id",
+    "tx_redeem_id",
+    "tx_cancel_id",
+    "tx_refund_id",
+    "tx_punish_id",
+    "xmr_lock_tx_hash",
+    "bitcoin_fees_sat",
+];
+
+const STATES_CSV_HEADER: [&str; 3] = ["swap_id", "saved_at", "state"];
+
+/// Collects the history entries of the given swaps from the database.
+pub fn entries<S>(db: &Database, swaps: Vec<(Uuid, S)>) -> Result<Vec<Entry>>
+where
+    S: Display,
+{
+    swaps
+        .into_iter()
+        .map(|(swap_id, state)| {
+            let details = db.get_details(swap_id)?;
+            let counterparty_peer_id = db
+                .get_peer_id(swap_id)
+                .ok()
+                .map(|peer_id| peer_id.to_string());
+
+            Ok(Entry::new(swap_id, state, details, counterparty_peer_id))
+        })
+        .collect()
+}
+
+/// A state a swap went through.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct StateEntry {
+    pub swap_id: Uuid,
+    pub saved_at: Option<String>,
+    pub state: String,
+}
+
+/// Collects all states of a swap from the database, oldest first.
+///
+/// Swaps that were started before states were logged only show their
+/// current state.
+pub fn states(db: &Database, swap_id: Uuid) -> Result<Vec<StateEntry>> {
+    let logged = db.get_state_log(swap_id)?;
+
+    if logged.is_empty() {
+        return Ok(vec![StateEntry {
+            swap_id,
+            saved_at: None,
+            state: db.get_state(swap_id)?.to_string(),
+        }]);
+    }
+
+    Ok(logged
+        .into_iter()
+        .map(|LoggedState { timestamp, state }| StateEntry {
+            swap_id,
+            saved_at: Some(format_timestamp(timestamp)),
+            state: state.to_string(),
+        })
+        .collect())
+}
+
+/// Prints the states of a swap to stdout in the given format.
+pub fn print_states(states: &[StateEntry], format: Format) -> Result<()> {
+    match format {
+        Format::Table => {
+            let mut table = Table::new();
+
+            table.add_row(row!["SAVED AT", "STATE"]);
+
+            for entry in states {
+                table.add_row(row![entry.saved_at.as_deref().unwrap_or("-"), entry.state]);
+            }
+
+            table.printstd();
+        }
+        Format::Json => {
+            println!("{}", serde_json::to_string_pretty(states)?);
+        }
+        Format::Csv => {
+            print!("{}", states_to_csv(states));
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the history to stdout in the given format.
+pub fn print(entries: &[Entry], format: Format) -> Result<()> {
+    match format {
+        Format::Table => {
+            let mut table = Table::new();
+
+            table.add_row(row![
+                "SWAP ID", "STATE", "STARTED", "FINISHED", "PEER", "BTC", "XMR", "PRICE", "FEES",
+                "TX LOCK"
+            ]);
+
+            for entry in entries {
+                table.add_row(row![
+                    entry.swap_id,
+                    entry.state,
+                    entry.started_at.as_deref().unwrap_or("-"),
+                    entry.finished_at.as_deref().unwrap_or("-"),
+                    entry.counterparty_peer_id.as_deref().unwrap_or("-"),
+                    or_dash(entry.btc_amount_sat, |sat| {
+                        crate::bitcoin::Amount::from_sat(sat).to_string()
+                    }),
+                    or_dash(entry.xmr_amount_piconero, |piconero| {
+                        crate::monero::Amount::from_piconero(piconero).to_string()
+                    }),
+                    or_dash(entry.price_sat_per_xmr, |sat| {
+                        crate::bitcoin::Amount::from_sat(sat).to_string()
+                    }),
+                    or_dash(entry.bitcoin_fees_sat, |sat| {
+                        crate::bitcoin::Amount::from_sat(sat).to_string()
+                    }),
+                    or_dash(entry.tx_lock_id, |txid| txid.to_string()),
+                ]);
+            }
+
+            table.printstd();
+        }
+        Format::Json => {
+            println!("{}", serde_json::to_string_pretty(entries)?);
+        }
+        Format::Csv => {
+            print!("{}", to_csv(entries));
+        }
+    }
+
+    Ok(())
+}
+
+fn or_dash<T>(value: Option<T>, display: impl Fn(T) -> String) -> String {
+    value.map(display).unwrap_or_else(|| "-".to_string())
+}
+
+fn to_csv(entries: &[Entry]) -> String {
+    let mut csv = csv_line(CSV_HEADER.iter().map(|field| field.to_string()));
+
+    for entry in entries {
+        csv.push_str(&csv_line(entry.csv_fields().into_iter()));
+    }
+
+    csv
+}
+
+fn states_to_csv(states: &[StateEntry]) -> String {
+    let mut csv = csv_line(STATES_CSV_HEADER.iter().map(|field| field.to_string()));
+
+    for entry in states {
+        csv.push_str(&csv_line(
+            vec![
+                entry.swap_id.to_string(),
+                entry.saved_at.clone().unwrap_or_default(),
+                entry.state.clone(),
+            ]
+            .into_iter(),
+        ));
+    }
+
+    csv
+}
+
+fn csv_line(fields: impl Iterator<Item = String>) -> String {
+    let mut line = fields.map(escape_csv_field).collect::<Vec<_>>().join(",");
+    line.push('\n');
+
+    line
+}
+
+fn escape_csv_field(field: String) -> String {
+    if field.contains(|c: char| matches!(c, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    OffsetDateTime::from_unix_timestamp(timestamp).format(TimeFormat::Rfc3339)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::Amount;
+    use crate::database::Role;
+    use crate::monero;
+
+    #[test]
+    fn entry_contains_formatted_details() {
+        let swap_id = Uuid::new_v4();
+        let details = SwapDetails {
+            role: Some(Role::Bob),
+            started_at: Some(1_620_000_000),
+            btc_amount: Some(Amount::from_sat(1_000_000)),
+            xmr_amount: Some(monero::Amount::from_piconero(250_000_000_000)),
+            tx_lock_fee: Some(Amount::from_sat(500)),
+            ..SwapDetails::default()
+        };
+
+        let entry = Entry::new(swap_id, "Started", details, None);
+
+        assert_eq!(
+            entry.started_at.as_deref(),
+            Some("2021-05-03T00:00:00+00:00")
+        );
+        assert_eq!(entry.finished_at, None);
+        assert_eq!(entry.btc_amount_sat, Some(1_000_000));
+        assert_eq!(entry.price_sat_per_xmr, Some(4_000_000));
+        assert_eq!(entry.bitcoin_fees_sat, Some(500));
+    }
+
+    #[test]
+    fn csv_has_a_header_and_one_line_per_swap() {
+        let swap_id = Uuid::new_v4();
+        let entry = Entry::new(swap_id, "Done: SafelyAborted", SwapDetails::default(), None);
+
+        let csv = to_csv(&[entry]);
+        let lines = csv.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], CSV_HEADER.join(","));
+        assert_eq!(
+            lines[1],
+            format!("{},Done: SafelyAborted,,,,,,,,,,,,,", swap_id)
+        );
+    }
+
+    #[test]
+    fn states_csv_has_a_header_and_one_line_per_state() {
+        let swap_id = Uuid::new_v4();
+        let states = vec![
+            StateEntry {
+                swap_id,
+                saved_at: Some(format_timestamp(1_620_000_000)),
+                state: "quote has been requested".to_string(),
+            },
+            StateEntry {
+                swap_id,
+                saved_at: None,
+                state: "btc is locked".to_string(),
+            },
+        ];
+
+        let csv = states_to_csv(&states);
+        let lines = csv.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], STATES_CSV_HEADER.join(","));
+        assert_eq!(
+            lines[1],
+            format!(
+                "{},2021-05-03T00:00:00+00:00,quote has been requested",
+                swap_id
+            )
+        );
+        assert_eq!(lines[2], format!("{},,btc is locked", swap_id));
+    }
+
+    #[test]
+    fn csv_fields_with_separators_are_quoted() {
+        assert_eq!(escape_csv_field("plain".to_string()), "plain");
+        assert_eq!(escape_csv_field("a,b".to_string()), "\"a,b\"");
+        assert_eq!(
+            escape_csv_field("say \"hi\"".to_string()),
+            "\"say \"\"hi\"\"\""
+        );
+    }
+}
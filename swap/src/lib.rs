@@ -20,8 +20,10 @@ pub mod asb;
 pub mod bitcoin;
 pub mod cli;
 pub mod database;
+pub mod encryption;
 pub mod env;
 pub mod fs;
+pub mod history;
 pub mod kraken;
 pub mod monero;
 pub mod network;
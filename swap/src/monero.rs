@@ -276,6 +276,37 @@ pub mod monero_amount {
     }
 }
 
+/// (De)serializes amounts as XMR.
+pub mod monero_amount_as_xmr {
+    use crate::monero::Amount;
+    use rust_decimal::prelude::ToPrimitive;
+    use rust_decimal::Decimal;
+    use serde::ser::Error as _;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(x: &Amount, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut xmr = Decimal::from(x.as_piconero());
+        xmr.set_scale(12).map_err(S::Error::custom)?;
+        let xmr = xmr
+            .to_f64()
+            .ok_or_else(|| S::Error::custom("amount not representable as f64"))?;
+
+        s.serialize_f64(xmr)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Amount, <D as Deserializer<'de>>::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let xmr = f64::deserialize(deserializer)?;
+
+        Amount::from_monero(xmr).map_err(de::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -347,6 +378,9 @@ mod tests {
     #[derive(Debug, Serialize, Deserialize, PartialEq)]
     pub struct MoneroAmount(#[serde(with = "monero_amount")] crate::monero::Amount);
 
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    pub struct MoneroAmountAsXmr(#[serde(with = "monero_amount_as_xmr")] crate::monero::Amount);
+
     #[test]
     fn serde_monero_private_key() {
         let key = MoneroPrivateKey(monero::PrivateKey::from_scalar(
@@ -364,4 +398,14 @@ mod tests {
         let decoded: MoneroAmount = serde_cbor::from_slice(&encoded).unwrap();
         assert_eq!(amount, decoded);
     }
+
+    #[test]
+    fn serde_monero_amount_as_xmr() {
+        let amount = MoneroAmountAsXmr(crate::monero::Amount::from_piconero(1_500_000_000_000));
+        let encoded = serde_json::to_string(&amount).unwrap();
+        assert_eq!(encoded, "1.5");
+
+        let decoded: MoneroAmountAsXmr = serde_json::from_str(&encoded).unwrap();
+        assert_eq!(amount, decoded);
+    }
 }
@@ -202,6 +202,44 @@ impl Wallet {
         ))
     }
 
+    /// Transfers the amount from the primary account to the address.
+    pub async fn transfer_to(&self, address: Address, amount: Amount) -> Result<TransferProof> {
+        let res = self
+            .inner
+            .lock()
+            .await
+            .transfer_single(0, amount.as_piconero(), &address.to_string())
+            .await?;
+
+        tracing::debug!(%amount, to = %address, tx_id = %res.tx_hash, "Sent transfer");
+
+        Ok(TransferProof::new(
+            TxHash(res.tx_hash),
+            res.tx_key
+                .context("Missing tx_key in `transfer` response")?,
+        ))
+    }
+
+    /// Returns the confirmations of a transfer to the address.
+    pub async fn transfer_confirmations(
+        &self,
+        transfer_proof: &TransferProof,
+        address: Address,
+    ) -> Result<u64> {
+        let check = self
+            .inner
+            .lock()
+            .await
+            .check_tx_key(
+                transfer_proof.tx_hash().0,
+                transfer_proof.tx_key().to_string(),
+                address.to_string(),
+            )
+            .await?;
+
+        Ok(check.confirmations)
+    }
+
     pub async fn watch_for_transfer(&self, request: WatchRequest) -> Result<()> {
         let WatchRequest {
             conf_target,
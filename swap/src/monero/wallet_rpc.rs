@@ -120,7 +120,14 @@ impl WalletRpc {
         Ok(monero_wallet_rpc)
     }
 
-    pub async fn run(&self, network: Network, daemon_address: &str) -> Result<WalletRpcProcess> {
+    /// Connects to the daemon through the Tor socks5 proxy if its port is
+    /// given.
+    pub async fn run(
+        &self,
+        network: Network,
+        daemon_address: &str,
+        tor_socks5_port: Option<u16>,
+    ) -> Result<WalletRpcProcess> {
         let port = tokio::net::TcpListener::bind("127.0.0.1:0")
             .await?
             .local_addr()?
@@ -143,11 +150,17 @@ impl WalletRpc {
             }
         };
 
+        let proxy_flag = match tor_socks5_port {
+            Some(port) => vec!["--proxy".to_string(), format!("127.0.0.1:{}", port)],
+            None => vec![],
+        };
+
         let mut child = Command::new(self.exec_path())
             .env("LANG", "en_AU.UTF-8")
             .stdout(Stdio::piped())
             .kill_on_drop(true)
             .args(network_flag)
+            .args(proxy_flag)
             .arg("--daemon-address")
             .arg(daemon_address)
             .arg("--rpc-bind-port")
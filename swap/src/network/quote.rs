@@ -1,13 +1,19 @@
-use crate::bitcoin;
+use crate::asb::Rate;
 use crate::network::json_pull_codec::JsonPullCodec;
+use crate::network::spot_price::TimelockRanges;
 use crate::protocol::{alice, bob};
+use crate::{bitcoin, monero};
+use anyhow::{bail, Context, Result};
 use libp2p::core::ProtocolName;
+use libp2p::identity::{Keypair, PublicKey};
 use libp2p::request_response::{
     ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
     RequestResponseMessage,
 };
-use libp2p::PeerId;
+use libp2p::{Multiaddr, PeerId};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 const PROTOCOL: &str = "/comit/xmr/btc/bid-quote/1.0.0";
 type OutEvent = RequestResponseEvent<(), BidQuote>;
@@ -36,6 +42,87 @@ pub struct BidQuote {
     /// The maximum quantity the maker is willing to buy.
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     pub max_quantity: bitcoin::Amount,
+    /// The onion addresses the maker can be reached at.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<Multiaddr>,
+    /// The timelocks the maker accepts in a spot price request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timelocks: Option<TimelockRanges>,
+    /// Set if the maker honours the price until the quote expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binding: Option<Binding>,
+}
+
+/// Makes a quote binding, the maker sells at the quoted price in a spot price
+/// request that references the quote by its id.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub id: Uuid,
+    /// Unix timestamp until which the quote is honoured.
+    pub valid_until: i64,
+    /// The protobuf encoded libp2p public key of the maker.
+    pub public_key: Vec<u8>,
+    /// Signature over the id, the expiry, the price and the quantities.
+    pub signature: Vec<u8>,
+}
+
+impl BidQuote {
+    /// Makes the quote binding until `valid_until` by signing it with the
+    /// libp2p identity of the maker.
+    pub fn sign(mut self, identity: &Keypair, id: Uuid, valid_until: i64) -> Result<Self> {
+        let signature = identity
+            .sign(&self.signed_message(id, valid_until))
+            .context("Failed to sign quote")?;
+
+        self.binding = Some(Binding {
+            id,
+            valid_until,
+            public_key: identity.public().into_protobuf_encoding(),
+            signature,
+        });
+
+        Ok(self)
+    }
+
+    /// Checks that the binding quote was signed by the given peer.
+    pub fn verify(&self, peer: PeerId) -> Result<&Binding> {
+        let binding = self.binding.as_ref().context("Quote is not binding")?;
+
+        let public_key = PublicKey::from_protobuf_encoding(&binding.public_key)
+            .context("Failed to decode public key of binding quote")?;
+        let signer = PeerId::from(public_key.clone());
+        if signer != peer {
+            bail!(
+                "Binding quote {} was signed by {} instead of {}",
+                binding.id,
+                signer,
+                peer
+            );
+        }
+
+        let message = self.signed_message(binding.id, binding.valid_until);
+        if !public_key.verify(&message, &binding.signature) {
+            bail!("Binding quote {} has an invalid signature", binding.id);
+        }
+
+        Ok(binding)
+    }
+
+    /// The amount of XMR the quoted price buys for the given amount of BTC.
+    pub fn xmr_for(&self, btc: bitcoin::Amount) -> Result<monero::Amount> {
+        Rate::new(self.price, Decimal::from(0u64)).sell_quote(btc)
+    }
+
+    fn signed_message(&self, id: Uuid, valid_until: i64) -> Vec<u8> {
+        let mut message = PROTOCOL.as_bytes().to_vec();
+        message.extend_from_slice(id.as_bytes());
+        message.extend_from_slice(&valid_until.to_be_bytes());
+        message.extend_from_slice(&self.price.as_sat().to_be_bytes());
+        message.extend_from_slice(&self.min_quantity.as_sat().to_be_bytes());
+        message.extend_from_slice(&self.max_quantity.as_sat().to_be_bytes());
+
+        message
+    }
 }
 
 /// Constructs a new instance of the `quote` behaviour to be used by Alice.
@@ -85,3 +172,47 @@ impl From<(PeerId, Message)> for bob::OutEvent {
     }
 }
 crate::impl_from_rr_event!(OutEvent, bob::OutEvent, PROTOCOL);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_quote_is_verified_against_the_signer() {
+        let identity = Keypair::generate_ed25519();
+        let quote = quote().sign(&identity, Uuid::new_v4(), 1_000).unwrap();
+
+        assert!(quote.verify(PeerId::from(identity.public())).is_ok());
+        assert!(quote
+            .verify(PeerId::from(Keypair::generate_ed25519().public()))
+            .is_err());
+    }
+
+    #[test]
+    fn tampered_binding_quote_is_rejected() {
+        let identity = Keypair::generate_ed25519();
+        let mut quote = quote().sign(&identity, Uuid::new_v4(), 1_000).unwrap();
+
+        quote.price = bitcoin::Amount::from_btc(0.001).unwrap();
+
+        assert!(quote.verify(PeerId::from(identity.public())).is_err());
+    }
+
+    #[test]
+    fn quote_without_binding_is_not_verified() {
+        let identity = Keypair::generate_ed25519();
+
+        assert!(quote().verify(PeerId::from(identity.public())).is_err());
+    }
+
+    fn quote() -> BidQuote {
+        BidQuote {
+            price: bitcoin::Amount::from_btc(0.01).unwrap(),
+            min_quantity: bitcoin::Amount::from_btc(0.001).unwrap(),
+            max_quantity: bitcoin::Amount::from_btc(0.1).unwrap(),
+            addresses: vec![],
+            timelocks: None,
+            binding: None,
+        }
+    }
+}
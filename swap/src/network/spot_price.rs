@@ -1,8 +1,11 @@
-use crate::monero;
+use crate::bitcoin::{CancelTimelock, PunishTimelock};
 use crate::network::cbor_request_response::CborCodec;
+use crate::{env, monero};
 use libp2p::core::ProtocolName;
 use libp2p::request_response::{RequestResponse, RequestResponseEvent, RequestResponseMessage};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use uuid::Uuid;
 
 pub const PROTOCOL: &str = "/comit/xmr/btc/spot-price/1.0.0";
 pub type OutEvent = RequestResponseEvent<Request, Response>;
@@ -33,6 +36,13 @@ pub struct Request {
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     pub btc: bitcoin::Amount,
     pub blockchain_network: BlockchainNetwork,
+    /// The timelocks proposed for the swap, the defaults of the network if
+    /// none are given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timelocks: Option<Timelocks>,
+    /// The binding quote whose price the request accepts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_id: Option<Uuid>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -64,11 +74,123 @@ pub enum Error {
         cli: BlockchainNetwork,
         asb: BlockchainNetwork,
     },
+    TradingLimitReached(TradingLimit),
+    TimelocksNotAccepted {
+        proposed: Timelocks,
+        accepted: TimelockRanges,
+    },
+    /// The referenced quote expired, was already used or is unknown.
+    QuoteExpired {
+        quote_id: Uuid,
+    },
     /// To be used for errors that cannot be explained on the CLI side (e.g.
     /// rate update problems on the seller side)
     Other,
 }
 
+/// A trading limit of the ASB that would be exceeded by a swap.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum TradingLimit {
+    DailyVolume {
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        max: bitcoin::Amount,
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        remaining: bitcoin::Amount,
+    },
+    ConcurrentSwaps {
+        max: u64,
+    },
+    DailyVolumePerPeer {
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        max: bitcoin::Amount,
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        remaining: bitcoin::Amount,
+    },
+}
+
+impl fmt::Display for TradingLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TradingLimit::DailyVolume { max, remaining } => write!(
+                f,
+                "the daily trading volume of {} is reached, only {} can be bought",
+                max, remaining
+            ),
+            TradingLimit::ConcurrentSwaps { max } => {
+                write!(f, "the maximum of {} swaps in progress is reached", max)
+            }
+            TradingLimit::DailyVolumePerPeer { max, remaining } => write!(
+                f,
+                "the daily trading volume of {} per buyer is reached, only {} can be bought",
+                max, remaining
+            ),
+        }
+    }
+}
+
+/// The timelocks of a swap.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Timelocks {
+    pub cancel: CancelTimelock,
+    pub punish: PunishTimelock,
+}
+
+impl From<env::Config> for Timelocks {
+    fn from(env_config: env::Config) -> Self {
+        Self {
+            cancel: env_config.bitcoin_cancel_timelock,
+            punish: env_config.bitcoin_punish_timelock,
+        }
+    }
+}
+
+impl fmt::Display for Timelocks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cancel after {}, punish after {}",
+            self.cancel, self.punish
+        )
+    }
+}
+
+/// The timelocks the ASB accepts, all bounds are inclusive.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TimelockRanges {
+    pub min_cancel: CancelTimelock,
+    pub max_cancel: CancelTimelock,
+    pub min_punish: PunishTimelock,
+    pub max_punish: PunishTimelock,
+}
+
+impl TimelockRanges {
+    /// Only accepts the given timelocks.
+    pub fn exactly(timelocks: Timelocks) -> Self {
+        Self {
+            min_cancel: timelocks.cancel,
+            max_cancel: timelocks.cancel,
+            min_punish: timelocks.punish,
+            max_punish: timelocks.punish,
+        }
+    }
+
+    pub fn contains(&self, timelocks: Timelocks) -> bool {
+        (self.min_cancel..=self.max_cancel).contains(&timelocks.cancel)
+            && (self.min_punish..=self.max_punish).contains(&timelocks.punish)
+    }
+}
+
+impl fmt::Display for TimelockRanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cancel after {} to {}, punish after {} to {}",
+            self.min_cancel, self.max_cancel, self.min_punish, self.max_punish
+        )
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub struct BlockchainNetwork {
     #[serde(with = "crate::bitcoin::network")]
@@ -131,8 +253,63 @@ mod tests {
             .unwrap();
         assert_eq!(error, serialized);
 
+        let error = r#"{"Error":{"TradingLimitReached":{"DailyVolume":{"max":0,"remaining":0}}}}"#
+            .to_string();
+        let serialized = serde_json::to_string(&Response::Error(Error::TradingLimitReached(
+            TradingLimit::DailyVolume {
+                max: Default::default(),
+                remaining: Default::default(),
+            },
+        )))
+        .unwrap();
+        assert_eq!(error, serialized);
+
+        let error = r#"{"Error":{"TimelocksNotAccepted":{"proposed":{"cancel":6,"punish":6},"accepted":{"min_cancel":12,"max_cancel":72,"min_punish":12,"max_punish":72}}}}"#.to_string();
+        let serialized = serde_json::to_string(&Response::Error(Error::TimelocksNotAccepted {
+            proposed: Timelocks {
+                cancel: CancelTimelock::new(6),
+                punish: PunishTimelock::new(6),
+            },
+            accepted: TimelockRanges {
+                min_cancel: CancelTimelock::new(12),
+                max_cancel: CancelTimelock::new(72),
+                min_punish: PunishTimelock::new(12),
+                max_punish: PunishTimelock::new(72),
+            },
+        }))
+        .unwrap();
+        assert_eq!(error, serialized);
+
+        let error =
+            r#"{"Error":{"QuoteExpired":{"quote_id":"27b6e8bb-2d0d-4c8b-8bb4-2b8ad1c2d5e1"}}}"#
+                .to_string();
+        let serialized = serde_json::to_string(&Response::Error(Error::QuoteExpired {
+            quote_id: "27b6e8bb-2d0d-4c8b-8bb4-2b8ad1c2d5e1".parse().unwrap(),
+        }))
+        .unwrap();
+        assert_eq!(error, serialized);
+
         let error = r#"{"Error":"Other"}"#.to_string();
         let serialized = serde_json::to_string(&Response::Error(Error::Other)).unwrap();
         assert_eq!(error, serialized);
     }
+
+    #[test]
+    fn timelock_ranges_are_inclusive() {
+        let ranges = TimelockRanges {
+            min_cancel: CancelTimelock::new(12),
+            max_cancel: CancelTimelock::new(72),
+            min_punish: PunishTimelock::new(6),
+            max_punish: PunishTimelock::new(72),
+        };
+        let timelocks = |cancel, punish| Timelocks {
+            cancel: CancelTimelock::new(cancel),
+            punish: PunishTimelock::new(punish),
+        };
+
+        assert!(ranges.contains(timelocks(12, 6)));
+        assert!(ranges.contains(timelocks(72, 72)));
+        assert!(!ranges.contains(timelocks(11, 6)));
+        assert!(!ranges.contains(timelocks(12, 73)));
+    }
 }
@@ -1,10 +1,11 @@
+use crate::network::tor_transport::TorListenTransport;
 use crate::protocol::alice::event_loop::LatestRate;
 use crate::protocol::{alice, bob};
 use crate::seed::Seed;
 use crate::{asb, cli, env, monero, tor};
 use anyhow::Result;
 use libp2p::swarm::SwarmBuilder;
-use libp2p::{PeerId, Swarm};
+use libp2p::{identity, PeerId, Swarm};
 use std::fmt::Debug;
 
 #[allow(clippy::too_many_arguments)]
@@ -17,6 +18,7 @@ pub fn asb<LR>(
     latest_rate: LR,
     resume_only: bool,
     env_config: env::Config,
+    maybe_tor_transport: Option<TorListenTransport>,
 ) -> Result<Swarm<alice::Behaviour<LR>>>
 where
     LR: LatestRate + Send + 'static + Debug,
@@ -32,7 +34,7 @@ where
     );
 
     let identity = seed.derive_libp2p_identity();
-    let transport = asb::transport::new(&identity)?;
+    let transport = asb::transport::new(&identity, maybe_tor_transport)?;
     let peer_id = identity.public().into_peer_id();
 
     let swarm = SwarmBuilder::new(transport, behaviour, peer_id)
@@ -44,20 +46,22 @@ where
     Ok(swarm)
 }
 
+/// Dials through Tor if it is running, with `tor_only` the swarm cannot be
+/// created without it.
 pub async fn cli(
-    seed: &Seed,
+    identity: identity::Keypair,
     alice: PeerId,
     tor_socks5_port: u16,
+    tor_only: bool,
 ) -> Result<Swarm<bob::Behaviour>> {
-    let maybe_tor_socks5_port = match tor::Client::new(tor_socks5_port).assert_tor_running().await {
-        Ok(()) => Some(tor_socks5_port),
-        Err(_) => None,
+    let transport = match tor::Client::new(tor_socks5_port).assert_tor_running().await {
+        Ok(()) if tor_only => cli::transport::tor_only(&identity, tor_socks5_port)?,
+        Ok(()) => cli::transport::new(&identity, Some(tor_socks5_port))?,
+        Err(error) if tor_only => return Err(error.context("Tor is required but not running")),
+        Err(_) => cli::transport::new(&identity, None)?,
     };
 
     let behaviour = bob::Behaviour::new(alice);
-
-    let identity = seed.derive_libp2p_identity();
-    let transport = cli::transport::new(&identity, maybe_tor_socks5_port)?;
     let peer_id = identity.public().into_peer_id();
 
     let swarm = SwarmBuilder::new(transport, behaviour, peer_id)
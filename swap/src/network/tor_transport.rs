@@ -1,14 +1,19 @@
+use crate::tor;
 use anyhow::Result;
 use data_encoding::BASE32;
 use futures::future::{BoxFuture, FutureExt, Ready};
+use futures::stream::{BoxStream, StreamExt};
 use libp2p::core::multiaddr::{Multiaddr, Protocol};
-use libp2p::core::transport::TransportError;
+use libp2p::core::transport::{ListenerEvent, TransportError};
 use libp2p::core::Transport;
 use libp2p::tcp::tokio::{Tcp, TcpStream};
-use libp2p::tcp::TcpListenStream;
+use libp2p::tcp::{TcpListenStream, TokioTcpConfig};
 use std::io;
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::sync::Mutex;
 use tokio_socks::tcp::Socks5Stream;
+use torut::onion::TorSecretKeyV3;
 
 /// A [`Transport`] that can dial onion addresses through a running Tor daemon.
 #[derive(Clone)]
@@ -57,6 +62,208 @@ impl Transport for TorDialOnlyTransport {
     }
 }
 
+/// A [`Transport`] that listens on the onion addresses of a hidden service.
+///
+/// Listening on `/onion3/{ADDRESS}:{PORT}` binds a TCP listener to a random
+/// port on localhost and registers the hidden service through the control
+/// port of the Tor daemon, forwarding the onion port to the local one. The
+/// listener is therefore only reachable through Tor.
+///
+/// All listeners share the same hidden service. Registering the service
+/// replaces an earlier registration, so every registration includes the ports
+/// of all listeners. Tor forgets the service when it restarts, see
+/// [`TorListenTransport::supervise`].
+#[derive(Clone)]
+pub struct TorListenTransport {
+    tor_client: tor::Client,
+    key: TorSecretKeyV3,
+    ports: Arc<Mutex<Vec<(u16, SocketAddr)>>>,
+}
+
+impl TorListenTransport {
+    pub fn new(tor_client: tor::Client, key: TorSecretKeyV3) -> Self {
+        Self {
+            tor_client,
+            key,
+            ports: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    fn onion_address(&self) -> String {
+        self.key
+            .public()
+            .get_onion_address()
+            .get_address_without_dot_onion()
+    }
+
+    /// Registers the hidden service with the given port forwarded to the
+    /// port of the local listen address in addition to the ports that are
+    /// already forwarded.
+    async fn add_port(&self, onion_port: u16, local_addr: &Multiaddr) -> io::Result<()> {
+        let service = (onion_port, local_socket_addr(local_addr)?);
+
+        let mut ports = self.ports.lock().await;
+        let mut services = ports.clone();
+        services.push(service);
+
+        self.register(&services).await?;
+        *ports = services;
+
+        Ok(())
+    }
+
+    /// Registers the hidden service without the port forwarded to the port of
+    /// the expired local listen address.
+    async fn remove_port(&self, onion_port: u16, local_addr: &Multiaddr) -> io::Result<()> {
+        let service = (onion_port, local_socket_addr(local_addr)?);
+
+        let mut ports = self.ports.lock().await;
+        let services = ports
+            .iter()
+            .copied()
+            .filter(|forwarded| *forwarded != service)
+            .collect::<Vec<_>>();
+
+        self.register(&services).await?;
+        *ports = services;
+
+        Ok(())
+    }
+
+    /// Registers the hidden service with exactly the given ports, the service
+    /// is removed if there are none.
+    async fn register(
+        &self,
+        services: &[(u16, SocketAddr)],
+    ) -> io::Result<tor::AuthenticatedClient> {
+        let mut client = self
+            .tor_client
+            .into_authenticated_client()
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
+
+        let result = if services.is_empty() {
+            client.remove_service(&self.key).await
+        } else {
+            client.add_services(services, &self.key).await
+        };
+        result.map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        Ok(client)
+    }
+
+    /// Registers the hidden service again with the ports of all listeners
+    /// whenever the control connection is lost until the ASB is stopped.
+    ///
+    /// The service is registered detached and survives the control
+    /// connection, but Tor forgets it whenever Tor itself restarts.
+    pub async fn supervise(self) {
+        let client = self.tor_client.into_authenticated_client().await.ok();
+        let this = &self;
+
+        tor::supervise("hidden service", client, || async move {
+            let ports = this.ports.lock().await;
+            this.register(&ports).await
+        })
+        .await
+    }
+}
+
+/// The local address a port of the hidden service is forwarded to.
+fn local_socket_addr(local_addr: &Multiaddr) -> io::Result<SocketAddr> {
+    let local_port = local_addr
+        .iter()
+        .find_map(|protocol| match protocol {
+            Protocol::Tcp(port) => Some(port),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("No TCP port in listen address {}", local_addr),
+            )
+        })?;
+
+    Ok(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), local_port))
+}
+
+impl Transport for TorListenTransport {
+    type Output = TcpStream;
+    type Error = io::Error;
+    type Listener =
+        BoxStream<'static, Result<ListenerEvent<Self::ListenerUpgrade, Self::Error>, Self::Error>>;
+    type ListenerUpgrade = Ready<Result<Self::Output, Self::Error>>;
+    type Dial = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let onion_port = match addr.iter().next() {
+            Some(Protocol::Onion3(onion)) => {
+                let onion_address = BASE32.encode(onion.hash()).to_lowercase();
+                if onion_address != self.onion_address() {
+                    return Err(TransportError::Other(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "Cannot listen on {}, the onion address of this node is {}",
+                            addr,
+                            self.onion_address()
+                        ),
+                    )));
+                }
+
+                onion.port()
+            }
+            _ => return Err(TransportError::MultiaddrNotSupported(addr)),
+        };
+
+        let localhost = Multiaddr::empty()
+            .with(Protocol::Ip4(Ipv4Addr::LOCALHOST))
+            .with(Protocol::Tcp(0));
+        let tcp_listener = TokioTcpConfig::new().nodelay(true).listen_on(localhost)?;
+
+        let listener = tcp_listener.then(move |event| {
+            let transport = self.clone();
+            let addr = addr.clone();
+
+            async move {
+                match event? {
+                    ListenerEvent::NewAddress(local_addr) => {
+                        transport.add_port(onion_port, &local_addr).await?;
+                        tracing::debug!(%local_addr, onion_addr = %addr, "Registered hidden service");
+
+                        Ok(ListenerEvent::NewAddress(addr))
+                    }
+                    ListenerEvent::Upgrade {
+                        upgrade,
+                        remote_addr,
+                        ..
+                    } => Ok(ListenerEvent::Upgrade {
+                        upgrade,
+                        local_addr: addr,
+                        remote_addr,
+                    }),
+                    ListenerEvent::AddressExpired(local_addr) => {
+                        transport.remove_port(onion_port, &local_addr).await?;
+                        tracing::debug!(%local_addr, onion_addr = %addr, "Removed port from hidden service");
+
+                        Ok(ListenerEvent::AddressExpired(addr))
+                    }
+                    ListenerEvent::Error(error) => Ok(ListenerEvent::Error(error)),
+                }
+            }
+        });
+
+        Ok(listener.boxed())
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        Err(TransportError::MultiaddrNotSupported(addr))
+    }
+
+    fn address_translation(&self, _: &Multiaddr, _: &Multiaddr) -> Option<Multiaddr> {
+        None
+    }
+}
+
 /// Formats the given [`Multiaddr`] as an "address" string.
 ///
 /// For our purposes, we define an address as {HOST}(.{TLD}):{PORT}. This format
@@ -93,6 +300,35 @@ fn fmt_as_address_string(multi: Multiaddr) -> Result<String, TransportError<io::
 pub mod test {
     use super::*;
 
+    #[test]
+    fn listening_on_foreign_onion_address_fails() {
+        let transport = TorListenTransport::new(
+            tor::Client::default(),
+            crate::seed::Seed::random().unwrap().derive_torv3_key(),
+        );
+        let address = "/onion3/oarchy4tamydxcitaki6bc2v4leza6v35iezmu2chg2bap63sv6f2did:1024";
+
+        let result = transport.listen_on(address.parse().unwrap());
+
+        assert!(matches!(result, Err(TransportError::Other(_))));
+    }
+
+    #[test]
+    fn listening_on_tcp_address_is_not_supported() {
+        let transport = TorListenTransport::new(
+            tor::Client::default(),
+            crate::seed::Seed::random().unwrap().derive_torv3_key(),
+        );
+        let address = "/ip4/127.0.0.1/tcp/7777";
+
+        let result = transport.listen_on(address.parse().unwrap());
+
+        assert!(matches!(
+            result,
+            Err(TransportError::MultiaddrNotSupported(_))
+        ));
+    }
+
     #[test]
     fn test_tor_address_string() {
         let address =
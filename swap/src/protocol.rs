@@ -32,6 +32,12 @@ pub struct Message0 {
     tx_refund_fee: bitcoin::Amount,
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_cancel_fee: bitcoin::Amount,
+    /// Not sent by older versions, which always use the defaults of the
+    /// network.
+    #[serde(default)]
+    cancel_timelock: Option<bitcoin::CancelTimelock>,
+    #[serde(default)]
+    punish_timelock: Option<bitcoin::PunishTimelock>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -47,6 +53,12 @@ pub struct Message1 {
     tx_redeem_fee: bitcoin::Amount,
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_punish_fee: bitcoin::Amount,
+    /// Not sent by older versions, which always use the defaults of the
+    /// network.
+    #[serde(default)]
+    cancel_timelock: Option<bitcoin::CancelTimelock>,
+    #[serde(default)]
+    punish_timelock: Option<bitcoin::PunishTimelock>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -65,3 +77,76 @@ pub struct Message4 {
     tx_punish_sig: bitcoin::Signature,
     tx_cancel_sig: bitcoin::Signature,
 }
+
+/// Runs the execution setup between Alice and Bob with funded test wallets,
+/// up to the point where Bob can lock the Bitcoin.
+#[cfg(test)]
+pub async fn execution_setup() -> (alice::State3, bob::State2) {
+    use crate::env::{GetConfig, Regtest};
+    use rand::rngs::OsRng;
+
+    let alice_wallet = bitcoin::Wallet::new_funded_default_fees(bitcoin::Amount::ONE_BTC.as_sat());
+    let bob_wallet = bitcoin::Wallet::new_funded_default_fees(bitcoin::Amount::ONE_BTC.as_sat());
+    let spending_fee = bitcoin::Amount::from_sat(1_000);
+    let btc_amount = bitcoin::Amount::from_sat(500_000);
+    let xmr_amount = monero::Amount::from_piconero(10000);
+
+    let tx_redeem_fee = alice_wallet
+        .estimate_fee(bitcoin::TxRedeem::weight(), btc_amount)
+        .await
+        .unwrap();
+    let tx_punish_fee = alice_wallet
+        .estimate_fee(bitcoin::TxPunish::weight(), btc_amount)
+        .await
+        .unwrap();
+    let redeem_address = alice_wallet.new_address().await.unwrap();
+    let punish_address = alice_wallet.new_address().await.unwrap();
+
+    let config = Regtest::get_config();
+    let alice_state0 = alice::State0::new(
+        btc_amount,
+        xmr_amount,
+        config.bitcoin_cancel_timelock,
+        config.bitcoin_punish_timelock,
+        redeem_address,
+        punish_address,
+        tx_redeem_fee,
+        tx_punish_fee,
+        &mut OsRng,
+    )
+    .unwrap();
+
+    let bob_state0 = bob::State0::new(
+        Uuid::new_v4(),
+        &mut OsRng,
+        btc_amount,
+        xmr_amount,
+        config.bitcoin_cancel_timelock,
+        config.bitcoin_punish_timelock,
+        bob_wallet.new_address().await.unwrap(),
+        config.monero_finality_confirmations,
+        spending_fee,
+        spending_fee,
+    );
+
+    let message0 = bob_state0.next_message();
+
+    let (_, alice_state1) = alice_state0.receive(message0, &config).unwrap();
+    let alice_message1 = alice_state1.next_message();
+
+    let bob_state1 = bob_state0
+        .receive(&bob_wallet, alice_message1, &config)
+        .await
+        .unwrap();
+    let bob_message2 = bob_state1.next_message();
+
+    let alice_state2 = alice_state1.receive(bob_message2).unwrap();
+    let alice_message3 = alice_state2.next_message();
+
+    let bob_state2 = bob_state1.receive(alice_message3).unwrap();
+    let bob_message4 = bob_state2.next_message();
+
+    let alice_state3 = alice_state2.receive(bob_message4).unwrap();
+
+    (alice_state3, bob_state2)
+}
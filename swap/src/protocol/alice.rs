@@ -1,5 +1,6 @@
 //! Run an XMR/BTC swap in the role of Alice.
 //! Alice holds XMR and wishes receive BTC.
+use crate::asb::limits::Trades;
 use crate::database::Database;
 use crate::env::Config;
 use crate::{bitcoin, monero};
@@ -33,4 +34,7 @@ pub struct Swap {
     pub env_config: Config,
     pub swap_id: Uuid,
     pub db: Arc<Database>,
+    /// The trades the limits are checked against, updated whenever the state
+    /// is saved.
+    pub trades: Trades,
 }
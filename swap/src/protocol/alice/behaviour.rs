@@ -1,4 +1,5 @@
 use crate::network::quote::BidQuote;
+use crate::network::spot_price::Timelocks;
 use crate::network::{encrypted_signature, quote, transfer_proof};
 use crate::protocol::alice::event_loop::LatestRate;
 use crate::protocol::alice::{execution_setup, spot_price, State3};
@@ -19,6 +20,7 @@ pub enum OutEvent {
         peer: PeerId,
         btc: bitcoin::Amount,
         xmr: monero::Amount,
+        timelocks: Timelocks,
     },
     QuoteRequested {
         channel: ResponseChannel<BidQuote>,
@@ -1,7 +1,12 @@
+use crate::asb::cold_storage::{self, ColdStorage};
+use crate::asb::limits::{Limits, Trades};
+use crate::asb::onion;
+use crate::asb::price_feed;
 use crate::asb::Rate;
 use crate::database::Database;
 use crate::env::Config;
 use crate::network::quote::BidQuote;
+use crate::network::spot_price::TimelockRanges;
 use crate::network::transfer_proof;
 use crate::protocol::alice::{AliceState, Behaviour, OutEvent, State0, State3, Swap};
 use crate::{bitcoin, kraken, monero};
@@ -11,13 +16,15 @@ use futures::future::{BoxFuture, FutureExt};
 use futures::stream::{FuturesUnordered, StreamExt};
 use libp2p::request_response::{RequestId, ResponseChannel};
 use libp2p::swarm::SwarmEvent;
-use libp2p::{PeerId, Swarm};
+use libp2p::{identity, Multiaddr, PeerId, Swarm};
 use rand::rngs::OsRng;
 use rust_decimal::Decimal;
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::fmt::Debug;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use time::OffsetDateTime;
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
@@ -44,6 +51,17 @@ where
     latest_rate: LR,
     min_buy: bitcoin::Amount,
     max_buy: bitcoin::Amount,
+    /// Where the redeem and punish transactions pay to, the internal wallet
+    /// if not set.
+    cold_storage: Option<ColdStorage>,
+    /// New maker parameters, e.g. after the config file was reloaded.
+    maker_updates: mpsc::Receiver<MakerParams>,
+    /// Signs binding quotes, quotes are only indications if not set.
+    identity: Option<identity::Keypair>,
+    /// How long a binding quote is honoured.
+    quote_validity: Option<Duration>,
+    /// The trades in the database the limits are checked against.
+    trades: Trades,
 
     swap_sender: mpsc::Sender<Swap>,
 
@@ -78,6 +96,8 @@ where
         max_buy: bitcoin::Amount,
     ) -> Result<(Self, mpsc::Receiver<Swap>)> {
         let swap_channel = MpscChannels::default();
+        // Without updates the channel is closed right away.
+        let (_, maker_updates) = mpsc::channel(1);
 
         let event_loop = EventLoop {
             swarm,
@@ -89,6 +109,11 @@ where
             swap_sender: swap_channel.sender,
             min_buy,
             max_buy,
+            cold_storage: None,
+            maker_updates,
+            identity: None,
+            quote_validity: None,
+            trades: Trades::default(),
             recv_encrypted_signature: Default::default(),
             inflight_encrypted_signatures: Default::default(),
             send_transfer_proof: Default::default(),
@@ -98,6 +123,38 @@ where
         Ok((event_loop, swap_channel.receiver))
     }
 
+    /// Derives the redeem and punish addresses of new swaps from the cold
+    /// storage instead of the internal wallet.
+    pub fn with_cold_storage(mut self, cold_storage: ColdStorage) -> Self {
+        self.cold_storage = Some(cold_storage);
+        self
+    }
+
+    /// Applies the maker parameters received on the channel to new swaps.
+    pub fn with_maker_updates(mut self, maker_updates: mpsc::Receiver<MakerParams>) -> Self {
+        self.maker_updates = maker_updates;
+        self
+    }
+
+    /// Checks the trading limits against the trades, which have to be kept
+    /// up to date with the states saved in the database.
+    pub fn with_trades(mut self, trades: Trades) -> Self {
+        self.trades = trades;
+        self
+    }
+
+    /// Hands out quotes signed with the identity that are honoured for the
+    /// given duration.
+    pub fn with_binding_quotes(
+        mut self,
+        identity: identity::Keypair,
+        quote_validity: Option<Duration>,
+    ) -> Self {
+        self.identity = Some(identity);
+        self.quote_validity = quote_validity;
+        self
+    }
+
     pub fn peer_id(&self) -> PeerId {
         *Swarm::local_peer_id(&self.swarm)
     }
@@ -134,6 +191,7 @@ where
                 monero_wallet: self.monero_wallet.clone(),
                 env_config: self.env_config,
                 db: self.db.clone(),
+                trades: self.trades.clone(),
                 state: state.into(),
                 swap_id,
             };
@@ -146,20 +204,22 @@ where
             }
         }
 
+        self.update_trades();
+
         loop {
             tokio::select! {
                 swarm_event = self.swarm.next_event() => {
                     match swarm_event {
-                        SwarmEvent::Behaviour(OutEvent::ExecutionSetupStart { peer, btc, xmr }) => {
+                        SwarmEvent::Behaviour(OutEvent::ExecutionSetupStart { peer, btc, xmr, timelocks }) => {
 
                             let tx_redeem_fee = self.bitcoin_wallet
-                                .estimate_fee(bitcoin::TxRedeem::weight(), btc)
+                                .estimate_fee(self.payout_weight(bitcoin::TxRedeem::weight()), btc)
                                 .await;
                             let tx_punish_fee = self.bitcoin_wallet
-                                .estimate_fee(bitcoin::TxPunish::weight(), btc)
+                                .estimate_fee(self.payout_weight(bitcoin::TxPunish::weight()), btc)
                                 .await;
-                            let redeem_address = self.bitcoin_wallet.new_address().await;
-                            let punish_address = self.bitcoin_wallet.new_address().await;
+                            let redeem_address = self.new_address().await;
+                            let punish_address = self.new_address().await;
 
                             let (redeem_address, punish_address) = match (
                                 redeem_address,
@@ -190,7 +250,8 @@ where
                             let state0 = match State0::new(
                                 btc,
                                 xmr,
-                                self.env_config,
+                                timelocks.cancel,
+                                timelocks.punish,
                                 redeem_address,
                                 punish_address,
                                 tx_redeem_fee,
@@ -204,7 +265,7 @@ where
                                 }
                             };
 
-                            self.swarm.behaviour_mut().execution_setup.run(peer, state0);
+                            self.swarm.behaviour_mut().execution_setup.run(peer, state0, self.env_config);
                         }
                         SwarmEvent::Behaviour(OutEvent::SwapRequestDeclined { peer, error }) => {
                             tracing::warn!(%peer, "Ignoring spot price request because: {}", error);
@@ -220,8 +281,9 @@ where
                                     tracing::error!("Failed to fetch Monero balance: {:#}", e);
                                 }
                             }
+                            self.update_trades();
 
-                            let quote = match self.make_quote(self.min_buy, self.max_buy).await {
+                            let quote = match self.make_quote(peer, self.min_buy, self.max_buy).await {
                                 Ok(quote) => quote,
                                 Err(error) => {
                                     tracing::warn!(%peer, "Failed to make quote. Error {:#}", error);
@@ -235,6 +297,7 @@ where
                         }
                         SwarmEvent::Behaviour(OutEvent::ExecutionSetupDone{bob_peer_id, swap_id, state3}) => {
                             let _ = self.handle_execution_setup_done(bob_peer_id, swap_id, *state3).await;
+                            self.update_trades();
                         }
                         SwarmEvent::Behaviour(OutEvent::TransferProofAcknowledged { peer, id }) => {
                             tracing::debug!(%peer, "Bob acknowledged transfer proof");
@@ -346,12 +409,52 @@ where
                 Some(response_channel) = self.inflight_encrypted_signatures.next() => {
                     let _ = self.swarm.behaviour_mut().encrypted_signature.send_response(response_channel, ());
                 }
+                Some(params) = self.maker_updates.recv() => {
+                    self.update_maker_params(params);
+                }
             }
         }
     }
 
+    async fn new_address(&self) -> Result<bitcoin::Address> {
+        cold_storage::new_address(self.cold_storage.as_ref(), &self.bitcoin_wallet).await
+    }
+
+    /// The weight of a transaction paying to an address from
+    /// [`Self::new_address`].
+    fn payout_weight(&self, p2wpkh_weight: usize) -> usize {
+        cold_storage::weight(self.cold_storage.as_ref(), p2wpkh_weight)
+    }
+
+    fn update_maker_params(&mut self, params: MakerParams) {
+        self.min_buy = params.min_buy;
+        self.max_buy = params.max_buy;
+
+        let spot_price = &mut self.swarm.behaviour_mut().spot_price;
+        spot_price.update_min_max_buy(params.min_buy, params.max_buy);
+        spot_price.update_resume_only(params.resume_only);
+        spot_price.update_limits(params.limits);
+        spot_price.update_timelocks(params.timelocks);
+        self.quote_validity = params.quote_validity;
+
+        tracing::info!(
+            min_buy = %params.min_buy,
+            max_buy = %params.max_buy,
+            resume_only = params.resume_only,
+            "Updated maker parameters"
+        );
+    }
+
+    /// Hands the trades the limits are checked against to the spot price
+    /// behaviour.
+    fn update_trades(&mut self) {
+        let trades = self.trades.all();
+        self.swarm.behaviour_mut().spot_price.update_trades(trades);
+    }
+
     async fn make_quote(
         &mut self,
+        peer: PeerId,
         min_buy: bitcoin::Amount,
         max_buy: bitcoin::Amount,
     ) -> Result<BidQuote> {
@@ -360,11 +463,45 @@ where
             .latest_rate()
             .context("Failed to get latest rate")?;
 
-        Ok(BidQuote {
+        let quote = BidQuote {
             price: rate.ask().context("Failed to compute asking price")?,
             min_quantity: min_buy,
             max_quantity: max_buy,
-        })
+            addresses: self.onion_addresses(),
+            timelocks: Some(self.swarm.behaviour().spot_price.timelock_ranges()),
+            binding: None,
+        };
+
+        let (identity, quote_validity) = match (&self.identity, self.quote_validity) {
+            (Some(identity), Some(quote_validity)) => (identity, quote_validity),
+            _ => return Ok(quote),
+        };
+
+        let id = Uuid::new_v4();
+        let valid_until = (OffsetDateTime::now_utc() + quote_validity).unix_timestamp();
+        let quote = quote.sign(identity, id, valid_until)?;
+
+        self.swarm
+            .behaviour_mut()
+            .spot_price
+            .insert_quote(id, peer, rate, valid_until);
+
+        Ok(quote)
+    }
+
+    /// The onion addresses the ASB announces in addition to the one it was
+    /// dialed at.
+    ///
+    /// Clearnet addresses are left out, so a CLI that reached the ASB over
+    /// Tor does not learn its IP address.
+    fn onion_addresses(&self) -> Vec<Multiaddr> {
+        let peer_id = self.peer_id();
+
+        self.swarm
+            .external_addresses()
+            .filter(|record| onion::is_onion(&record.addr))
+            .map(|record| onion::with_peer_id(record.addr.clone(), peer_id))
+            .collect()
     }
 
     async fn handle_execution_setup_done(
@@ -373,6 +510,22 @@ where
         swap_id: Uuid,
         state3: State3,
     ) {
+        let labels = vec![
+            (
+                state3.redeem_address().script_pubkey(),
+                bitcoin::UtxoLabel::Redeem { swap_id },
+            ),
+            (
+                state3.punish_address().script_pubkey(),
+                bitcoin::UtxoLabel::Punish { swap_id },
+            ),
+        ];
+        for (script, label) in labels {
+            if let Err(error) = self.db.insert_utxo_label(script, label).await {
+                tracing::warn!(%swap_id, "Failed to label address: {:#}", error);
+            }
+        }
+
         let handle = self.new_handle(bob_peer_id, swap_id);
 
         let initial_state = AliceState::Started {
@@ -385,6 +538,7 @@ where
             monero_wallet: self.monero_wallet.clone(),
             env_config: self.env_config,
             db: self.db.clone(),
+            trades: self.trades.clone(),
             state: initial_state,
             swap_id,
         };
@@ -437,6 +591,19 @@ where
     }
 }
 
+/// The parameters of the maker that can be changed while the ASB is running.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MakerParams {
+    pub min_buy: bitcoin::Amount,
+    pub max_buy: bitcoin::Amount,
+    pub resume_only: bool,
+    pub limits: Limits,
+    pub timelocks: Option<TimelockRanges>,
+    /// How long a binding quote is honoured, quotes are only indications if
+    /// not set.
+    pub quote_validity: Option<Duration>,
+}
+
 pub trait LatestRate {
     type Error: std::error::Error + Send + Sync + 'static;
 
@@ -471,34 +638,117 @@ impl LatestRate for FixedRate {
     }
 }
 
-/// Produces [`Rate`]s based on [`PriceUpdate`]s from kraken and a configured
-/// spread.
+/// A source of ask prices, the Kraken price ticker or a local price feed.
+pub trait AskPrice {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    fn latest_ask(&mut self) -> Result<bitcoin::Amount, Self::Error>;
+}
+
+impl AskPrice for kraken::PriceUpdates {
+    type Error = kraken::Error;
+
+    fn latest_ask(&mut self) -> Result<bitcoin::Amount, Self::Error> {
+        Ok(self.latest_update()?.ask)
+    }
+}
+
+impl AskPrice for price_feed::PriceUpdates {
+    type Error = price_feed::Error;
+
+    fn latest_ask(&mut self) -> Result<bitcoin::Amount, Self::Error> {
+        Ok(self.latest_update()?.ask)
+    }
+}
+
+/// Produces [`Rate`]s based on the ask prices of a price source and a
+/// configured spread.
+///
+/// All clones share the spread, so updating it affects every clone.
 #[derive(Debug, Clone)]
-pub struct KrakenRate {
-    ask_spread: Decimal,
-    price_updates: kraken::PriceUpdates,
+pub struct SpreadRate<P> {
+    ask_spread: Arc<RwLock<Decimal>>,
+    price_updates: P,
 }
 
-impl KrakenRate {
-    pub fn new(ask_spread: Decimal, price_updates: kraken::PriceUpdates) -> Self {
+/// Produces [`Rate`]s based on the Kraken price ticker.
+pub type KrakenRate = SpreadRate<kraken::PriceUpdates>;
+
+/// Produces [`Rate`]s based on a local price feed.
+pub type LocalRate = SpreadRate<price_feed::PriceUpdates>;
+
+impl<P> SpreadRate<P> {
+    pub fn new(ask_spread: Decimal, price_updates: P) -> Self {
         Self {
-            ask_spread,
+            ask_spread: Arc::new(RwLock::new(ask_spread)),
             price_updates,
         }
     }
+
+    pub fn update_ask_spread(&self, ask_spread: Decimal) {
+        *self
+            .ask_spread
+            .write()
+            .expect("no panic while holding the ask spread lock") = ask_spread;
+    }
 }
 
-impl LatestRate for KrakenRate {
-    type Error = kraken::Error;
+impl<P> LatestRate for SpreadRate<P>
+where
+    P: AskPrice,
+{
+    type Error = P::Error;
 
     fn latest_rate(&mut self) -> Result<Rate, Self::Error> {
-        let update = self.price_updates.latest_update()?;
-        let rate = Rate::new(update.ask, self.ask_spread);
+        let ask = self.price_updates.latest_ask()?;
+        let ask_spread = *self
+            .ask_spread
+            .read()
+            .expect("no panic while holding the ask spread lock");
+        let rate = Rate::new(ask, ask_spread);
 
         Ok(rate)
     }
 }
 
+/// The rate of the price source selected in the `[maker]` config, the Kraken
+/// price ticker unless a local price feed is configured.
+#[derive(Debug, Clone)]
+pub enum MakerRate {
+    Kraken(KrakenRate),
+    Local(LocalRate),
+}
+
+impl MakerRate {
+    pub fn update_ask_spread(&self, ask_spread: Decimal) {
+        match self {
+            MakerRate::Kraken(rate) => rate.update_ask_spread(ask_spread),
+            MakerRate::Local(rate) => rate.update_ask_spread(ask_spread),
+        }
+    }
+}
+
+impl LatestRate for MakerRate {
+    type Error = MakerRateError;
+
+    fn latest_rate(&mut self) -> Result<Rate, Self::Error> {
+        let rate = match self {
+            MakerRate::Kraken(rate) => rate.latest_rate()?,
+            MakerRate::Local(rate) => rate.latest_rate()?,
+        };
+
+        Ok(rate)
+    }
+}
+
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum MakerRateError {
+    #[error(transparent)]
+    Kraken(#[from] kraken::Error),
+    #[error(transparent)]
+    Local(#[from] price_feed::Error),
+}
+
 #[derive(Debug)]
 pub struct EventLoopHandle {
     recv_encrypted_signature: Option<bmrng::RequestReceiver<bitcoin::EncryptedSignature, ()>>,
@@ -1,3 +1,4 @@
+use crate::env;
 use crate::network::cbor_request_response::BUF_SIZE;
 use crate::protocol::alice::{State0, State3};
 use crate::protocol::{alice, Message0, Message2, Message4};
@@ -49,13 +50,13 @@ impl Default for Behaviour {
 }
 
 impl Behaviour {
-    pub fn run(&mut self, bob: PeerId, state0: State0) {
+    pub fn run(&mut self, bob: PeerId, state0: State0, env_config: env::Config) {
         self.inner.do_protocol_listener(bob, move |mut substream| {
             let protocol = async move {
                 let message0 =
                     serde_cbor::from_slice::<Message0>(&substream.read_message(BUF_SIZE).await?)
                         .context("Failed to deserialize message0")?;
-                let (swap_id, state1) = state0.receive(message0)?;
+                let (swap_id, state1) = state0.receive(message0, &env_config)?;
 
                 substream
                     .write_message(
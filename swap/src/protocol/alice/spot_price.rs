@@ -1,6 +1,10 @@
+use crate::asb::limits::{Limits, Trade};
+use crate::asb::Rate;
 use crate::network::cbor_request_response::CborCodec;
 use crate::network::spot_price;
-use crate::network::spot_price::{BlockchainNetwork, SpotPriceProtocol};
+use crate::network::spot_price::{
+    BlockchainNetwork, SpotPriceProtocol, TimelockRanges, Timelocks, TradingLimit,
+};
 use crate::protocol::alice;
 use crate::protocol::alice::event_loop::LatestRate;
 use crate::{env, monero};
@@ -10,9 +14,22 @@ use libp2p::request_response::{
 };
 use libp2p::swarm::{NetworkBehaviourAction, NetworkBehaviourEventProcess, PollParameters};
 use libp2p::{NetworkBehaviour, PeerId};
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Debug;
 use std::task::{Context, Poll};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// How long an accepted spot price counts towards the limits on its own.
+///
+/// By then the swap was either saved in the database or it was aborted.
+const ACCEPTED_TRADE_EXPIRY_SECS: i64 = 5 * 60;
+
+/// How many binding quotes a peer can hold at once.
+///
+/// Every quote request hands out a new one, the oldest are dropped so a peer
+/// can not pile up quotes to pick the best rate from later.
+const MAX_QUOTES_PER_PEER: usize = 3;
 
 #[derive(Debug)]
 pub enum OutEvent {
@@ -20,6 +37,7 @@ pub enum OutEvent {
         peer: PeerId,
         btc: bitcoin::Amount,
         xmr: monero::Amount,
+        timelocks: Timelocks,
     },
     Error {
         peer: PeerId,
@@ -53,6 +71,28 @@ where
     latest_rate: LR,
     #[behaviour(ignore)]
     resume_only: bool,
+    #[behaviour(ignore)]
+    limits: Limits,
+    /// The accepted timelocks, only the defaults of the network if not set.
+    #[behaviour(ignore)]
+    timelocks: Option<TimelockRanges>,
+    /// The trades in the database the limits are checked against.
+    #[behaviour(ignore)]
+    trades: Vec<Trade>,
+    /// The spot prices accepted since the trades were last updated.
+    #[behaviour(ignore)]
+    accepted: Vec<Trade>,
+    /// The binding quotes handed out that were not used yet.
+    #[behaviour(ignore)]
+    quotes: HashMap<Uuid, Quote>,
+}
+
+/// A binding quote, honoured once for the peer it was handed out to.
+#[derive(Debug, Clone, Copy)]
+struct Quote {
+    peer: PeerId,
+    rate: Rate,
+    valid_until: i64,
 }
 
 /// Behaviour that handles spot prices.
@@ -86,6 +126,11 @@ where
             env_config,
             latest_rate,
             resume_only,
+            limits: Limits::default(),
+            timelocks: None,
+            trades: Vec::new(),
+            accepted: Vec::new(),
+            quotes: HashMap::new(),
         }
     }
 
@@ -93,6 +138,93 @@ where
         self.balance = balance;
     }
 
+    pub fn update_min_max_buy(&mut self, min_buy: bitcoin::Amount, max_buy: bitcoin::Amount) {
+        self.min_buy = min_buy;
+        self.max_buy = max_buy;
+    }
+
+    pub fn update_resume_only(&mut self, resume_only: bool) {
+        self.resume_only = resume_only;
+    }
+
+    pub fn update_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn update_timelocks(&mut self, timelocks: Option<TimelockRanges>) {
+        self.timelocks = timelocks;
+    }
+
+    pub fn timelock_ranges(&self) -> TimelockRanges {
+        self.timelocks
+            .unwrap_or_else(|| TimelockRanges::exactly(Timelocks::from(self.env_config)))
+    }
+
+    /// Honours the rate for the given peer until the quote expires.
+    ///
+    /// Drops the oldest quotes of the peer if it already holds
+    /// [`MAX_QUOTES_PER_PEER`] of them.
+    pub fn insert_quote(&mut self, id: Uuid, peer: PeerId, rate: Rate, valid_until: i64) {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+
+        self.quotes.retain(|_, quote| quote.valid_until >= now);
+
+        let mut outstanding = self
+            .quotes
+            .iter()
+            .filter(|(_, quote)| quote.peer == peer)
+            .map(|(id, quote)| (quote.valid_until, *id))
+            .collect::<Vec<_>>();
+        outstanding.sort_unstable();
+        let excess = (outstanding.len() + 1).saturating_sub(MAX_QUOTES_PER_PEER);
+        for (_, id) in outstanding.into_iter().take(excess) {
+            self.quotes.remove(&id);
+        }
+
+        self.quotes.insert(id, Quote {
+            peer,
+            rate,
+            valid_until,
+        });
+    }
+
+    /// Replaces the trades the limits are checked against.
+    ///
+    /// Spot prices that were accepted recently keep counting on their own
+    /// until a swap of the same peer and amount that started after them shows
+    /// up in the trades, their swaps may not be saved yet.
+    pub fn update_trades(&mut self, trades: Vec<Trade>) {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+
+        self.accepted.retain(|trade| {
+            trade.started_at.map_or(false, |started_at| {
+                started_at > now - ACCEPTED_TRADE_EXPIRY_SECS
+            })
+        });
+        for trade in &trades {
+            if let Some(index) = self
+                .accepted
+                .iter()
+                .position(|accepted| is_saved_as(accepted, trade))
+            {
+                self.accepted.remove(index);
+            }
+        }
+        self.trades = trades;
+    }
+
+    fn quoted_rate(&self, id: Uuid, peer: PeerId, now: i64) -> Result<Rate, Error> {
+        match self.quotes.get(&id) {
+            Some(quote) if quote.peer != peer => Err(Error::UnknownQuote { id }),
+            Some(quote) if quote.valid_until < now => Err(Error::QuoteExpired {
+                id,
+                valid_until: quote.valid_until,
+            }),
+            Some(quote) => Ok(quote.rate),
+            None => Err(Error::UnknownQuote { id }),
+        }
+    }
+
     fn decline(
         &mut self,
         peer: PeerId,
@@ -127,6 +259,13 @@ where
     }
 }
 
+/// Whether the swap of an accepted spot price was saved as the trade.
+fn is_saved_as(accepted: &Trade, trade: &Trade) -> bool {
+    accepted.peer == trade.peer
+        && accepted.btc == trade.btc
+        && accepted.started_at <= trade.started_at
+}
+
 impl<LR> NetworkBehaviourEventProcess<spot_price::OutEvent> for Behaviour<LR>
 where
     LR: LatestRate + Send + 'static,
@@ -176,6 +315,19 @@ where
             return;
         }
 
+        let timelocks = request
+            .timelocks
+            .unwrap_or_else(|| Timelocks::from(self.env_config));
+        let accepted = self.timelock_ranges();
+
+        if !accepted.contains(timelocks) {
+            self.decline(peer, channel, Error::TimelocksNotAccepted {
+                proposed: timelocks,
+                accepted,
+            });
+            return;
+        }
+
         let btc = request.btc;
 
         if btc < self.min_buy {
@@ -194,12 +346,31 @@ where
             return;
         }
 
-        let rate = match self.latest_rate.latest_rate() {
-            Ok(rate) => rate,
-            Err(e) => {
-                self.decline(peer, channel, Error::LatestRateFetchFailed(Box::new(e)));
-                return;
-            }
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let trades = self.trades.iter().chain(self.accepted.iter());
+        if let Err(limit) = self.limits.check(trades, peer, btc, now) {
+            self.decline(peer, channel, Error::TradingLimitReached {
+                limit,
+                buy: btc,
+            });
+            return;
+        }
+
+        let rate = match request.quote_id {
+            Some(id) => match self.quoted_rate(id, peer, now) {
+                Ok(rate) => rate,
+                Err(error) => {
+                    self.decline(peer, channel, error);
+                    return;
+                }
+            },
+            None => match self.latest_rate.latest_rate() {
+                Ok(rate) => rate,
+                Err(e) => {
+                    self.decline(peer, channel, Error::LatestRateFetchFailed(Box::new(e)));
+                    return;
+                }
+            },
         };
         let xmr = match rate.sell_quote(btc) {
             Ok(xmr) => xmr,
@@ -228,17 +399,40 @@ where
             tracing::error!(%peer, "Failed to send spot price response of {} for {}", xmr, btc)
         }
 
-        self.events
-            .push_back(OutEvent::ExecutionSetupParams { peer, btc, xmr });
+        if let Some(id) = request.quote_id {
+            self.quotes.remove(&id);
+        }
+
+        self.accepted.push(Trade {
+            peer: Some(peer),
+            btc,
+            started_at: Some(now),
+            finished: false,
+        });
+
+        self.events.push_back(OutEvent::ExecutionSetupParams {
+            peer,
+            btc,
+            xmr,
+            timelocks,
+        });
     }
 }
 
 impl From<OutEvent> for alice::OutEvent {
     fn from(event: OutEvent) -> Self {
         match event {
-            OutEvent::ExecutionSetupParams { peer, btc, xmr } => {
-                Self::ExecutionSetupStart { peer, btc, xmr }
-            }
+            OutEvent::ExecutionSetupParams {
+                peer,
+                btc,
+                xmr,
+                timelocks,
+            } => Self::ExecutionSetupStart {
+                peer,
+                btc,
+                xmr,
+                timelocks,
+            },
             OutEvent::Error { peer, error } => Self::SwapRequestDeclined { peer, error },
         }
     }
@@ -267,6 +461,20 @@ pub enum Error {
     LatestRateFetchFailed(#[source] Box<dyn std::error::Error + Send + 'static>),
     #[error("Failed to calculate quote: {0}")]
     SellQuoteCalculationFailed(#[source] anyhow::Error),
+    #[error("Buying {buy} exceeds a trading limit: {limit}")]
+    TradingLimitReached {
+        limit: TradingLimit,
+        buy: bitcoin::Amount,
+    },
+    #[error("Proposed timelocks ({proposed}) are not accepted, accepted are {accepted}")]
+    TimelocksNotAccepted {
+        proposed: Timelocks,
+        accepted: TimelockRanges,
+    },
+    #[error("Quote {id} is unknown or was already used")]
+    UnknownQuote { id: Uuid },
+    #[error("Quote {id} expired at {valid_until}")]
+    QuoteExpired { id: Uuid, valid_until: i64 },
     #[error("Blockchain networks did not match, we are on {asb:?}, but request from {cli:?}")]
     BlockchainNetworkMismatch {
         cli: spot_price::BlockchainNetwork,
@@ -293,6 +501,18 @@ impl Error {
                     asb: *asb,
                 }
             }
+            Error::TradingLimitReached { limit, .. } => {
+                spot_price::Error::TradingLimitReached(*limit)
+            }
+            Error::TimelocksNotAccepted { proposed, accepted } => {
+                spot_price::Error::TimelocksNotAccepted {
+                    proposed: *proposed,
+                    accepted: *accepted,
+                }
+            }
+            Error::UnknownQuote { id } | Error::QuoteExpired { id, .. } => {
+                spot_price::Error::QuoteExpired { quote_id: *id }
+            }
             Error::LatestRateFetchFailed(_) | Error::SellQuoteCalculationFailed(_) => {
                 spot_price::Error::Other
             }
@@ -304,6 +524,7 @@ impl Error {
 mod tests {
     use super::*;
     use crate::asb::Rate;
+    use crate::bitcoin::{CancelTimelock, PunishTimelock};
     use crate::env::GetConfig;
     use crate::monero;
     use crate::network::test::{await_events_or_timeout, connect, new_swarm};
@@ -322,6 +543,8 @@ mod tests {
                 rate: TestRate::default(), // 0.01
                 resume_only: false,
                 env_config: env::Testnet::get_config(),
+                limits: Limits::default(),
+                timelocks: None,
             }
         }
     }
@@ -452,6 +675,212 @@ mod tests {
         .await;
     }
 
+    #[tokio::test]
+    async fn given_daily_volume_reached_by_accepted_spot_prices_then_returns_error() {
+        let max = bitcoin::Amount::from_btc(0.015).unwrap();
+        let limits = Limits {
+            max_volume_24h_btc: Some(max),
+            ..Limits::default()
+        };
+        let mut test =
+            SpotPriceTest::setup(AliceBehaviourValues::default().with_limits(limits)).await;
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let expected_xmr = monero::Amount::from_monero(1.0).unwrap();
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+
+        let limit = TradingLimit::DailyVolume {
+            max,
+            remaining: bitcoin::Amount::from_btc(0.005).unwrap(),
+        };
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_error(
+            alice::spot_price::Error::TradingLimitReached {
+                limit,
+                buy: btc_to_swap,
+            },
+            bob::spot_price::Error::TradingLimitReached(limit),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn given_accepted_spot_price_was_saved_then_it_is_counted_once() {
+        let max = bitcoin::Amount::from_btc(0.025).unwrap();
+        let limits = Limits {
+            max_volume_24h_btc: Some(max),
+            ..Limits::default()
+        };
+        let mut test =
+            SpotPriceTest::setup(AliceBehaviourValues::default().with_limits(limits)).await;
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let expected_xmr = monero::Amount::from_monero(1.0).unwrap();
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+
+        let saved = Trade {
+            peer: Some(test.bob_peer_id),
+            btc: btc_to_swap,
+            started_at: Some(OffsetDateTime::now_utc().unix_timestamp()),
+            finished: false,
+        };
+        test.alice_swarm.behaviour_mut().update_trades(vec![saved]);
+
+        test.construct_and_send_request(btc_to_swap);
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn given_proposed_timelocks_in_range_then_returns_price() {
+        let ranges = TimelockRanges {
+            min_cancel: CancelTimelock::new(6),
+            max_cancel: CancelTimelock::new(72),
+            min_punish: PunishTimelock::new(6),
+            max_punish: PunishTimelock::new(72),
+        };
+        let mut test =
+            SpotPriceTest::setup(AliceBehaviourValues::default().with_timelocks(ranges)).await;
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let expected_xmr = monero::Amount::from_monero(1.0).unwrap();
+        let timelocks = Timelocks {
+            cancel: CancelTimelock::new(6),
+            punish: PunishTimelock::new(12),
+        };
+
+        test.construct_and_send_request_with_timelocks(btc_to_swap, timelocks);
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn given_proposed_timelocks_out_of_range_then_returns_error() {
+        let mut test = SpotPriceTest::setup(AliceBehaviourValues::default()).await;
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let proposed = Timelocks {
+            cancel: CancelTimelock::new(1),
+            punish: PunishTimelock::new(1),
+        };
+        let accepted = TimelockRanges::exactly(Timelocks::from(env::Testnet::get_config()));
+
+        test.construct_and_send_request_with_timelocks(btc_to_swap, proposed);
+        test.assert_error(
+            alice::spot_price::Error::TimelocksNotAccepted { proposed, accepted },
+            bob::spot_price::Error::TimelocksNotAccepted { proposed, accepted },
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn given_binding_quote_then_returns_quoted_price_once() {
+        let mut test = SpotPriceTest::setup(AliceBehaviourValues::default()).await;
+
+        let quote_id = Uuid::new_v4();
+        let quoted_rate = Rate::new(
+            bitcoin::Amount::from_btc(0.02).unwrap(),
+            Decimal::from(0u64),
+        );
+        let valid_until = OffsetDateTime::now_utc().unix_timestamp() + 60;
+        let bob_peer_id = test.bob_peer_id;
+        test.alice_swarm.behaviour_mut().insert_quote(
+            quote_id,
+            bob_peer_id,
+            quoted_rate,
+            valid_until,
+        );
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let expected_xmr = monero::Amount::from_monero(0.5).unwrap();
+
+        test.construct_and_send_request_with_quote(btc_to_swap, quote_id);
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+
+        test.construct_and_send_request_with_quote(btc_to_swap, quote_id);
+        test.assert_error(
+            alice::spot_price::Error::UnknownQuote { id: quote_id },
+            bob::spot_price::Error::QuoteExpired { quote_id },
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn given_too_many_quotes_for_peer_then_oldest_is_dropped() {
+        let mut test = SpotPriceTest::setup(AliceBehaviourValues::default()).await;
+
+        let bob_peer_id = test.bob_peer_id;
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let quote_ids = (0..=MAX_QUOTES_PER_PEER)
+            .map(|i| {
+                let quote_id = Uuid::new_v4();
+                test.alice_swarm.behaviour_mut().insert_quote(
+                    quote_id,
+                    bob_peer_id,
+                    Rate::new(
+                        bitcoin::Amount::from_btc(0.02).unwrap(),
+                        Decimal::from(0u64),
+                    ),
+                    now + 60 + i as i64,
+                );
+                quote_id
+            })
+            .collect::<Vec<_>>();
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+        let expected_xmr = monero::Amount::from_monero(0.5).unwrap();
+
+        let oldest = quote_ids[0];
+        test.construct_and_send_request_with_quote(btc_to_swap, oldest);
+        test.assert_error(
+            alice::spot_price::Error::UnknownQuote { id: oldest },
+            bob::spot_price::Error::QuoteExpired { quote_id: oldest },
+        )
+        .await;
+
+        let latest = quote_ids[MAX_QUOTES_PER_PEER];
+        test.construct_and_send_request_with_quote(btc_to_swap, latest);
+        test.assert_price((btc_to_swap, expected_xmr), expected_xmr)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn given_expired_quote_then_returns_error() {
+        let mut test = SpotPriceTest::setup(AliceBehaviourValues::default()).await;
+
+        let quote_id = Uuid::new_v4();
+        let valid_until = OffsetDateTime::now_utc().unix_timestamp() - 1;
+        let bob_peer_id = test.bob_peer_id;
+        test.alice_swarm.behaviour_mut().insert_quote(
+            quote_id,
+            bob_peer_id,
+            Rate::new(
+                bitcoin::Amount::from_btc(0.02).unwrap(),
+                Decimal::from(0u64),
+            ),
+            valid_until,
+        );
+
+        let btc_to_swap = bitcoin::Amount::from_btc(0.01).unwrap();
+
+        test.construct_and_send_request_with_quote(btc_to_swap, quote_id);
+        test.assert_error(
+            alice::spot_price::Error::QuoteExpired {
+                id: quote_id,
+                valid_until,
+            },
+            bob::spot_price::Error::QuoteExpired { quote_id },
+        )
+        .await;
+    }
+
     #[tokio::test]
     async fn given_alice_in_resume_only_mode_then_returns_error() {
         let mut test =
@@ -545,6 +974,8 @@ mod tests {
                 bitcoin: bitcoin::Network::Bitcoin,
                 monero: monero::Network::Mainnet,
             },
+            timelocks: None,
+            quote_id: None,
         };
 
         test.send_request(request);
@@ -578,6 +1009,7 @@ mod tests {
         bob_swarm: Swarm<spot_price::Behaviour>,
 
         alice_peer_id: PeerId,
+        bob_peer_id: PeerId,
     }
 
     impl SpotPriceTest {
@@ -593,7 +1025,11 @@ mod tests {
                     values.resume_only,
                 )
             });
-            let (mut bob_swarm, ..) = new_swarm(|_, _| bob::spot_price::bob());
+            alice_swarm.behaviour_mut().update_limits(values.limits);
+            alice_swarm
+                .behaviour_mut()
+                .update_timelocks(values.timelocks);
+            let (mut bob_swarm, _, bob_peer_id) = new_swarm(|_, _| bob::spot_price::bob());
 
             connect(&mut alice_swarm, &mut bob_swarm).await;
 
@@ -601,6 +1037,7 @@ mod tests {
                 alice_swarm,
                 bob_swarm,
                 alice_peer_id,
+                bob_peer_id,
             }
         }
 
@@ -611,6 +1048,42 @@ mod tests {
                     bitcoin: bitcoin::Network::Testnet,
                     monero: monero::Network::Stagenet,
                 },
+                timelocks: None,
+                quote_id: None,
+            };
+            self.send_request(request);
+        }
+
+        pub fn construct_and_send_request_with_timelocks(
+            &mut self,
+            btc_to_swap: bitcoin::Amount,
+            timelocks: Timelocks,
+        ) {
+            let request = spot_price::Request {
+                btc: btc_to_swap,
+                blockchain_network: BlockchainNetwork {
+                    bitcoin: bitcoin::Network::Testnet,
+                    monero: monero::Network::Stagenet,
+                },
+                timelocks: Some(timelocks),
+                quote_id: None,
+            };
+            self.send_request(request);
+        }
+
+        pub fn construct_and_send_request_with_quote(
+            &mut self,
+            btc_to_swap: bitcoin::Amount,
+            quote_id: Uuid,
+        ) {
+            let request = spot_price::Request {
+                btc: btc_to_swap,
+                blockchain_network: BlockchainNetwork {
+                    bitcoin: bitcoin::Network::Testnet,
+                    monero: monero::Network::Stagenet,
+                },
+                timelocks: None,
+                quote_id: Some(quote_id),
             };
             self.send_request(request);
         }
@@ -690,6 +1163,51 @@ mod tests {
                             assert_eq!(cli1, cli2);
                             assert_eq!(asb1, asb2);
                         }
+                        (
+                            alice::spot_price::Error::TradingLimitReached {
+                                limit: limit1,
+                                buy: buy1,
+                            },
+                            alice::spot_price::Error::TradingLimitReached {
+                                limit: limit2,
+                                buy: buy2,
+                            },
+                        ) => {
+                            assert_eq!(limit1, limit2);
+                            assert_eq!(buy1, buy2);
+                        }
+                        (
+                            alice::spot_price::Error::TimelocksNotAccepted {
+                                proposed: proposed1,
+                                accepted: accepted1,
+                            },
+                            alice::spot_price::Error::TimelocksNotAccepted {
+                                proposed: proposed2,
+                                accepted: accepted2,
+                            },
+                        ) => {
+                            assert_eq!(proposed1, proposed2);
+                            assert_eq!(accepted1, accepted2);
+                        }
+                        (
+                            alice::spot_price::Error::QuoteExpired {
+                                id: id1,
+                                valid_until: valid_until1,
+                            },
+                            alice::spot_price::Error::QuoteExpired {
+                                id: id2,
+                                valid_until: valid_until2,
+                            },
+                        ) => {
+                            assert_eq!(id1, id2);
+                            assert_eq!(valid_until1, valid_until2);
+                        }
+                        (
+                            alice::spot_price::Error::UnknownQuote { id: id1 },
+                            alice::spot_price::Error::UnknownQuote { id: id2 },
+                        ) => {
+                            assert_eq!(id1, id2);
+                        }
                         (
                             alice::spot_price::Error::AmountBelowMinimum { .. },
                             alice::spot_price::Error::AmountBelowMinimum { .. },
@@ -743,6 +1261,8 @@ mod tests {
         pub rate: TestRate, // 0.01
         pub resume_only: bool,
         pub env_config: env::Config,
+        pub limits: Limits,
+        pub timelocks: Option<TimelockRanges>,
     }
 
     impl AliceBehaviourValues {
@@ -780,6 +1300,16 @@ mod tests {
             self.env_config = env_config;
             self
         }
+
+        pub fn with_limits(mut self, limits: Limits) -> AliceBehaviourValues {
+            self.limits = limits;
+            self
+        }
+
+        pub fn with_timelocks(mut self, timelocks: TimelockRanges) -> AliceBehaviourValues {
+            self.timelocks = Some(timelocks);
+            self
+        }
     }
 
     #[derive(Clone, Debug)]
@@ -130,7 +130,8 @@ impl State0 {
     pub fn new<R>(
         btc: bitcoin::Amount,
         xmr: monero::Amount,
-        env_config: Config,
+        cancel_timelock: CancelTimelock,
+        punish_timelock: PunishTimelock,
         redeem_address: bitcoin::Address,
         punish_address: bitcoin::Address,
         tx_redeem_fee: bitcoin::Amount,
@@ -159,14 +160,14 @@ impl State0 {
             punish_address,
             btc,
             xmr,
-            cancel_timelock: env_config.bitcoin_cancel_timelock,
-            punish_timelock: env_config.bitcoin_punish_timelock,
+            cancel_timelock,
+            punish_timelock,
             tx_redeem_fee,
             tx_punish_fee,
         })
     }
 
-    pub fn receive(self, msg: Message0) -> Result<(Uuid, State1)> {
+    pub fn receive(self, msg: Message0, env_config: &Config) -> Result<(Uuid, State1)> {
         let valid = CROSS_CURVE_PROOF_SYSTEM.verify(
             &msg.dleq_proof_s_b,
             (
@@ -182,6 +183,23 @@ impl State0 {
             bail!("Bob's dleq proof doesn't verify")
         }
 
+        let cancel_timelock = msg
+            .cancel_timelock
+            .unwrap_or(env_config.bitcoin_cancel_timelock);
+        let punish_timelock = msg
+            .punish_timelock
+            .unwrap_or(env_config.bitcoin_punish_timelock);
+
+        if cancel_timelock != self.cancel_timelock || punish_timelock != self.punish_timelock {
+            bail!(
+                "Bob's timelocks (cancel after {}, punish after {}) do not match the negotiated ones (cancel after {}, punish after {})",
+                cancel_timelock,
+                punish_timelock,
+                self.cancel_timelock,
+                self.punish_timelock
+            )
+        }
+
         let v = self.v_a + msg.v_b;
 
         Ok((msg.swap_id, State1 {
@@ -247,6 +265,8 @@ impl State1 {
             punish_address: self.punish_address.clone(),
             tx_redeem_fee: self.tx_redeem_fee,
             tx_punish_fee: self.tx_punish_fee,
+            cancel_timelock: Some(self.cancel_timelock),
+            punish_timelock: Some(self.punish_timelock),
         }
     }
 
@@ -447,6 +467,22 @@ impl State3 {
         }
     }
 
+    pub fn btc(&self) -> bitcoin::Amount {
+        self.btc
+    }
+
+    pub fn xmr(&self) -> monero::Amount {
+        self.xmr
+    }
+
+    pub fn redeem_address(&self) -> &bitcoin::Address {
+        &self.redeem_address
+    }
+
+    pub fn punish_address(&self) -> &bitcoin::Address {
+        &self.punish_address
+    }
+
     pub fn tx_cancel(&self) -> TxCancel {
         TxCancel::new(
             &self.tx_lock,
@@ -556,7 +592,7 @@ impl State3 {
             .context("Failed to complete Bitcoin punish transaction")
     }
 
-    fn tx_punish(&self) -> TxPunish {
+    pub fn tx_punish(&self) -> TxPunish {
         bitcoin::TxPunish::new(
             &self.tx_cancel(),
             &self.punish_address,
@@ -41,10 +41,14 @@ where
         )
         .await?;
 
-        let db_state = (&current_state).into();
+        let db_state: database::Alice = (&current_state).into();
         swap.db
-            .insert_latest_state(swap.swap_id, database::Swap::Alice(db_state))
+            .insert_latest_state(swap.swap_id, database::Swap::Alice(db_state.clone()))
             .await?;
+
+        if let Err(error) = swap.trades.update(&**swap.db, swap.swap_id, &db_state) {
+            error!("Failed to update trades for limits: {:#}", error);
+        }
     }
 
     Ok(current_state)
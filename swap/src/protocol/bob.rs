@@ -1,6 +1,11 @@
 use crate::database::Database;
+use crate::network::spot_price::Timelocks;
 use crate::{bitcoin, env, monero};
-use anyhow::Result;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::fmt::Debug;
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -29,6 +34,84 @@ pub struct Swap {
     pub env_config: env::Config,
     pub id: Uuid,
     pub receive_monero_address: monero::Address,
+    pub lock_signing: LockSigning,
+    /// The timelocks proposed to Alice, the defaults of the network if not
+    /// set.
+    pub timelocks: Option<Timelocks>,
+    /// How the terms are confirmed before the execution setup, they are
+    /// accepted right away if not set.
+    pub confirmation: Option<Arc<dyn ConfirmTerms>>,
+}
+
+/// Describes how the Bitcoin lock transaction is signed.
+#[derive(Debug, Clone)]
+pub enum LockSigning {
+    /// The lock transaction is signed by the CLI's internal wallet.
+    InternalWallet,
+    /// The lock transaction is handed out as unsigned PSBT to be signed by
+    /// an external wallet.
+    ExternalWallet(Arc<dyn SignLock>),
+}
+
+/// Signs the Bitcoin lock transaction with a wallet other than the CLI's
+/// internal wallet.
+#[async_trait]
+pub trait SignLock: Debug + Send + Sync {
+    async fn sign(
+        &self,
+        psbt: bitcoin::PartiallySignedTransaction,
+    ) -> Result<bitcoin::PartiallySignedTransaction>;
+}
+
+/// Accepts or declines the terms of a swap once the spot price is known.
+#[async_trait]
+pub trait ConfirmTerms: Debug + Send + Sync {
+    /// Fails if the terms are not accepted.
+    async fn confirm(&self, terms: Terms) -> Result<()>;
+}
+
+/// The terms of a swap once the spot price is known, before the execution
+/// setup.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Terms {
+    pub btc: bitcoin::Amount,
+    pub xmr: monero::Amount,
+    /// The XMR amount at the price of the quote.
+    pub quoted_xmr: Option<monero::Amount>,
+    /// Estimated before the lock transaction is built, the fee it pays
+    /// depends on the inputs it spends.
+    pub lock_fee: bitcoin::Amount,
+    pub cancel_fee: bitcoin::Amount,
+    pub refund_fee: bitcoin::Amount,
+}
+
+impl Terms {
+    /// The price of 1 XMR the swap buys at.
+    pub fn price(&self) -> Result<bitcoin::Amount> {
+        let price = Decimal::from(self.btc.as_sat())
+            .checked_div(self.xmr.as_piconero_decimal())
+            .context("Division overflow")?
+            * Decimal::from(monero::Amount::ONE_XMR.as_piconero());
+        let price = price.to_u64().context("Failed to fit price into u64")?;
+
+        Ok(bitcoin::Amount::from_sat(price))
+    }
+
+    /// How many percent the XMR amount is below the amount at the quoted
+    /// price, negative if the swap buys more than quoted.
+    pub fn slippage(&self) -> Result<Option<Decimal>> {
+        let quoted_xmr = match self.quoted_xmr {
+            Some(quoted_xmr) => quoted_xmr.as_piconero_decimal(),
+            None => return Ok(None),
+        };
+
+        let slippage = (quoted_xmr - self.xmr.as_piconero_decimal())
+            .checked_div(quoted_xmr)
+            .context("Division overflow")?
+            * Decimal::from(100u64);
+
+        Ok(Some(slippage.round_dp(2)))
+    }
 }
 
 impl Swap {
@@ -42,6 +125,7 @@ impl Swap {
         event_loop_handle: EventLoopHandle,
         receive_monero_address: monero::Address,
         btc_amount: bitcoin::Amount,
+        lock_signing: LockSigning,
     ) -> Self {
         Self {
             state: BobState::Started { btc_amount },
@@ -52,9 +136,13 @@ impl Swap {
             env_config,
             id,
             receive_monero_address,
+            lock_signing,
+            timelocks: None,
+            confirmation: None,
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn from_db(
         db: Database,
         id: Uuid,
@@ -63,6 +151,7 @@ impl Swap {
         env_config: env::Config,
         event_loop_handle: EventLoopHandle,
         receive_monero_address: monero::Address,
+        lock_signing: LockSigning,
     ) -> Result<Self> {
         let state = db.get_state(id)?.try_into_bob()?.into();
 
@@ -75,6 +164,19 @@ impl Swap {
             env_config,
             id,
             receive_monero_address,
+            lock_signing,
+            timelocks: None,
+            confirmation: None,
         })
     }
+
+    pub fn with_timelocks(mut self, timelocks: Option<Timelocks>) -> Self {
+        self.timelocks = timelocks;
+        self
+    }
+
+    pub fn with_confirmation(mut self, confirmation: Arc<dyn ConfirmTerms>) -> Self {
+        self.confirmation = Some(confirmation);
+        self
+    }
 }
@@ -1,6 +1,6 @@
 use crate::bitcoin::EncryptedSignature;
 use crate::network::quote::BidQuote;
-use crate::network::spot_price::{BlockchainNetwork, Response};
+use crate::network::spot_price::{BlockchainNetwork, Response, Timelocks};
 use crate::network::{encrypted_signature, spot_price};
 use crate::protocol::bob;
 use crate::protocol::bob::{Behaviour, OutEvent, State0, State2};
@@ -14,6 +14,7 @@ use libp2p::{PeerId, Swarm};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 #[allow(missing_debug_implementations)]
@@ -22,6 +23,7 @@ pub struct EventLoop {
     swarm: libp2p::Swarm<Behaviour>,
     bitcoin_wallet: Arc<bitcoin::Wallet>,
     alice_peer_id: PeerId,
+    env_config: env::Config,
 
     // these streams represents outgoing requests that we have to make
     quote_requests: bmrng::RequestReceiverStream<(), BidQuote>,
@@ -68,6 +70,7 @@ impl EventLoop {
             swarm,
             alice_peer_id,
             bitcoin_wallet,
+            env_config,
             execution_setup_requests: execution_setup.1.into(),
             transfer_proof: transfer_proof.0,
             encrypted_signatures: encrypted_signature.1.into(),
@@ -87,6 +90,8 @@ impl EventLoop {
             spot_price: spot_price.0,
             quote: quote.0,
             env_config,
+            alice_peer_id,
+            last_quote: None,
         };
 
         Ok((event_loop, handle))
@@ -206,7 +211,7 @@ impl EventLoop {
                     self.inflight_quote_requests.insert(id, responder);
                 },
                 Some((request, responder)) = self.execution_setup_requests.next().fuse(), if self.is_connected_to_alice() => {
-                    self.swarm.behaviour_mut().execution_setup.run(self.alice_peer_id, request, self.bitcoin_wallet.clone());
+                    self.swarm.behaviour_mut().execution_setup.run(self.alice_peer_id, request, self.bitcoin_wallet.clone(), self.env_config);
                     self.inflight_execution_setup = Some(responder);
                 },
                 Some((tx_redeem_encsig, responder)) = self.encrypted_signatures.next().fuse(), if self.is_connected_to_alice() => {
@@ -241,6 +246,10 @@ pub struct EventLoopHandle {
     spot_price: bmrng::RequestSender<spot_price::Request, spot_price::Response>,
     quote: bmrng::RequestSender<(), BidQuote>,
     env_config: env::Config,
+    alice_peer_id: PeerId,
+    /// The last quote received, a binding one is referenced in the spot
+    /// price request.
+    last_quote: Option<BidQuote>,
 }
 
 impl EventLoopHandle {
@@ -261,7 +270,25 @@ impl EventLoopHandle {
         Ok(transfer_proof)
     }
 
-    pub async fn request_spot_price(&mut self, btc: bitcoin::Amount) -> Result<monero::Amount> {
+    pub async fn request_spot_price(
+        &mut self,
+        btc: bitcoin::Amount,
+        timelocks: Option<Timelocks>,
+    ) -> Result<monero::Amount> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let quote = self.last_quote.clone();
+        let quote_id = match quote.as_ref().and_then(|quote| quote.binding.as_ref()) {
+            Some(binding) if binding.valid_until >= now => Some(binding.id),
+            Some(binding) => {
+                tracing::info!(
+                    quote_id = %binding.id,
+                    "Binding quote expired, requesting the current spot price"
+                );
+                None
+            }
+            None => None,
+        };
+
         let response = self
             .spot_price
             .send_receive(spot_price::Request {
@@ -270,11 +297,28 @@ impl EventLoopHandle {
                     bitcoin: self.env_config.bitcoin_network,
                     monero: self.env_config.monero_network,
                 },
+                timelocks,
+                quote_id,
             })
             .await?;
 
         match response {
-            Response::Xmr(xmr) => Ok(xmr),
+            Response::Xmr(xmr) => {
+                if let (Some(quote), Some(quote_id)) = (quote, quote_id) {
+                    let quoted = quote.xmr_for(btc)?;
+
+                    if xmr != quoted {
+                        bail!(
+                            "Seller offered {} instead of the {} of binding quote {}",
+                            xmr,
+                            quoted,
+                            quote_id
+                        );
+                    }
+                }
+
+                Ok(xmr)
+            }
             Response::Error(error) => {
                 let error: bob::spot_price::Error = error.into();
                 bail!(error);
@@ -282,8 +326,30 @@ impl EventLoopHandle {
         }
     }
 
+    /// Requests a quote, a binding quote is verified and referenced in the
+    /// spot price request.
     pub async fn request_quote(&mut self) -> Result<BidQuote> {
-        Ok(self.quote.send_receive(()).await?)
+        let quote = self.quote.send_receive(()).await?;
+
+        if let Some(binding) = &quote.binding {
+            quote
+                .verify(self.alice_peer_id)
+                .context("Seller sent an invalid binding quote")?;
+            tracing::debug!(
+                quote_id = %binding.id,
+                valid_until = binding.valid_until,
+                "Received binding quote"
+            );
+        }
+
+        self.last_quote = Some(quote.clone());
+
+        Ok(quote)
+    }
+
+    /// The last quote received, if any.
+    pub fn last_quote(&self) -> Option<&BidQuote> {
+        self.last_quote.as_ref()
     }
 
     pub async fn send_encrypted_signature(
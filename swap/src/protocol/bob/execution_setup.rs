@@ -1,3 +1,4 @@
+use crate::env;
 use crate::network::cbor_request_response::BUF_SIZE;
 use crate::protocol::bob::{State0, State2};
 use crate::protocol::{bob, Message1, Message3};
@@ -42,6 +43,7 @@ impl Behaviour {
         alice: PeerId,
         state0: State0,
         bitcoin_wallet: Arc<crate::bitcoin::Wallet>,
+        env_config: env::Config,
     ) {
         self.inner.do_protocol_dialer(alice, move |mut substream| {
             let protocol = async move {
@@ -57,7 +59,9 @@ impl Behaviour {
                 let message1 =
                     serde_cbor::from_slice::<Message1>(&substream.read_message(BUF_SIZE).await?)
                         .context("Failed to deserialize message1")?;
-                let state1 = state0.receive(bitcoin_wallet.as_ref(), message1).await?;
+                let state1 = state0
+                    .receive(bitcoin_wallet.as_ref(), message1, &env_config)
+                    .await?;
 
                 substream
                     .write_message(
@@ -4,6 +4,7 @@ use crate::network::spot_price::SpotPriceProtocol;
 use crate::protocol::bob::OutEvent;
 use libp2p::request_response::{ProtocolSupport, RequestResponseConfig};
 use libp2p::PeerId;
+use uuid::Uuid;
 
 const PROTOCOL: &str = spot_price::PROTOCOL;
 pub type SpotPriceOutEvent = spot_price::OutEvent;
@@ -60,6 +61,18 @@ pub enum Error {
         asb: spot_price::BlockchainNetwork,
     },
 
+    #[error("Seller refused the swap because {0}, please try again later")]
+    TradingLimitReached(spot_price::TradingLimit),
+
+    #[error("Seller does not accept the proposed timelocks ({proposed}), accepted are {accepted}")]
+    TimelocksNotAccepted {
+        proposed: spot_price::Timelocks,
+        accepted: spot_price::TimelockRanges,
+    },
+
+    #[error("Seller no longer honours quote {quote_id}, please try again")]
+    QuoteExpired { quote_id: Uuid },
+
     /// To be used for errors that cannot be explained on the CLI side (e.g.
     /// rate update problems on the seller side)
     #[error("Seller encountered a problem, please try again later.")]
@@ -80,6 +93,11 @@ impl From<spot_price::Error> for Error {
             spot_price::Error::BlockchainNetworkMismatch { cli, asb } => {
                 Error::BlockchainNetworkMismatch { cli, asb }
             }
+            spot_price::Error::TradingLimitReached(limit) => Error::TradingLimitReached(limit),
+            spot_price::Error::TimelocksNotAccepted { proposed, accepted } => {
+                Error::TimelocksNotAccepted { proposed, accepted }
+            }
+            spot_price::Error::QuoteExpired { quote_id } => Error::QuoteExpired { quote_id },
             spot_price::Error::Other => Error::Other,
         }
     }
@@ -3,6 +3,7 @@ use crate::bitcoin::{
     self, current_epoch, CancelTimelock, ExpiredTimelocks, PunishTimelock, Transaction, TxCancel,
     TxLock, Txid,
 };
+use crate::env::Config;
 use crate::monero;
 use crate::monero::wallet::WatchRequest;
 use crate::monero::{monero_private_key, TransferProof};
@@ -142,6 +143,8 @@ impl State0 {
             refund_address: self.refund_address.clone(),
             tx_refund_fee: self.tx_refund_fee,
             tx_cancel_fee: self.tx_cancel_fee,
+            cancel_timelock: Some(self.cancel_timelock),
+            punish_timelock: Some(self.punish_timelock),
         }
     }
 
@@ -149,6 +152,7 @@ impl State0 {
         self,
         wallet: &bitcoin::Wallet<B, D, C>,
         msg: Message1,
+        env_config: &Config,
     ) -> Result<State1>
     where
         C: EstimateFeeRate,
@@ -169,6 +173,23 @@ impl State0 {
             bail!("Alice's dleq proof doesn't verify")
         }
 
+        let cancel_timelock = msg
+            .cancel_timelock
+            .unwrap_or(env_config.bitcoin_cancel_timelock);
+        let punish_timelock = msg
+            .punish_timelock
+            .unwrap_or(env_config.bitcoin_punish_timelock);
+
+        if cancel_timelock != self.cancel_timelock || punish_timelock != self.punish_timelock {
+            bail!(
+                "Alice's timelocks (cancel after {}, punish after {}) do not match the negotiated ones (cancel after {}, punish after {})",
+                cancel_timelock,
+                punish_timelock,
+                self.cancel_timelock,
+                self.punish_timelock
+            )
+        }
+
         let tx_lock = bitcoin::TxLock::new(wallet, self.btc, msg.A, self.b.public()).await?;
         let v = msg.v_a + self.v_b;
 
@@ -297,6 +318,14 @@ pub struct State2 {
 }
 
 impl State2 {
+    pub fn xmr(&self) -> monero::Amount {
+        self.xmr
+    }
+
+    pub fn tx_lock(&self) -> &TxLock {
+        &self.tx_lock
+    }
+
     pub fn next_message(&self) -> Message4 {
         let tx_cancel = TxCancel::new(
             &self.tx_lock,
@@ -373,6 +402,10 @@ pub struct State3 {
 }
 
 impl State3 {
+    pub fn xmr(&self) -> monero::Amount {
+        self.xmr
+    }
+
     pub fn lock_xmr_watch_request(&self, transfer_proof: TransferProof) -> WatchRequest {
         let S_b_monero =
             monero::PublicKey::from_private_key(&monero::PrivateKey::from_scalar(self.s_b));
@@ -428,6 +461,29 @@ impl State3 {
         self.tx_lock.txid()
     }
 
+    /// Validates a lock transaction PSBT that was signed by an external
+    /// wallet.
+    ///
+    /// The PSBT has to pay the agreed amount to the shared output and must
+    /// spend the same inputs as the lock transaction we agreed on with Alice,
+    /// otherwise the signatures we exchanged would not be valid.
+    pub fn validate_signed_tx_lock(
+        &self,
+        psbt: bitcoin::PartiallySignedTransaction,
+    ) -> Result<TxLock> {
+        let tx_lock = TxLock::from_psbt(psbt, self.A, self.b.public(), self.tx_lock.lock_amount())?;
+
+        if tx_lock.txid() != self.tx_lock.txid() {
+            bail!(
+                "Signed lock transaction {} does not match the agreed lock transaction {}",
+                tx_lock.txid(),
+                self.tx_lock.txid()
+            )
+        }
+
+        Ok(tx_lock)
+    }
+
     pub async fn current_epoch(
         &self,
         bitcoin_wallet: &bitcoin::Wallet,
@@ -476,15 +532,17 @@ pub struct State4 {
 }
 
 impl State4 {
+    pub fn tx_redeem(&self) -> bitcoin::TxRedeem {
+        bitcoin::TxRedeem::new(&self.tx_lock, &self.redeem_address, self.tx_redeem_fee)
+    }
+
     pub fn tx_redeem_encsig(&self) -> bitcoin::EncryptedSignature {
-        let tx_redeem =
-            bitcoin::TxRedeem::new(&self.tx_lock, &self.redeem_address, self.tx_redeem_fee);
+        let tx_redeem = self.tx_redeem();
         self.b.encsign(self.S_a_bitcoin, tx_redeem.digest())
     }
 
     pub async fn watch_for_redeem_btc(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<State5> {
-        let tx_redeem =
-            bitcoin::TxRedeem::new(&self.tx_lock, &self.redeem_address, self.tx_redeem_fee);
+        let tx_redeem = self.tx_redeem();
         let tx_redeem_encsig = self.b.encsign(self.S_a_bitcoin, tx_redeem.digest());
 
         bitcoin_wallet
@@ -590,17 +648,25 @@ pub struct State6 {
 }
 
 impl State6 {
-    pub async fn expired_timelock(
-        &self,
-        bitcoin_wallet: &bitcoin::Wallet,
-    ) -> Result<ExpiredTimelocks> {
-        let tx_cancel = TxCancel::new(
+    pub fn tx_cancel(&self) -> TxCancel {
+        TxCancel::new(
             &self.tx_lock,
             self.cancel_timelock,
             self.A,
             self.b.public(),
             self.tx_cancel_fee,
-        );
+        )
+    }
+
+    pub fn tx_refund(&self) -> bitcoin::TxRefund {
+        bitcoin::TxRefund::new(&self.tx_cancel(), &self.refund_address, self.tx_refund_fee)
+    }
+
+    pub async fn expired_timelock(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+    ) -> Result<ExpiredTimelocks> {
+        let tx_cancel = self.tx_cancel();
 
         let tx_lock_status = bitcoin_wallet.status_of_script(&self.tx_lock).await?;
         let tx_cancel_status = bitcoin_wallet.status_of_script(&tx_cancel).await?;
@@ -617,13 +683,7 @@ impl State6 {
         &self,
         bitcoin_wallet: &bitcoin::Wallet,
     ) -> Result<Transaction> {
-        let tx_cancel = bitcoin::TxCancel::new(
-            &self.tx_lock,
-            self.cancel_timelock,
-            self.A,
-            self.b.public(),
-            self.tx_cancel_fee,
-        );
+        let tx_cancel = self.tx_cancel();
 
         let tx = bitcoin_wallet.get_raw_transaction(tx_cancel.txid()).await?;
 
@@ -631,15 +691,10 @@ impl State6 {
     }
 
     pub async fn submit_tx_cancel(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<Txid> {
-        let transaction = bitcoin::TxCancel::new(
-            &self.tx_lock,
-            self.cancel_timelock,
-            self.A,
-            self.b.public(),
-            self.tx_cancel_fee,
-        )
-        .complete_as_bob(self.A, self.b.clone(), self.tx_cancel_sig_a.clone())
-        .context("Failed to complete Bitcoin cancel transaction")?;
+        let transaction = self
+            .tx_cancel()
+            .complete_as_bob(self.A, self.b.clone(), self.tx_cancel_sig_a.clone())
+            .context("Failed to complete Bitcoin cancel transaction")?;
 
         let (tx_id, _) = bitcoin_wallet.broadcast(transaction, "cancel").await?;
 
@@ -655,15 +710,7 @@ impl State6 {
     }
 
     pub fn signed_refund_transaction(&self) -> Result<Transaction> {
-        let tx_cancel = bitcoin::TxCancel::new(
-            &self.tx_lock,
-            self.cancel_timelock,
-            self.A,
-            self.b.public(),
-            self.tx_cancel_fee,
-        );
-        let tx_refund =
-            bitcoin::TxRefund::new(&tx_cancel, &self.refund_address, self.tx_refund_fee);
+        let tx_refund = self.tx_refund();
 
         let adaptor = Adaptor::<HashTranscript<Sha256>, Deterministic<Sha256>>::default();
 
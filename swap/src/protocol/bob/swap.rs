@@ -1,12 +1,17 @@
-use crate::bitcoin::{ExpiredTimelocks, TxCancel, TxRefund};
-use crate::database::Swap;
+use crate::bitcoin::{ExpiredTimelocks, TxCancel, TxLock, TxRefund, UtxoLabel};
+use crate::database::{Database, Swap};
 use crate::env::Config;
+use crate::network::spot_price::Timelocks;
 use crate::protocol::bob;
 use crate::protocol::bob::event_loop::EventLoopHandle;
 use crate::protocol::bob::state::*;
+use crate::protocol::bob::{ConfirmTerms, LockSigning, Terms};
 use crate::{bitcoin, monero};
 use anyhow::{bail, Context, Result};
 use rand::rngs::OsRng;
+use std::convert::TryFrom;
+use std::time::Duration;
+use time::OffsetDateTime;
 use tokio::select;
 use uuid::Uuid;
 
@@ -36,10 +41,14 @@ pub async fn run_until(
             swap.id,
             current_state,
             &mut swap.event_loop_handle,
+            &swap.db,
             swap.bitcoin_wallet.as_ref(),
             swap.monero_wallet.as_ref(),
             &swap.env_config,
             swap.receive_monero_address,
+            &swap.lock_signing,
+            swap.timelocks,
+            swap.confirmation.as_deref(),
         )
         .await?;
 
@@ -52,47 +61,96 @@ pub async fn run_until(
     Ok(current_state)
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn next_state(
     swap_id: Uuid,
     state: BobState,
     event_loop_handle: &mut EventLoopHandle,
+    db: &Database,
     bitcoin_wallet: &bitcoin::Wallet,
     monero_wallet: &monero::Wallet,
     env_config: &Config,
     receive_monero_address: monero::Address,
+    lock_signing: &LockSigning,
+    timelocks: Option<Timelocks>,
+    confirmation: Option<&dyn ConfirmTerms>,
 ) -> Result<BobState> {
     tracing::trace!(%state, "Advancing state");
 
     Ok(match state {
         BobState::Started { btc_amount } => {
             let bitcoin_refund_address = bitcoin_wallet.new_address().await?;
+            db.insert_utxo_label(
+                bitcoin_refund_address.script_pubkey(),
+                UtxoLabel::Refund { swap_id },
+            )
+            .await?;
             let tx_refund_fee = bitcoin_wallet
                 .estimate_fee(TxRefund::weight(), btc_amount)
                 .await?;
             let tx_cancel_fee = bitcoin_wallet
                 .estimate_fee(TxCancel::weight(), btc_amount)
                 .await?;
+            let tx_lock_fee = bitcoin_wallet
+                .estimate_fee(TxLock::weight(), btc_amount)
+                .await?;
 
             let state2 = request_price_and_setup(
                 swap_id,
                 btc_amount,
                 event_loop_handle,
                 env_config,
+                timelocks,
                 bitcoin_refund_address,
                 tx_refund_fee,
                 tx_cancel_fee,
+                tx_lock_fee,
+                confirmation,
             )
             .await?;
 
-            BobState::ExecutionSetupDone(state2)
+            match state2 {
+                Some(state2) => BobState::ExecutionSetupDone(state2),
+                None => BobState::SafelyAborted,
+            }
         }
         BobState::ExecutionSetupDone(state2) => {
             // Alice and Bob have exchanged info
             let (state3, tx_lock) = state2.lock_btc().await?;
-            let signed_tx = bitcoin_wallet
-                .sign_and_finalize(tx_lock.clone().into())
-                .await
-                .context("Failed to sign Bitcoin lock transaction")?;
+            let signed_tx = match lock_signing {
+                LockSigning::InternalWallet => bitcoin_wallet
+                    .sign_and_finalize(tx_lock.into())
+                    .await
+                    .context("Failed to sign Bitcoin lock transaction")?,
+                LockSigning::ExternalWallet(signer) => {
+                    let signed_psbt = signer.sign(tx_lock.into()).await?;
+                    let tx_lock = state3.validate_signed_tx_lock(signed_psbt)?;
+
+                    // Leave part of the time Alice waits for the lock transaction
+                    // for it to reach her.
+                    let timeout = env_config.bitcoin_lock_mempool_timeout;
+                    let deadline = timeout - timeout / 6;
+                    // Swaps set up before the moment was recorded are not checked.
+                    let execution_setup_done_at = db.get_details(swap_id)?.execution_setup_done_at;
+                    if let Some(execution_setup_done_at) = execution_setup_done_at {
+                        let elapsed =
+                            OffsetDateTime::now_utc().unix_timestamp() - execution_setup_done_at;
+                        let elapsed = Duration::from_secs(u64::try_from(elapsed).unwrap_or(0));
+
+                        if elapsed > deadline {
+                            tracing::error!(
+                                "Not locking Bitcoin: Signing the lock transaction took longer than Alice waits for it"
+                            );
+                            return Ok(BobState::SafelyAborted);
+                        }
+                    }
+
+                    bitcoin_wallet
+                        .finalize(tx_lock.into())
+                        .await
+                        .context("Failed to finalize externally signed Bitcoin lock transaction")?
+                }
+            };
             let (..) = bitcoin_wallet.broadcast(signed_tx, "lock").await?;
 
             BobState::BtcLocked(state3)
@@ -269,26 +327,55 @@ async fn next_state(
     })
 }
 
+/// Requests the spot price and runs the execution setup with Alice.
+///
+/// Returns `None` without starting the execution setup if the terms are not
+/// accepted.
+#[allow(clippy::too_many_arguments)]
 pub async fn request_price_and_setup(
     swap_id: Uuid,
     btc: bitcoin::Amount,
     event_loop_handle: &mut EventLoopHandle,
     env_config: &Config,
+    timelocks: Option<Timelocks>,
     bitcoin_refund_address: bitcoin::Address,
     tx_refund_fee: bitcoin::Amount,
     tx_cancel_fee: bitcoin::Amount,
-) -> Result<bob::state::State2> {
-    let xmr = event_loop_handle.request_spot_price(btc).await?;
-
-    tracing::info!(%btc, %xmr, "Spot price");
+    tx_lock_fee: bitcoin::Amount,
+    confirmation: Option<&dyn ConfirmTerms>,
+) -> Result<Option<bob::state::State2>> {
+    let xmr = event_loop_handle.request_spot_price(btc, timelocks).await?;
+    let timelocks = timelocks.unwrap_or_else(|| Timelocks::from(*env_config));
+
+    tracing::info!(%btc, %xmr, %timelocks, "Spot price");
+
+    if let Some(confirmation) = confirmation {
+        let quoted_xmr = event_loop_handle
+            .last_quote()
+            .map(|quote| quote.xmr_for(btc))
+            .transpose()?;
+        let terms = Terms {
+            btc,
+            xmr,
+            quoted_xmr,
+            lock_fee: tx_lock_fee,
+            cancel_fee: tx_cancel_fee,
+            refund_fee: tx_refund_fee,
+        };
+
+        if let Err(error) = confirmation.confirm(terms).await {
+            tracing::error!("Not swapping: {:#}", error);
+            return Ok(None);
+        }
+    }
 
     let state0 = State0::new(
         swap_id,
         &mut OsRng,
         btc,
         xmr,
-        env_config.bitcoin_cancel_timelock,
-        env_config.bitcoin_punish_timelock,
+        timelocks.cancel,
+        timelocks.punish,
         bitcoin_refund_address,
         env_config.monero_finality_confirmations,
         tx_refund_fee,
@@ -297,5 +384,5 @@ pub async fn request_price_and_setup(
 
     let state2 = event_loop_handle.execution_setup(state0).await?;
 
-    Ok(state2)
+    Ok(Some(state2))
 }
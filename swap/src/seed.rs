@@ -1,9 +1,13 @@
+use crate::encryption::{Encrypted, Passphrase};
 use crate::fs::ensure_directory_exists;
 use ::bitcoin::secp256k1::constants::SECRET_KEY_SIZE;
 use ::bitcoin::secp256k1::{self, SecretKey};
 use anyhow::{Context, Result};
 use bdk::bitcoin::util::bip32::ExtendedPrivKey;
+use bip39::Mnemonic;
 use bitcoin::hashes::{sha256, Hash, HashEngine};
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Password;
 use libp2p::identity;
 use pem::{encode, Pem};
 use rand::prelude::*;
@@ -13,9 +17,14 @@ use std::fs::{self, File};
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use torut::onion::TorSecretKeyV3;
+use uuid::Uuid;
 
 pub const SEED_LENGTH: usize = 32;
 
+const SEED_FILE_NAME: &str = "seed.pem";
+const SEED_PEM_TAG: &str = "SEED";
+const ENCRYPTED_SEED_PEM_TAG: &str = "ENCRYPTED SEED";
+
 #[derive(Eq, PartialEq)]
 pub struct Seed([u8; SEED_LENGTH]);
 
@@ -42,10 +51,36 @@ impl Seed {
     }
 
     pub fn derive_libp2p_identity(&self) -> identity::Keypair {
+        identity::Keypair::Ed25519(self.derive_libp2p_keypair())
+    }
+
+    /// The ed25519 keypair behind [`Seed::derive_libp2p_identity`].
+    pub fn derive_libp2p_keypair(&self) -> identity::ed25519::Keypair {
         let bytes = self.derive(b"NETWORK").derive(b"LIBP2P_IDENTITY").bytes();
         let key = identity::ed25519::SecretKey::from_bytes(bytes).expect("we always pass 32 bytes");
 
-        identity::Keypair::Ed25519(key.into())
+        key.into()
+    }
+
+    /// The libp2p identity of the CLI for a single swap.
+    ///
+    /// Using a different identity for every swap prevents sellers from
+    /// linking the swaps of a user. It can always be derived again from the
+    /// seed and the swap id to resume the swap.
+    pub fn derive_libp2p_identity_for_swap(&self, swap_id: Uuid) -> identity::Keypair {
+        identity::Keypair::Ed25519(self.derive_libp2p_keypair_for_swap(swap_id))
+    }
+
+    /// The ed25519 keypair behind [`Seed::derive_libp2p_identity_for_swap`].
+    pub fn derive_libp2p_keypair_for_swap(&self, swap_id: Uuid) -> identity::ed25519::Keypair {
+        let bytes = self
+            .derive(b"NETWORK")
+            .derive(b"LIBP2P_IDENTITY")
+            .derive(swap_id.as_bytes())
+            .bytes();
+        let key = identity::ed25519::SecretKey::from_bytes(bytes).expect("we always pass 32 bytes");
+
+        key.into()
     }
 
     pub fn derive_torv3_key(&self) -> TorSecretKeyV3 {
@@ -56,22 +91,97 @@ impl Seed {
         esk.to_bytes().into()
     }
 
-    pub fn from_file_or_generate(data_dir: &Path) -> Result<Self, Error> {
-        let file_path_buf = data_dir.join("seed.pem");
+    /// Reads the seed from the data dir or generates a new one if there is
+    /// none yet.
+    ///
+    /// The passphrase is read if the seed file is encrypted, a new seed is
+    /// encrypted with it unless it is left empty.
+    pub fn from_file_or_generate(data_dir: &Path, passphrase: &Passphrase) -> Result<Self, Error> {
+        let file_path_buf = data_dir.join(SEED_FILE_NAME);
         let file_path = Path::new(&file_path_buf);
 
         if file_path.exists() {
-            return Self::from_file(&file_path);
+            return Self::from_file(&file_path, passphrase);
         }
 
         tracing::debug!("No seed file found, creating at: {}", file_path.display());
 
         let random_seed = Seed::random()?;
-        random_seed.write_to(file_path.to_path_buf())?;
+        random_seed.write_new_to(file_path.to_path_buf(), passphrase)?;
 
         Ok(random_seed)
     }
 
+    /// The seed as BIP39 mnemonic of 24 words.
+    pub fn to_mnemonic(&self) -> Mnemonic {
+        Mnemonic::from_entropy(&self.bytes()).expect("32 bytes are valid BIP39 entropy")
+    }
+
+    pub fn from_mnemonic(words: &str) -> Result<Self, Error> {
+        let entropy = Mnemonic::parse(words)?.to_entropy();
+
+        if entropy.len() != SEED_LENGTH {
+            return Err(Error::IncorrectLength(entropy.len()));
+        }
+
+        let mut array = [0; SEED_LENGTH];
+        array.copy_from_slice(&entropy);
+
+        Ok(Self::from(array))
+    }
+
+    /// Writes the seed of the given mnemonic to the data dir, encrypted with
+    /// the passphrase unless it is left empty.
+    ///
+    /// Fails if there is a seed file already, it has to be moved away to
+    /// restore another seed.
+    pub fn restore_from_mnemonic(
+        data_dir: &Path,
+        words: &str,
+        passphrase: &Passphrase,
+    ) -> Result<Self, Error> {
+        let file_path = data_dir.join(SEED_FILE_NAME);
+
+        if file_path.exists() {
+            return Err(Error::SeedFileExists(file_path));
+        }
+
+        let seed = Self::from_mnemonic(words)?;
+        seed.write_new_to(file_path, passphrase)?;
+
+        Ok(seed)
+    }
+
+    /// Encrypts the plaintext seed file in the data dir in place.
+    pub fn encrypt_file(data_dir: &Path, new_passphrase: &str) -> Result<(), Error> {
+        let file_path = data_dir.join(SEED_FILE_NAME);
+        let pem = read_pem(&file_path)?;
+
+        if pem.tag == ENCRYPTED_SEED_PEM_TAG {
+            return Err(Error::AlreadyEncrypted);
+        }
+
+        Self::from_pem(pem)?.write_encrypted_to(file_path, new_passphrase)
+    }
+
+    /// Encrypts the encrypted seed file in the data dir with a new
+    /// passphrase.
+    pub fn change_passphrase(
+        data_dir: &Path,
+        current_passphrase: &str,
+        new_passphrase: &str,
+    ) -> Result<(), Error> {
+        let file_path = data_dir.join(SEED_FILE_NAME);
+        let pem = read_pem(&file_path)?;
+
+        if pem.tag != ENCRYPTED_SEED_PEM_TAG {
+            return Err(Error::NotEncrypted);
+        }
+
+        Self::from_encrypted_pem(pem, current_passphrase)?
+            .write_encrypted_to(file_path, new_passphrase)
+    }
+
     /// Derive a new seed using the given scope.
     ///
     /// This function is purposely kept private because it is only a helper
@@ -92,19 +202,33 @@ impl Seed {
         self.0
     }
 
-    fn from_file<D>(seed_file: D) -> Result<Self, Error>
+    fn from_file<D>(seed_file: D, passphrase: &Passphrase) -> Result<Self, Error>
     where
         D: AsRef<OsStr>,
     {
         let file = Path::new(&seed_file);
-        let contents = fs::read_to_string(file)?;
-        let pem = pem::parse(contents)?;
+        let pem = read_pem(file)?;
 
         tracing::debug!("Reading in seed from {}", file.display());
 
+        if pem.tag == ENCRYPTED_SEED_PEM_TAG {
+            let passphrase = passphrase.read().map_err(Error::Encryption)?;
+            return Self::from_encrypted_pem(pem, &passphrase);
+        }
+
         Self::from_pem(pem)
     }
 
+    fn from_encrypted_pem(pem: pem::Pem, passphrase: &str) -> Result<Self, Error> {
+        let encrypted = serde_cbor::from_slice::<Encrypted>(&pem.contents)?;
+        let contents = encrypted.decrypt(passphrase).map_err(Error::Encryption)?;
+
+        Self::from_pem(Pem {
+            tag: SEED_PEM_TAG.to_string(),
+            contents,
+        })
+    }
+
     fn from_pem(pem: pem::Pem) -> Result<Self, Error> {
         if pem.contents.len() != SEED_LENGTH {
             Err(Error::IncorrectLength(pem.contents.len()))
@@ -119,23 +243,70 @@ impl Seed {
     }
 
     fn write_to(&self, seed_file: PathBuf) -> Result<(), Error> {
-        ensure_directory_exists(&seed_file)?;
-
         let data = self.bytes();
         let pem = Pem {
-            tag: String::from("SEED"),
+            tag: String::from(SEED_PEM_TAG),
             contents: data.to_vec(),
         };
 
-        let pem_string = encode(&pem);
+        write_pem(seed_file, &pem)
+    }
+
+    /// Writes a new seed file, encrypted if a passphrase is given.
+    fn write_new_to(&self, seed_file: PathBuf, passphrase: &Passphrase) -> Result<(), Error> {
+        match passphrase.read_new().map_err(Error::Encryption)? {
+            Some(passphrase) => self.write_encrypted_to(seed_file, &passphrase),
+            None => {
+                tracing::warn!(
+                    "The seed file is not encrypted, use `seed encrypt` to protect it with a passphrase"
+                );
+                self.write_to(seed_file)
+            }
+        }
+    }
 
-        let mut file = File::create(seed_file)?;
-        file.write_all(pem_string.as_bytes())?;
+    fn write_encrypted_to(&self, seed_file: PathBuf, passphrase: &str) -> Result<(), Error> {
+        let encrypted = Encrypted::encrypt(&self.bytes(), passphrase).map_err(Error::Encryption)?;
+        let pem = Pem {
+            tag: String::from(ENCRYPTED_SEED_PEM_TAG),
+            contents: serde_cbor::to_vec(&encrypted)?,
+        };
 
-        Ok(())
+        write_pem(seed_file, &pem)
     }
 }
 
+fn read_pem(seed_file: &Path) -> Result<Pem, Error> {
+    let contents = fs::read_to_string(seed_file)?;
+
+    Ok(pem::parse(contents)?)
+}
+
+/// Writes the PEM to a temporary file first so an existing seed file is
+/// only replaced once the new one is complete.
+fn write_pem(seed_file: PathBuf, pem: &Pem) -> Result<(), Error> {
+    ensure_directory_exists(&seed_file)?;
+
+    let pem_string = encode(pem);
+    let tmp_file = seed_file.with_extension("pem.tmp");
+
+    let mut file = File::create(&tmp_file)?;
+    file.write_all(pem_string.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(tmp_file, seed_file)?;
+
+    Ok(())
+}
+
+/// Asks the user for the words of a BIP39 mnemonic.
+pub fn prompt_mnemonic() -> Result<String> {
+    let words = Password::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter the words of the mnemonic separated by spaces")
+        .interact()?;
+
+    Ok(words)
+}
+
 impl fmt::Debug for Seed {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Seed([*****])")
@@ -168,11 +339,24 @@ pub enum Error {
     Rand(#[from] rand::Error),
     #[error("no default path")]
     NoDefaultPath,
+    #[error("CBOR: ")]
+    Cbor(#[from] serde_cbor::Error),
+    #[error("{0:#}")]
+    Encryption(anyhow::Error),
+    #[error("the seed file is encrypted already")]
+    AlreadyEncrypted,
+    #[error("the seed file is not encrypted")]
+    NotEncrypted,
+    #[error("BIP39 mnemonic: {0}")]
+    Mnemonic(#[from] bip39::Error),
+    #[error("refusing to overwrite existing seed file {}", .0.display())]
+    SeedFileExists(PathBuf),
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
     use std::env::temp_dir;
 
     #[test]
@@ -248,7 +432,184 @@ mbKANv2qKGmNVg1qtquj6Hx1pFPelpqOfE2JaJJAMEg1FlFhNRNlFlE=
         seed.write_to(tmpfile.clone())
             .expect("Write seed to temp file");
 
-        let rinsed = Seed::from_file(tmpfile).expect("Read from temp file");
+        let rinsed = Seed::from_file(tmpfile, &Passphrase::None).expect("Read from temp file");
         assert_eq!(seed.0, rinsed.0);
     }
+
+    #[test]
+    fn encrypted_seed_file_is_read_with_passphrase() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let passphrase_file = data_dir.path().join("passphrase");
+        fs::write(&passphrase_file, "passphrase").unwrap();
+
+        let seed = Seed::from_file_or_generate(data_dir.path(), &Passphrase::None).unwrap();
+        Seed::encrypt_file(data_dir.path(), "passphrase").unwrap();
+
+        let contents = fs::read_to_string(data_dir.path().join(SEED_FILE_NAME)).unwrap();
+        assert!(contents.starts_with("-----BEGIN ENCRYPTED SEED-----"));
+
+        let decrypted =
+            Seed::from_file_or_generate(data_dir.path(), &Passphrase::File(passphrase_file))
+                .unwrap();
+        assert_eq!(seed, decrypted);
+    }
+
+    #[test]
+    fn new_seed_is_encrypted_with_given_passphrase() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let passphrase_file = data_dir.path().join("passphrase");
+        fs::write(&passphrase_file, "passphrase").unwrap();
+        let passphrase = Passphrase::File(passphrase_file);
+
+        let seed = Seed::from_file_or_generate(data_dir.path(), &passphrase).unwrap();
+
+        let contents = fs::read_to_string(data_dir.path().join(SEED_FILE_NAME)).unwrap();
+        assert!(contents.starts_with("-----BEGIN ENCRYPTED SEED-----"));
+        assert_eq!(
+            Seed::from_file_or_generate(data_dir.path(), &passphrase).unwrap(),
+            seed
+        );
+        assert!(Seed::from_file_or_generate(data_dir.path(), &Passphrase::None).is_err());
+    }
+
+    #[test]
+    fn changed_passphrase_replaces_old_passphrase() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let old_passphrase = data_dir.path().join("old");
+        let new_passphrase = data_dir.path().join("new");
+        fs::write(&old_passphrase, "old").unwrap();
+        fs::write(&new_passphrase, "new").unwrap();
+
+        let seed = Seed::from_file_or_generate(data_dir.path(), &Passphrase::None).unwrap();
+        Seed::encrypt_file(data_dir.path(), "old").unwrap();
+        Seed::change_passphrase(data_dir.path(), "old", "new").unwrap();
+
+        assert!(
+            Seed::from_file_or_generate(data_dir.path(), &Passphrase::File(old_passphrase))
+                .is_err()
+        );
+        assert_eq!(
+            Seed::from_file_or_generate(data_dir.path(), &Passphrase::File(new_passphrase))
+                .unwrap(),
+            seed
+        );
+    }
+
+    #[test]
+    fn every_swap_has_its_own_libp2p_identity() {
+        let seed = Seed::random().unwrap();
+        let swap_id = Uuid::new_v4();
+
+        let identity = seed.derive_libp2p_identity_for_swap(swap_id).public();
+
+        assert_eq!(
+            identity,
+            seed.derive_libp2p_identity_for_swap(swap_id).public()
+        );
+        assert_ne!(
+            identity,
+            seed.derive_libp2p_identity_for_swap(Uuid::new_v4())
+                .public()
+        );
+        assert_ne!(identity, seed.derive_libp2p_identity().public());
+    }
+
+    #[test]
+    fn mnemonic_has_24_words() {
+        let seed = Seed::random().unwrap();
+
+        assert_eq!(seed.to_mnemonic().word_count(), 24);
+    }
+
+    #[test]
+    fn restoring_from_mnemonic_does_not_overwrite_seed_file() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let seed = Seed::from_file_or_generate(data_dir.path(), &Passphrase::None).unwrap();
+        let other = Seed::random().unwrap().to_mnemonic().to_string();
+
+        match Seed::restore_from_mnemonic(data_dir.path(), &other, &Passphrase::None) {
+            Err(Error::SeedFileExists(_)) => {} // pass
+            _ => panic!("should fail with SeedFileExists error"),
+        }
+        assert_eq!(
+            Seed::from_file_or_generate(data_dir.path(), &Passphrase::None).unwrap(),
+            seed
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn seed_restored_from_mnemonic_derives_the_same_keys(bytes in any::<[u8; SEED_LENGTH]>()) {
+            let seed = Seed::from(bytes);
+
+            let restored = Seed::from_mnemonic(&seed.to_mnemonic().to_string()).unwrap();
+
+            prop_assert_eq!(restored.bytes(), seed.bytes());
+            prop_assert_eq!(
+                restored.derive_extended_private_key(bitcoin::Network::Bitcoin).unwrap(),
+                seed.derive_extended_private_key(bitcoin::Network::Bitcoin).unwrap()
+            );
+            prop_assert_eq!(
+                restored.derive_libp2p_identity().public(),
+                seed.derive_libp2p_identity().public()
+            );
+            prop_assert_eq!(
+                restored.derive_torv3_key().public().get_onion_address().get_address_without_dot_onion(),
+                seed.derive_torv3_key().public().get_onion_address().get_address_without_dot_onion()
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn seed_file_restored_from_mnemonic_equals_original(bytes in any::<[u8; SEED_LENGTH]>()) {
+            let data_dir = tempfile::tempdir().unwrap();
+            let seed = Seed::from(bytes);
+
+            Seed::restore_from_mnemonic(
+                data_dir.path(),
+                &seed.to_mnemonic().to_string(),
+                &Passphrase::None,
+            )
+            .unwrap();
+
+            let read = Seed::from_file_or_generate(data_dir.path(), &Passphrase::None).unwrap();
+            prop_assert_eq!(read, seed);
+        }
+    }
+
+    #[test]
+    fn restored_seed_is_encrypted_with_given_passphrase() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let passphrase_file = data_dir.path().join("passphrase");
+        fs::write(&passphrase_file, "passphrase").unwrap();
+        let passphrase = Passphrase::File(passphrase_file);
+        let seed = Seed::random().unwrap();
+
+        Seed::restore_from_mnemonic(
+            data_dir.path(),
+            &seed.to_mnemonic().to_string(),
+            &passphrase,
+        )
+        .unwrap();
+
+        let contents = fs::read_to_string(data_dir.path().join(SEED_FILE_NAME)).unwrap();
+        assert!(contents.starts_with("-----BEGIN ENCRYPTED SEED-----"));
+        assert_eq!(
+            Seed::from_file_or_generate(data_dir.path(), &passphrase).unwrap(),
+            seed
+        );
+    }
+
+    #[test]
+    fn encrypting_encrypted_seed_file_fails() {
+        let data_dir = tempfile::tempdir().unwrap();
+        Seed::from_file_or_generate(data_dir.path(), &Passphrase::None).unwrap();
+        Seed::encrypt_file(data_dir.path(), "passphrase").unwrap();
+
+        match Seed::encrypt_file(data_dir.path(), "passphrase") {
+            Err(Error::AlreadyEncrypted) => {} // pass
+            _ => panic!("should fail with AlreadyEncrypted error"),
+        }
+    }
 }
@@ -1,6 +1,7 @@
 use anyhow::{anyhow, bail, Context, Result};
 use std::future::Future;
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::time::Duration;
 use tokio::net::TcpStream;
 use torut::control::{AsyncEvent, AuthenticatedConn, ConnError, UnauthenticatedConn};
 use torut::onion::TorSecretKeyV3;
@@ -8,6 +9,9 @@ use torut::onion::TorSecretKeyV3;
 pub const DEFAULT_SOCKS5_PORT: u16 = 9050;
 pub const DEFAULT_CONTROL_PORT: u16 = 9051;
 
+/// How often the control connection is checked while supervising a service.
+const SUPERVISE_INTERVAL: Duration = Duration::from_secs(30);
+
 #[derive(Debug, Clone, Copy)]
 pub struct Client {
     socks5_address: SocketAddrV4,
@@ -111,18 +115,100 @@ pub struct AuthenticatedClient {
 }
 
 impl AuthenticatedClient {
-    /// Add an ephemeral tor service on localhost with the provided key
+    /// Add a detached tor service on localhost with the provided key
     /// `service_port` and `onion_port` can be different but don't have to as
     /// they are on different networks.
+    ///
+    /// A detached service outlives the control connection, it is only removed
+    /// when Tor stops. A service with the same key that is still registered
+    /// from an earlier connection is replaced.
     pub async fn add_services(
         &mut self,
         services: &[(u16, SocketAddr)],
         tor_key: &TorSecretKeyV3,
     ) -> Result<()> {
+        let onion_address = tor_key
+            .public()
+            .get_onion_address()
+            .get_address_without_dot_onion();
+
+        // Fails if the service does not exist, which is the common case.
+        let _ = self.inner.del_onion(&onion_address).await;
+
         let mut listeners = services.iter();
         self.inner
-            .add_onion_v3(tor_key, false, false, false, None, &mut listeners)
+            .add_onion_v3(tor_key, true, false, false, None, &mut listeners)
             .await
             .map_err(|e| anyhow!("Could not add onion service.: {:#?}", e))
     }
+
+    /// Removes the service with the provided key.
+    pub async fn remove_service(&mut self, tor_key: &TorSecretKeyV3) -> Result<()> {
+        let onion_address = tor_key
+            .public()
+            .get_onion_address()
+            .get_address_without_dot_onion();
+
+        self.inner
+            .del_onion(&onion_address)
+            .await
+            .map_err(|e| anyhow!("Could not remove onion service: {:#?}", e))
+    }
+
+    /// Checks that the control connection is still alive.
+    pub async fn assert_connected(&mut self) -> Result<()> {
+        self.inner
+            .get_info("version")
+            .await
+            .map_err(|e| anyhow!("Lost connection to Tor control port: {:#?}", e))?;
+
+        Ok(())
+    }
+}
+
+/// Registers a detached service again whenever the control connection is lost
+/// until the returned future is dropped.
+///
+/// A detached service survives the control connection, but Tor forgets it
+/// whenever Tor itself restarts. The connection is checked periodically and
+/// once it is lost, `register` is retried until Tor is back.
+pub async fn supervise<F, Fut, E>(
+    service: &str,
+    client: Option<AuthenticatedClient>,
+    mut register: F,
+) where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::result::Result<AuthenticatedClient, E>>,
+    E: Into<anyhow::Error>,
+{
+    let mut client = client;
+
+    loop {
+        tokio::time::sleep(SUPERVISE_INTERVAL).await;
+
+        if let Some(connected) = client.as_mut() {
+            match connected.assert_connected().await {
+                Ok(()) => continue,
+                Err(error) => {
+                    tracing::warn!("{:#}, registering {} again", error, service);
+                    client = None;
+                }
+            }
+        }
+
+        match register().await {
+            Ok(registered) => {
+                tracing::info!("Registered {} again", service);
+                client = Some(registered);
+            }
+            Err(error) => {
+                tracing::warn!(
+                    "Failed to register {}, retrying in {}s: {:#}",
+                    service,
+                    SUPERVISE_INTERVAL.as_secs(),
+                    error.into()
+                );
+            }
+        }
+    }
 }
@@ -240,6 +240,7 @@ async fn start_alice(
         latest_rate,
         resume_only,
         env_config,
+        None,
     )
     .unwrap();
     swarm.listen_on(listen_address).unwrap();
@@ -305,6 +306,7 @@ async fn init_test_wallets(
             .expect("Could not create extended private key from seed"),
         env_config,
         1,
+        None,
     )
     .await
     .expect("could not init btc wallet");
@@ -415,6 +417,7 @@ impl BobParams {
             self.env_config,
             handle,
             self.monero_wallet.get_main_address(),
+            bob::LockSigning::InternalWallet,
         )?;
 
         Ok((swap, event_loop))
@@ -438,6 +441,7 @@ impl BobParams {
             handle,
             self.monero_wallet.get_main_address(),
             btc_amount,
+            bob::LockSigning::InternalWallet,
         );
 
         Ok((swap, event_loop))
@@ -449,7 +453,13 @@ impl BobParams {
     ) -> Result<(bob::EventLoop, bob::EventLoopHandle)> {
         let tor_socks5_port = get_port()
             .expect("We don't care about Tor in the tests so we get a free port to disable it.");
-        let mut swarm = swarm::cli(&self.seed, self.alice_peer_id, tor_socks5_port).await?;
+        let mut swarm = swarm::cli(
+            self.seed.derive_libp2p_identity_for_swap(swap_id),
+            self.alice_peer_id,
+            tor_socks5_port,
+            false,
+        )
+        .await?;
         swarm
             .behaviour_mut()
             .add_address(self.alice_peer_id, self.alice_address.clone());